@@ -0,0 +1,11 @@
+(object
+  (_) @class.inner) @class.outer
+
+(array
+  (_) @class.inner) @class.outer
+
+(pair
+  value: (_) @parameter.inner) @parameter.outer
+
+(array
+  (_) @parameter.inner)
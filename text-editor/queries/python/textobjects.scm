@@ -0,0 +1,16 @@
+(function_definition
+  body: (_) @function.inner) @function.outer
+
+(lambda) @function.outer
+
+(class_definition
+  body: (_) @class.inner) @class.outer
+
+(parameters
+  (_) @parameter.inner)
+
+(lambda_parameters
+  (_) @parameter.inner)
+
+(argument_list
+  (_) @parameter.inner)
@@ -0,0 +1,2 @@
+(function_definition
+  body: (_) @function.inner) @function.outer
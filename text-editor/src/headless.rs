@@ -0,0 +1,163 @@
+use crate::{
+    models::{
+        document::Document,
+        editor::{Editor, Mode},
+        notation::parse_keys,
+    },
+    ui,
+};
+
+use crossterm::event::{KeyEvent, MouseEvent};
+use tui::{Terminal, backend::TestBackend, layout::Rect};
+
+/// A `Headless` editor runs without a terminal, drawing onto a virtual
+/// screen of a fixed size. It is driven the same way as the real main loop:
+/// the screen is drawn, then the next event is handled in the editor area
+/// of that draw.
+pub struct Headless {
+    editor: Editor,
+    terminal: Terminal<TestBackend>,
+    area: Rect, // editor area of the last draw
+}
+
+impl Headless {
+    pub fn new(doc: Document, width: u16, height: u16) -> Self {
+        Self::with_editor(Editor::new(doc), width, height)
+    }
+
+    /// Wraps an editor that was already set up.
+    pub fn with_editor(editor: Editor, width: u16, height: u16) -> Self {
+        let terminal =
+            Terminal::new(TestBackend::new(width, height)).expect("test backend cannot fail");
+        let mut headless = Self {
+            editor,
+            terminal,
+            area: Rect::default(),
+        };
+        headless.draw();
+        headless
+    }
+
+    /// Creates a headless editor on an unnamed buffer holding `text`.
+    pub fn with_text(text: &str, width: u16, height: u16) -> Self {
+        Self::new(Document::from_text(text), width, height)
+    }
+
+    /// Feeds the keys written in `notation` to the editor, one at a time;
+    /// see `notation::parse_keys`. Nothing is fed if the notation is invalid.
+    pub fn keys(&mut self, notation: &str) -> Result<(), String> {
+        for key in parse_keys(notation)? {
+            self.key(key);
+        }
+        Ok(())
+    }
+
+    pub fn key(&mut self, key: KeyEvent) {
+        self.editor.handle_key(key, &self.area);
+        self.draw();
+    }
+
+    /// Runs the Ex command `cmd` as if typed after `:`; see
+    /// `Editor::run_command`.
+    pub fn command(&mut self, cmd: &str) -> Result<(), String> {
+        let result = self.editor.run_command(cmd, &self.area);
+        self.draw();
+        result
+    }
+
+    /// Handles what language servers sent, as the main loop does while
+    /// waiting for input. Returns whether they sent anything.
+    pub fn poll_lsp(&mut self) -> bool {
+        let polled = self.editor.poll_lsp(&self.area);
+        self.draw();
+        polled
+    }
+
+    /// Picks up the result of a running `:make`, as the main loop does
+    /// while waiting for input. Returns whether the build was done.
+    pub fn poll_make(&mut self) -> bool {
+        let polled = self.editor.poll_make(&self.area);
+        self.draw();
+        polled
+    }
+
+    /// Feeds a mouse event, with its cell on the virtual screen.
+    pub fn mouse(&mut self, event: MouseEvent) {
+        self.editor.handle_mouse(event, &self.area);
+        self.draw();
+    }
+
+    /// Tells the editor the terminal gained or lost focus.
+    pub fn focus(&mut self, gained: bool) {
+        if gained {
+            self.editor.handle_focus_gained();
+        } else {
+            self.editor.handle_focus_lost();
+        }
+        self.draw();
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.editor.handle_paste(text, &self.area);
+        self.draw();
+    }
+
+    /// Resizes the virtual screen, as if the terminal was resized.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.terminal.backend_mut().resize(width, height);
+        let chunks = ui::screen_layout(Rect::new(0, 0, width, height), &self.editor);
+        self.editor.handle_resize(&chunks[1]);
+        self.draw();
+    }
+
+    fn draw(&mut self) {
+        let editor = &self.editor;
+        let mut area = self.area;
+        self.terminal
+            .draw(|f| area = ui::draw(f, editor))
+            .expect("test backend cannot fail");
+        self.area = area;
+    }
+
+    /// Returns the text of the active buffer.
+    pub fn text(&self) -> String {
+        self.editor.doc().text()
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.editor.cursor()
+    }
+
+    pub fn mode(&self) -> Mode {
+        *self.editor.mode()
+    }
+
+    /// Returns the rows of the virtual screen as drawn after the last event,
+    /// without trailing blanks.
+    pub fn screen(&self) -> Vec<String> {
+        let buffer = self.terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| {
+                let row: String = (0..buffer.area.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect();
+                row.trim_end().to_string()
+            })
+            .collect()
+    }
+
+    /// Returns the `(x, y)` cell the terminal cursor was left in.
+    pub fn screen_cursor(&mut self) -> (u16, u16) {
+        self.terminal
+            .get_cursor()
+            .expect("test backend cannot fail")
+    }
+
+    pub fn editor(&self) -> &Editor {
+        &self.editor
+    }
+
+    pub fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.editor
+    }
+}
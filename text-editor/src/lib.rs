@@ -0,0 +1,3 @@
+pub mod headless;
+pub mod models;
+pub mod ui;
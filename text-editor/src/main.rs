@@ -1,116 +1,273 @@
-mod models;
-
-use crate::models::{
-    document::Document,
-    editor::{Editor, Mode},
+use text_editor::{
+    headless::Headless,
+    models::{
+        document::Document,
+        editor::{Editor, OpenIn},
+        jump::{self, FileArg},
+        tab::SplitDirection,
+    },
+    ui,
 };
 
-use std::{env, io};
+use std::{
+    fs::{self, File},
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
 
-use clap::{Parser, command};
+use clap::Parser;
 use crossterm::{
-    event::{self, Event},
+    event::{
+        self, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, Event,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use tui::{
-    Frame, Terminal,
-    backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
-    widgets::{Block, Paragraph, Wrap},
-};
+use tui::{Terminal, backend::CrosstermBackend, layout::Rect};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    file: Option<String>,
+    /// Files to edit, as FILE, FILE:LINE or FILE:LINE:COL, with - reading
+    /// the buffer from stdin; +N, +/PATTERN or + opens the next file at
+    /// that line, the first match or the last line
+    files: Vec<String>,
+
+    /// Open the files in windows stacked top to bottom
+    #[arg(short = 'o', conflicts_with_all = ["vsplit", "tabs"])]
+    split: bool,
+
+    /// Open the files in windows side by side
+    #[arg(short = 'O', conflicts_with = "tabs")]
+    vsplit: bool,
+
+    /// Open the files in tab pages
+    #[arg(short = 'p')]
+    tabs: bool,
+
+    /// Write the buffer to stdout on quit, unless quitting with :cq
+    #[arg(long)]
+    stdout: bool,
+
+    /// Run the Ex commands in FILE, one per line, without the UI
+    #[arg(short = 'e', long = "script", value_name = "FILE")]
+    script: Option<PathBuf>,
+
+    /// Run an Ex command without the UI, after any script; may be repeated
+    #[arg(short = 'c', value_name = "CMD")]
+    commands: Vec<String>,
 }
 
-fn main() -> io::Result<()> {
+/// The virtual screen size batch commands run on.
+const BATCH_SIZE: (u16, u16) = (80, 24);
+/// How often language servers are checked while waiting for input.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn main() -> io::Result<ExitCode> {
     let args = Args::parse();
 
-    let doc = if let Some(path) = args.file {
-        Document::open(&path).unwrap_or_else(|_| Document::new())
-    } else {
-        Document::new()
+    if args.script.is_some() || !args.commands.is_empty() {
+        return run_batch(args);
+    }
+
+    let files = match jump::parse_file_args(&args.files) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(ExitCode::FAILURE);
+        }
     };
+    let doc = open_file(files.first()).unwrap_or_else(|_| Document::new());
 
+    // keys are read from the terminal even when stdin is a pipe, and the UI
+    // is drawn there when stdout is one
+    let mut screen: Box<dyn Write> = if io::stdout().is_terminal() {
+        Box::new(io::stdout())
+    } else {
+        Box::new(File::options().write(true).open("/dev/tty")?)
+    };
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+    execute!(
+        screen,
+        EnterAlternateScreen,
+        EnableBracketedPaste,
+        EnableFocusChange
+    )?;
+    let backend = CrosstermBackend::new(screen);
     let mut terminal = Terminal::new(backend)?;
-    let mut editor = Editor::new(doc);
+    let mut editor = start_editor(doc, &files, &args);
+    let mut mouse_captured = false;
 
     while !editor.should_quit() {
-        let mut last_size = terminal.size()?;
-        terminal.draw(|f| {
-            last_size = f.size();
-
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Min(1),    // editor area
-                    Constraint::Length(1), // status bar
-                ])
-                .split(last_size);
-
-            draw_editor(f, &chunks[0], &editor);
-            draw_status_bar(f, &chunks[1], &editor);
-        })?;
-
-        if let Event::Key(key_event) = event::read()? {
-            editor.handle_key(key_event, &last_size);
+        if editor.options().mouse != mouse_captured {
+            mouse_captured = editor.options().mouse;
+            if mouse_captured {
+                execute!(terminal.backend_mut(), EnableMouseCapture)?;
+            } else {
+                execute!(terminal.backend_mut(), DisableMouseCapture)?;
+            }
+        }
+
+        let mut editor_area = terminal.size()?;
+        terminal.draw(|f| editor_area = ui::draw(f, &editor))?;
+
+        // language servers answer in the background, what they sent is
+        // handled while waiting for input
+        let mut redraw = false;
+        while !redraw && !event::poll(POLL_INTERVAL)? {
+            redraw = editor.poll_lsp(&editor_area) | editor.poll_make(&editor_area);
+        }
+        if redraw {
+            continue;
+        }
+
+        match event::read()? {
+            Event::Key(key_event) => editor.handle_key(key_event, &editor_area),
+            Event::Mouse(mouse_event) => editor.handle_mouse(mouse_event, &editor_area),
+            Event::Paste(text) => editor.handle_paste(&text, &editor_area),
+            Event::Resize(width, height) => {
+                let chunks = ui::screen_layout(Rect::new(0, 0, width, height), &editor);
+                editor.handle_resize(&chunks[1]);
+            }
+            Event::FocusGained => editor.handle_focus_gained(),
+            Event::FocusLost => editor.handle_focus_lost(),
         }
     }
 
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    Ok(())
-}
+    if mouse_captured {
+        execute!(terminal.backend_mut(), DisableMouseCapture)?;
+    }
+    execute!(
+        terminal.backend_mut(),
+        DisableBracketedPaste,
+        DisableFocusChange,
+        LeaveAlternateScreen
+    )?;
 
-fn draw_editor<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor) {
-    let lines: Vec<String> = editor
-        .doc()
-        .lines()
-        .iter()
-        .map(|gb| gb.to_string())
-        .collect();
+    if let Some(doc) = editor.output() {
+        write_stdout(doc)?;
+    }
+    Ok(if editor.aborted() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
 
-    let text = lines.join("\n");
+/// Opens `file`, reading it from stdin if it is `-`, or else a new buffer.
+fn open_file(file: Option<&FileArg>) -> io::Result<Document> {
+    match file.map(|f| f.path.as_str()) {
+        Some("-") => Ok(Document::from_text(&io::read_to_string(io::stdin())?)),
+        Some(path) => Document::open(path),
+        None => Ok(Document::new()),
+    }
+}
 
-    let paragraph = Paragraph::new(text).wrap(Wrap { trim: false });
+/// Creates the editor on `doc`, the first of `files`, and opens the rest
+/// as the arguments say.
+fn start_editor(doc: Document, files: &[FileArg], args: &Args) -> Editor {
+    let mut editor = Editor::new(doc);
+    if args.stdout {
+        editor.set_output(Some(0));
+    }
+    if let Some(jump) = files.first().and_then(|f| f.jump.as_ref()) {
+        editor.jump_to(jump);
+    }
 
-    f.render_widget(paragraph, *area);
+    let open_in = if args.split {
+        OpenIn::Splits(SplitDirection::Horizontal)
+    } else if args.vsplit {
+        OpenIn::Splits(SplitDirection::Vertical)
+    } else if args.tabs {
+        OpenIn::Tabs
+    } else {
+        OpenIn::Buffers
+    };
+    editor.open_files(files.get(1..).unwrap_or_default(), open_in);
+    editor
+}
 
-    let (y, x) = editor.doc_to_screen(&area);
-    f.set_cursor(x, y);
+/// Writes `doc` to stdout with its line endings, ending the last line too.
+fn write_stdout(doc: &Document) -> io::Result<()> {
+    let ending = doc.line_ending().as_str();
+    let mut stdout = io::stdout().lock();
+    for line in doc.lines() {
+        write!(stdout, "{}{}", line, ending)?;
+    }
+    stdout.flush()
 }
 
-fn draw_status_bar<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor) {
-    let status = if let Some(msg) = editor.status_msg() {
-        msg.clone()
-    } else if *(editor.mode()) == Mode::Command {
-        format!(":{}", editor.command_buffer())
-    } else {
-        format!(
-            " {} | {} | {}:{} ",
-            editor.mode().as_str(),
-            editor.doc().file_name(),
-            editor.cursor().0 + 1,
-            editor.cursor().1 + 1
-        )
+/// Runs the script and `-c` commands against the file without entering raw
+/// mode. Failed commands are reported on stderr and make the exit code
+/// non-zero. Unless a command quit, the buffer is then printed to stdout.
+/// With `--stdout` it is printed on quit too.
+fn run_batch(args: Args) -> io::Result<ExitCode> {
+    let files = match jump::parse_file_args(&args.files) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let doc = match open_file(files.first()) {
+        Ok(doc) => doc,
+        Err(e) => {
+            eprintln!("{}: {}", files[0].path, e);
+            return Ok(ExitCode::FAILURE);
+        }
     };
 
-    let paragraph = Paragraph::new(status)
-        .style(
-            Style::default()
-                .fg(Color::Black)
-                .bg(Color::White)
-                .add_modifier(Modifier::BOLD),
-        )
-        .block(Block::default());
+    let mut commands = Vec::new();
+    if let Some(script) = &args.script {
+        let text = match fs::read_to_string(script) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{}: {}", script.display(), e);
+                return Ok(ExitCode::FAILURE);
+            }
+        };
+        // blank lines and `"` comments are skipped, as in a vimrc
+        commands.extend(
+            text.lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty() && !line.starts_with('"')),
+        );
+    }
+    commands.extend(args.commands.iter().cloned());
+
+    let (width, height) = BATCH_SIZE;
+    let mut editor = start_editor(doc, &files, &args);
+    for msg in editor.messages() {
+        eprintln!("{}", msg.text);
+    }
+    // a batch run does not wait for language servers, so none are started
+    editor.options_mut().lsp = false;
+    let mut headless = Headless::with_editor(editor, width, height);
+    let mut failed = false;
+    for cmd in &commands {
+        if headless.editor().should_quit() {
+            break;
+        }
+        if let Err(e) = headless.command(cmd.trim_start_matches(':')) {
+            eprintln!("{}: {}", cmd, e);
+            failed = true;
+        }
+    }
+
+    let editor = headless.editor();
+    if !editor.should_quit() {
+        write_stdout(editor.doc())?;
+    } else if let Some(doc) = editor.output() {
+        write_stdout(doc)?;
+    }
 
-    f.render_widget(paragraph, *area);
+    Ok(if failed || editor.aborted() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
 }
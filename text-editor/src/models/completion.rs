@@ -0,0 +1,414 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::models::document::Document;
+
+/// How many lines of a file or directory the completion preview shows.
+pub const PREVIEW_LINES: usize = 10;
+
+/// A `Source` is where completion candidates come from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Source {
+    Lsp,
+    Buffer,
+    Path,
+}
+
+/// The sources used by default, in the order their candidates rank on
+/// equal scores.
+pub const DEFAULT_SOURCES: &str = "lsp,buffer,path";
+
+impl Source {
+    /// Parses a comma-separated list of sources such as `lsp,buffer`.
+    pub fn parse_list(spec: &str) -> Result<Vec<Self>, String> {
+        spec.split(',')
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Ok(match name {
+                    "lsp" => Source::Lsp,
+                    "buffer" => Source::Buffer,
+                    "path" => Source::Path,
+                    _ => return Err(format!("Unknown completion source: {}", name)),
+                })
+            })
+            .collect()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Lsp => "lsp",
+            Source::Buffer => "buffer",
+            Source::Path => "path",
+        }
+    }
+}
+
+/// A `Candidate` is a text the word before the cursor can be completed to.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub text: String,
+    pub source: Source,
+    pub detail: String,        // shown next to the text
+    pub info: Vec<String>,     // shown in the preview
+    pub path: Option<PathBuf>, // of a path candidate, previewed from disk
+}
+
+impl Candidate {
+    pub fn new(text: impl Into<String>, source: Source) -> Self {
+        Self {
+            text: text.into(),
+            source,
+            detail: String::new(),
+            info: Vec::new(),
+            path: None,
+        }
+    }
+}
+
+/// A `Completion` is the completion popup of insert mode: the candidates
+/// for the text typed since `start`, ranked by how well they match it.
+pub struct Completion {
+    pub start: (usize, usize),
+    pub waiting: bool, // for the language server's candidates
+    pub preview: Vec<String>,
+    sources: Vec<Source>,
+    candidates: Vec<Candidate>,
+    matches: Vec<usize>,     // indices of the matching candidates, best first
+    selected: Option<usize>, // index into `matches`
+    wanted: Option<isize>,   // a selection asked for while nothing matched
+}
+
+impl Completion {
+    pub fn new(start: (usize, usize), sources: Vec<Source>) -> Self {
+        Self {
+            start,
+            waiting: false,
+            preview: Vec::new(),
+            sources,
+            candidates: Vec::new(),
+            matches: Vec::new(),
+            selected: None,
+            wanted: None,
+        }
+    }
+
+    /// Adds `candidates`, skipping texts already offered.
+    pub fn add(&mut self, candidates: Vec<Candidate>) {
+        let mut seen: HashSet<String> = self.candidates.iter().map(|c| c.text.clone()).collect();
+        for candidate in candidates {
+            if seen.insert(candidate.text.clone()) {
+                self.candidates.push(candidate);
+            }
+        }
+    }
+
+    /// Ranks the candidates matching `prefix`, keeping the selected one
+    /// selected if it still matches.
+    pub fn filter(&mut self, prefix: &str) {
+        let selected = self.selected.map(|i| self.matches[i]);
+        let rank = |source: Source| self.sources.iter().position(|s| *s == source);
+        let mut scored: Vec<(i64, usize)> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| Some((fuzzy_score(prefix, &c.text)?, i)))
+            .collect();
+        // a stable sort keeps the order sources gave on equal ranks
+        scored.sort_by_key(|&(score, i)| (-score, rank(self.candidates[i].source)));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+
+        self.selected = selected.map(|s| self.matches.iter().position(|&i| i == s).unwrap_or(0));
+        if self.matches.is_empty() {
+            self.selected = None;
+        } else if let Some(delta) = self.wanted.take() {
+            self.select(delta);
+        }
+    }
+
+    /// Moves the selection `delta` matches on, wrapping around. With nothing
+    /// selected, the first or last match is, once there are matches.
+    pub fn select(&mut self, delta: isize) {
+        let n = self.matches.len() as isize;
+        if n == 0 {
+            self.wanted = Some(delta);
+            return;
+        }
+        let next = match self.selected {
+            Some(i) => i as isize + delta,
+            None if delta > 0 => delta - 1,
+            None => n + delta,
+        };
+        self.selected = Some(next.rem_euclid(n) as usize);
+    }
+
+    /// Returns the matching candidates, best first.
+    pub fn matches(&self) -> impl Iterator<Item = &Candidate> {
+        self.matches.iter().map(|&i| &self.candidates[i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Returns the index of the selected match.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn selected_candidate(&self) -> Option<&Candidate> {
+        self.selected.map(|i| &self.candidates[self.matches[i]])
+    }
+}
+
+/// Scores how well `text` matches `pattern` as a fuzzy subsequence, higher
+/// being better, or returns `None` if it does not. Matches at the start, at
+/// word boundaries and in runs score higher. The match ignores case unless
+/// `pattern` has an uppercase letter.
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let case_sensitive = pattern.chars().any(char::is_uppercase);
+    let same = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    let mut pattern = pattern.chars().peekable();
+    let mut score = 0;
+    let mut prev: Option<char> = None;
+    let mut run = false;
+    for c in text.chars() {
+        let Some(&p) = pattern.peek() else {
+            break;
+        };
+        if same(p, c) {
+            pattern.next();
+            score += 1;
+            if run {
+                score += 4;
+            }
+            score += match prev {
+                None => 8,
+                Some(prev) if !prev.is_alphanumeric() => 6,
+                Some(prev) if prev.is_lowercase() && c.is_uppercase() => 6,
+                _ => 0,
+            };
+            run = true;
+        } else {
+            // each gap after a match costs a little
+            if run {
+                score -= 1;
+            }
+            run = false;
+        }
+        prev = Some(c);
+    }
+
+    if pattern.peek().is_some() {
+        return None;
+    }
+    Some(score)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the words of `buffers`, those of `current` first, skipping the
+/// word that starts at `skip` in it, which is the one being completed.
+pub fn buffer_words(buffers: &[Document], current: usize, skip: (usize, usize)) -> Vec<Candidate> {
+    let order = std::iter::once(current).chain((0..buffers.len()).filter(|&i| i != current));
+    let mut words = Vec::new();
+    let mut seen = HashSet::new();
+    for buffer in order {
+        let doc = &buffers[buffer];
+        for (row, line) in doc.lines().iter().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            let mut col = 0;
+            while col < chars.len() {
+                if !is_word_char(chars[col]) {
+                    col += 1;
+                    continue;
+                }
+                let end = (col..chars.len())
+                    .find(|&i| !is_word_char(chars[i]))
+                    .unwrap_or(chars.len());
+                let word: String = chars[col..end].iter().collect();
+                if end - col > 1
+                    && (buffer, (row, col)) != (current, skip)
+                    && seen.insert(word.clone())
+                {
+                    let mut candidate = Candidate::new(word, Source::Buffer);
+                    candidate.info = vec![
+                        format!("{}:{}", doc.file_name(), row + 1),
+                        line.to_string().trim().to_string(),
+                    ];
+                    words.push(candidate);
+                }
+                col = end;
+            }
+        }
+    }
+    words
+}
+
+/// Returns the entries of directory `dir`, relative to `base` unless it is
+/// absolute or starts with `~/`, with directories ending in `/`.
+pub fn path_candidates(dir: &str, base: &Path) -> Vec<Candidate> {
+    let Some(path) = resolve_dir(dir, base) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&path) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<Candidate> = entries
+        .flatten()
+        .map(|entry| {
+            let mut name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if is_dir {
+                name.push('/');
+            }
+            let mut candidate = Candidate::new(name, Source::Path);
+            candidate.detail = if is_dir { "dir" } else { "file" }.to_string();
+            candidate.path = Some(entry.path());
+            candidate
+        })
+        .collect();
+    // hidden files last, then by name
+    candidates.sort_by(|a, b| {
+        (a.text.starts_with('.'), &a.text).cmp(&(b.text.starts_with('.'), &b.text))
+    });
+    candidates
+}
+
+/// Returns the directory `dir` names, or `None` if it is not one.
+pub fn resolve_dir(dir: &str, base: &Path) -> Option<PathBuf> {
+    let path = match dir.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+        None => base.join(dir),
+    };
+    path.is_dir().then_some(path)
+}
+
+/// Returns the first `lines` lines of the file at `path`, or the first
+/// entries of the directory.
+pub fn path_preview(path: &Path, lines: usize) -> Vec<String> {
+    if path.is_dir() {
+        return match fs::read_dir(path) {
+            Ok(entries) => {
+                let mut names: Vec<String> = entries
+                    .flatten()
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect();
+                names.sort();
+                names.truncate(lines);
+                names
+            }
+            Err(e) => vec![e.to_string()],
+        };
+    }
+    match fs::File::open(path) {
+        Ok(file) => BufReader::new(file)
+            .lines()
+            .take(lines)
+            .map_while(Result::ok)
+            .collect(),
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+/// Returns the candidates of an LSP completion response, in the order the
+/// server sorted them.
+pub fn lsp_candidates(result: &Value) -> Vec<Candidate> {
+    let items = match result {
+        Value::Array(items) => items.as_slice(),
+        _ => result["items"].as_array().map_or(&[][..], Vec::as_slice),
+    };
+    let mut items: Vec<&Value> = items.iter().collect();
+    items.sort_by_key(|item| {
+        item["sortText"]
+            .as_str()
+            .or(item["label"].as_str())
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    items
+        .into_iter()
+        .filter_map(|item| {
+            let text = item["textEdit"]["newText"]
+                .as_str()
+                .or(item["insertText"].as_str())
+                .or(item["label"].as_str())?;
+            let mut candidate = Candidate::new(text, Source::Lsp);
+            candidate.detail = item["detail"].as_str().unwrap_or_default().to_string();
+            let documentation = match &item["documentation"] {
+                Value::String(s) => s.as_str(),
+                docs => docs["value"].as_str().unwrap_or_default(),
+            };
+            candidate.info = std::iter::once(candidate.detail.as_str())
+                .filter(|d| !d.is_empty())
+                .chain(documentation.lines())
+                .map(str::to_string)
+                .collect();
+            Some(candidate)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_matches_subsequences_ignoring_case() {
+        assert!(fuzzy_score("fb", "foo_bar").is_some());
+        assert!(fuzzy_score("fB", "fooBar").is_some());
+        assert!(fuzzy_score("fB", "foobar").is_none());
+        assert!(fuzzy_score("bf", "foo_bar").is_none());
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn prefixes_and_word_starts_rank_first() {
+        let score = |text| fuzzy_score("ab", text).unwrap();
+        assert!(score("abc") > score("xabc"));
+        assert!(score("x_abc") > score("xxabc"));
+        assert!(score("a_b") > score("axb"));
+    }
+
+    #[test]
+    fn filter_ranks_and_keeps_the_selection() {
+        let mut completion = Completion::new((0, 0), vec![Source::Lsp, Source::Buffer]);
+        completion.add(vec![
+            Candidate::new("format", Source::Buffer),
+            Candidate::new("from_str", Source::Buffer),
+            Candidate::new("from_str", Source::Lsp),
+            Candidate::new("for_each", Source::Lsp),
+        ]);
+        completion.filter("fo");
+        let texts: Vec<&str> = completion.matches().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, ["for_each", "format", "from_str"]);
+
+        completion.select(1);
+        completion.select(1);
+        assert_eq!(completion.selected_candidate().unwrap().text, "format");
+        completion.filter("fmt");
+        assert_eq!(completion.selected_candidate().unwrap().text, "format");
+        completion.filter("xyz");
+        assert_eq!(completion.selected(), None);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::models::message::Severity;
+
+/// A `Diagnostic` is a problem reported for a range of a document, such as
+/// a compiler error from a language server.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub severity: Severity,
+    pub message: String,
+    pub source: Option<String>,
+}
+
+impl Diagnostic {
+    /// Returns the theme scope the diagnostic is drawn with.
+    pub fn scope(&self) -> &'static str {
+        scope(self.severity)
+    }
+
+    /// Returns the sign shown in the gutter for the diagnostic.
+    pub fn sign(&self) -> &'static str {
+        sign(self.severity)
+    }
+}
+
+/// Returns the theme scope problems of `severity` are drawn with, such as
+/// diagnostics and compiler messages in the quickfix list.
+pub fn scope(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "diagnostic.info",
+        Severity::Warn => "diagnostic.warn",
+        Severity::Error => "diagnostic.error",
+    }
+}
+
+/// Returns the sign shown in the gutter for problems of `severity`.
+pub fn sign(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "I",
+        Severity::Warn => "W",
+        Severity::Error => "E",
+    }
+}
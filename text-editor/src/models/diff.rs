@@ -0,0 +1,226 @@
+use std::ops::Range;
+
+use similar::{Algorithm, DiffTag, capture_diff_slices};
+
+/// A `Hunk` is a run of lines that differs between two versions of a text:
+/// the rows `old` were replaced by the rows `new`. Either may be empty, for
+/// lines only added or only removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+impl Hunk {
+    /// Returns the rows of the new text the hunk is shown on: the lines it
+    /// added or changed, or the line above lines it only removed.
+    pub fn rows(&self) -> Range<usize> {
+        if self.new.is_empty() {
+            let row = self.new.start.saturating_sub(1);
+            row..row + 1
+        } else {
+            self.new.clone()
+        }
+    }
+}
+
+/// Compares two versions of a text line by line, returning the hunks that
+/// differ in order.
+pub fn hunks(old: &[impl AsRef<str>], new: &[impl AsRef<str>]) -> Vec<Hunk> {
+    let old: Vec<&str> = old.iter().map(AsRef::as_ref).collect();
+    let new: Vec<&str> = new.iter().map(AsRef::as_ref).collect();
+    let equal = capture_diff_slices(Algorithm::Myers, &old, &new)
+        .into_iter()
+        .map(|op| op.as_tag_tuple())
+        .filter(|(tag, _, _)| *tag == DiffTag::Equal)
+        .map(|(_, old, new)| (old, new))
+        .chain([(old.len()..old.len(), new.len()..new.len())]);
+
+    // the hunks are the gaps between the runs of equal lines, as the other
+    // ops do not always give their rows in the other text
+    let mut hunks = Vec::new();
+    let (mut old_row, mut new_row) = (0, 0);
+    for (old, new) in equal {
+        if old.start > old_row || new.start > new_row {
+            hunks.push(Hunk {
+                old: old_row..old.start,
+                new: new_row..new.start,
+            });
+        }
+        (old_row, new_row) = (old.end, new.end);
+    }
+    hunks
+}
+
+/// Returns where old row `row` is in the new text: moved along by the hunks
+/// above it, or kept at the same offset into the hunk it was changed by. A
+/// removed row goes to the row after the removed lines, which is past the
+/// end if they were the last.
+pub fn map_row(hunks: &[Hunk], row: usize) -> usize {
+    let mut mapped = row;
+    for hunk in hunks {
+        if row < hunk.old.start {
+            break;
+        }
+        if row < hunk.old.end {
+            let offset = (row - hunk.old.start).min(hunk.new.len().saturating_sub(1));
+            return hunk.new.start + offset;
+        }
+        mapped = row - hunk.old.end + hunk.new.end;
+    }
+    mapped
+}
+
+/// Formats the `@@ -l,s +l,s @@` line heading a hunk in a unified diff,
+/// counting lines from 1 as diff does.
+pub fn hunk_header(old: &Range<usize>, new: &Range<usize>) -> String {
+    // an empty side gives the line before it
+    let start = |rows: &Range<usize>| rows.start + usize::from(!rows.is_empty());
+    format!(
+        "@@ -{},{} +{},{} @@",
+        start(old),
+        old.len(),
+        start(new),
+        new.len()
+    )
+}
+
+/// Writes the differences between two versions of a text as a unified diff
+/// without its file headers, with `context` unchanged lines around each
+/// hunk. Hunks closer than that share their context.
+pub fn unified(old: &[impl AsRef<str>], new: &[impl AsRef<str>], context: usize) -> Vec<String> {
+    let hunks = hunks(old, new);
+    let mut lines = Vec::new();
+    let mut i = 0;
+    while i < hunks.len() {
+        let mut end = i + 1;
+        while end < hunks.len() && hunks[end].old.start - hunks[end - 1].old.end <= 2 * context {
+            end += 1;
+        }
+        let (first, last) = (&hunks[i], &hunks[end - 1]);
+        let before = first.old.start.min(context);
+        let after = (old.len() - last.old.end).min(context);
+        lines.push(hunk_header(
+            &(first.old.start - before..last.old.end + after),
+            &(first.new.start - before..last.new.end + after),
+        ));
+
+        let mut row = first.old.start - before;
+        for hunk in &hunks[i..end] {
+            lines.extend(
+                old[row..hunk.old.start]
+                    .iter()
+                    .map(|l| format!(" {}", l.as_ref())),
+            );
+            lines.extend(
+                old[hunk.old.clone()]
+                    .iter()
+                    .map(|l| format!("-{}", l.as_ref())),
+            );
+            lines.extend(
+                new[hunk.new.clone()]
+                    .iter()
+                    .map(|l| format!("+{}", l.as_ref())),
+            );
+            row = hunk.old.end;
+        }
+        lines.extend(
+            old[row..row + after]
+                .iter()
+                .map(|l| format!(" {}", l.as_ref())),
+        );
+        i = end;
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::document::Document;
+    use proptest::prelude::*;
+
+    #[test]
+    fn hunks_cover_only_the_changed_lines() {
+        let old = ["a", "b", "c", "d", "e"];
+        let new = ["a", "B", "c", "x", "y", "d"];
+        let hunks = hunks(&old, &new);
+        assert_eq!(
+            hunks,
+            [
+                Hunk {
+                    old: 1..2,
+                    new: 1..2
+                },
+                Hunk {
+                    old: 3..3,
+                    new: 3..5
+                },
+                Hunk {
+                    old: 4..5,
+                    new: 6..6
+                },
+            ]
+        );
+        assert_eq!(map_row(&hunks, 0), 0);
+        assert_eq!(map_row(&hunks, 1), 1);
+        assert_eq!(map_row(&hunks, 3), 5);
+        assert_eq!(map_row(&hunks, 4), 6);
+    }
+
+    #[test]
+    fn hunk_headers_name_the_line_before_an_empty_side() {
+        assert_eq!(hunk_header(&(1..2), &(1..3)), "@@ -2,1 +2,2 @@");
+        assert_eq!(hunk_header(&(2..2), &(2..4)), "@@ -2,0 +3,2 @@");
+        assert_eq!(hunk_header(&(0..1), &(0..0)), "@@ -1,1 +0,0 @@");
+    }
+
+    #[test]
+    fn unified_diffs_share_context_between_close_hunks() {
+        let old = ["a", "b", "c", "d", "e", "f", "g", "h"];
+        let new = ["a", "B", "c", "d", "e", "f", "g"];
+        assert_eq!(
+            unified(&old, &new, 1),
+            [
+                "@@ -1,3 +1,3 @@",
+                " a",
+                "-b",
+                "+B",
+                " c",
+                "@@ -7,2 +7,1 @@",
+                " g",
+                "-h"
+            ]
+        );
+        assert_eq!(
+            unified(&old, &new, 3),
+            [
+                "@@ -1,8 +1,7 @@",
+                " a",
+                "-b",
+                "+B",
+                " c",
+                " d",
+                " e",
+                " f",
+                " g",
+                "-h",
+            ]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn setting_the_text_edits_the_document_into_it(
+            old in prop::collection::vec("[ab]{0,2}", 1..8),
+            new in prop::collection::vec("[ab]{0,2}", 1..8),
+        ) {
+            let mut doc = Document::from_text(&old.join("\n"));
+            let hunks = doc.set_text(&new.join("\n"));
+            prop_assert_eq!(doc.text(), new.join("\n"));
+            for row in 0..old.len() {
+                prop_assert!(map_row(&hunks, row) <= new.len());
+            }
+        }
+    }
+}
@@ -1,11 +1,28 @@
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
     io::{self, BufRead, BufReader, Write},
     path::{Path, PathBuf},
-    time::SystemTime,
+    rc::Rc,
+    time::{Duration, SystemTime},
 };
 
-use crate::models::gap_buffer::GapBuffer;
+use crate::models::{
+    diagnostic::{self, Diagnostic},
+    diff::{self, Hunk},
+    gap_buffer::GapBuffer,
+    highlighter::{HighlightSpan, Highlighter},
+    history::History,
+    lsp::{TextEdit, utf16_col},
+    message::Severity,
+    sign::{Sign, SignColumn},
+    syntax::Grammar,
+};
+
+#[cfg(feature = "tree-sitter")]
+use crate::models::syntax_tree::SyntaxTree;
+
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
 
 #[derive(Debug)]
 pub enum DocumentError {
@@ -13,6 +30,71 @@ pub enum DocumentError {
     ColOutOfBounds,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineEnding {
+    Unix,
+    Dos,
+}
+
+impl LineEnding {
+    /// Returns the line ending `text` uses: DOS if any line ends in CRLF.
+    pub fn detect(text: &str) -> Self {
+        if text.contains("\r\n") {
+            LineEnding::Dos
+        } else {
+            LineEnding::Unix
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Unix => "\n",
+            LineEnding::Dos => "\r\n",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineEnding::Unix => "unix",
+            LineEnding::Dos => "dos",
+        }
+    }
+}
+
+/// A `Change` is a single edit applied to a `Document`: `removed` was
+/// replaced by `inserted` at `start`.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub start: (usize, usize), // (row, col)
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Change {
+    pub fn old_end(&self) -> (usize, usize) {
+        Self::end_of(self.start, &self.removed)
+    }
+
+    /// Returns the change that undoes this one.
+    pub fn inverted(&self) -> Change {
+        Change {
+            start: self.start,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    fn end_of(start: (usize, usize), text: &str) -> (usize, usize) {
+        match text.rsplit_once('\n') {
+            Some((before, last)) => (
+                start.0 + before.matches('\n').count() + 1,
+                last.chars().count(),
+            ),
+            None => (start.0, start.1 + text.chars().count()),
+        }
+    }
+}
+
 /// A `Document` represents a text document in the text editor.
 pub struct Document {
     lines: Vec<GapBuffer>,
@@ -20,7 +102,29 @@ pub struct Document {
     temp_path: PathBuf,
     swap_path: Option<PathBuf>,
     dirty: bool,
+    readonly: bool,
+    line_ending: LineEnding,
+    modified: Option<SystemTime>, // of the file on disk when last read or written
     last_swap: SystemTime,
+    signs: SignColumn,
+    marks: BTreeMap<char, (usize, usize)>,
+    highlighter: Option<Highlighter>,
+    #[cfg(feature = "tree-sitter")]
+    syntax_tree: Option<SyntaxTree>,
+    history: History,
+    version: i32,
+    edits: Option<Vec<TextEdit>>, // since the last `take_edits`, once tracked
+    diagnostics: Vec<Diagnostic>,
+    diff_base: Option<Vec<String>>, // the lines of the file in git
+    hunks: Vec<Hunk>,               // changed from `diff_base`
+    hunks_version: Option<i32>,     // of the text `hunks` were found in
+    blame: Option<Vec<String>>,     // for each line, while shown
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Document {
@@ -31,26 +135,80 @@ impl Document {
             temp_path: PathBuf::new(),
             swap_path: None,
             dirty: false,
+            readonly: false,
+            line_ending: LineEnding::Unix,
+            modified: None,
             last_swap: SystemTime::now(),
+            signs: SignColumn::new(),
+            marks: BTreeMap::new(),
+            highlighter: None,
+            #[cfg(feature = "tree-sitter")]
+            syntax_tree: None,
+            history: History::new(),
+            version: 0,
+            edits: None,
+            diagnostics: Vec::new(),
+            diff_base: None,
+            hunks: Vec::new(),
+            hunks_version: None,
+            blame: None,
         }
     }
 
+    /// Creates an unnamed document holding `text`, read the way a file is:
+    /// CRLF line endings are detected and a final line ending is dropped.
+    pub fn from_text(text: &str) -> Self {
+        let mut doc = Self::new();
+        doc.line_ending = LineEnding::detect(text);
+        let text = text.replace("\r\n", "\n");
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        doc.lines = text
+            .split('\n')
+            .map(|line| {
+                let mut gb = GapBuffer::new();
+                gb.insert_str(0, line);
+                gb
+            })
+            .collect();
+        doc
+    }
+
     pub fn open(path: &str) -> io::Result<Self> {
         let original = PathBuf::from(path);
 
         let swap = Self::swap_path_for(&original);
 
         if swap.exists() {
-            return Err(io::Error::new(io::ErrorKind::Other, "Swap file exists"));
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "Swap file exists",
+            ));
         }
 
-        File::create(&swap)?;
+        let mut swap_file = File::create(&swap)?;
+        writeln!(swap_file, "# SWAP")?;
+        writeln!(swap_file, "# pid={}", std::process::id())?;
+
+        // a file that cannot be read leaves no swap file behind
+        Self::read(original, swap.clone()).inspect_err(|_| {
+            let _ = fs::remove_file(&swap);
+        })
+    }
 
+    fn read(original: PathBuf, swap: PathBuf) -> io::Result<Self> {
         let mut temp = original.clone();
         temp.set_extension("tmp");
 
         fs::copy(&original, &temp)?;
 
+        let metadata = fs::metadata(&original)?;
+        let readonly = metadata.permissions().readonly();
+        let line_ending = if fs::read(&temp)?.windows(2).any(|w| w == b"\r\n") {
+            LineEnding::Dos
+        } else {
+            LineEnding::Unix
+        };
+
         let file = File::open(&temp)?;
         let reader = BufReader::new(file);
 
@@ -72,37 +230,125 @@ impl Document {
             original_path: Some(original),
             temp_path: temp,
             dirty: false,
+            readonly,
+            line_ending,
+            modified: metadata.modified().ok(),
             swap_path: Some(swap),
             last_swap: SystemTime::now(),
+            signs: SignColumn::new(),
+            marks: BTreeMap::new(),
+            highlighter: None,
+            #[cfg(feature = "tree-sitter")]
+            syntax_tree: None,
+            history: History::new(),
+            version: 0,
+            edits: None,
+            diagnostics: Vec::new(),
+            diff_base: None,
+            hunks: Vec::new(),
+            hunks_version: None,
+            blame: None,
         })
     }
 
+    /// Writes the text to the file, or to `new_name`, which the document
+    /// then takes as its path along with its swap and temp files. Nothing
+    /// about the document changes if the file cannot be written.
     pub fn save(&mut self, new_name: Option<&str>) -> io::Result<()> {
-        if let Some(name) = new_name {
-            self.original_path = Some(PathBuf::from(name));
+        let original = match new_name.map(PathBuf::from).or(self.original_path.clone()) {
+            Some(path) => path,
+            None => return Err(io::Error::other("No file name")),
+        };
+
+        if self.readonly && new_name.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "File is read-only (add ! to override)",
+            ));
         }
 
-        let original = match &self.original_path {
-            Some(path) => path.clone(),
-            None => return Err(io::Error::new(io::ErrorKind::Other, "No file name")),
+        // a new name, or a document that never had a file, needs a temp
+        // file next to where it is written
+        let temp_path = if new_name.is_some() || self.temp_path.as_os_str().is_empty() {
+            original.with_extension("tmp")
+        } else {
+            self.temp_path.clone()
         };
 
-        let mut temp = File::create(&self.temp_path)?;
+        let mut temp = File::create(&temp_path)?;
 
         for (i, line) in self.lines.iter().enumerate() {
             temp.write_all(line.to_string().as_bytes())?;
             if i + 1 < self.lines.len() {
-                temp.write_all(b"\n")?;
+                temp.write_all(self.line_ending.as_str().as_bytes())?;
             }
         }
 
         temp.sync_all()?;
 
-        fs::rename(&self.temp_path, &original)?;
+        fs::rename(&temp_path, &original)?;
 
-        fs::copy(&original, &self.temp_path)?;
+        fs::copy(&original, &temp_path)?;
 
+        if temp_path != self.temp_path {
+            let _ = fs::remove_file(&self.temp_path);
+            self.temp_path = temp_path;
+        }
+        if new_name.is_some() {
+            self.set_path(&original);
+        }
+        self.history.mark_saved();
         self.dirty = false;
+        self.readonly = false;
+        self.modified = fs::metadata(&original)?.modified().ok();
+
+        Ok(())
+    }
+
+    /// Returns whether the file was modified on disk since it was last read
+    /// or written.
+    pub fn changed_on_disk(&self) -> bool {
+        let Some(path) = &self.original_path else {
+            return false;
+        };
+        match fs::metadata(path).and_then(|m| m.modified()) {
+            Ok(modified) => self.modified.is_some_and(|m| m != modified),
+            Err(_) => false,
+        }
+    }
+
+    /// Re-reads the file from disk, replacing the whole text as one change
+    /// so the reload can be undone.
+    pub fn reload(&mut self) -> io::Result<()> {
+        let Some(original) = self.original_path.clone() else {
+            return Err(io::Error::other("No file name"));
+        };
+
+        let metadata = fs::metadata(&original)?;
+        let text = fs::read_to_string(&original)?;
+        let line_ending = LineEnding::detect(&text);
+        let text = text.replace("\r\n", "\n");
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        if !self.temp_path.as_os_str().is_empty() {
+            fs::copy(&original, &self.temp_path)?;
+        }
+
+        let last = self.lines.len() - 1;
+        let end = (last, self.lines[last].len());
+        let removed = self.text_range((0, 0), end);
+        self.history.commit();
+        self.splice((0, 0), end, text);
+        self.changed(Change {
+            start: (0, 0),
+            removed,
+            inserted: text.to_string(),
+        });
+        self.history.mark_saved();
+
+        self.line_ending = line_ending;
+        self.dirty = false;
+        self.readonly = metadata.permissions().readonly();
+        self.modified = metadata.modified().ok();
 
         Ok(())
     }
@@ -120,7 +366,11 @@ impl Document {
         let r = line.split(col);
         self.lines.insert(row + 1, r);
 
-        self.dirty = true;
+        self.changed(Change {
+            start: (row, col),
+            removed: String::new(),
+            inserted: "\n".to_string(),
+        });
 
         Ok(())
     }
@@ -137,7 +387,11 @@ impl Document {
 
         line.insert_char(col, c);
 
-        self.dirty = true;
+        self.changed(Change {
+            start: (row, col),
+            removed: String::new(),
+            inserted: c.to_string(),
+        });
 
         Ok(())
     }
@@ -155,7 +409,14 @@ impl Document {
                 .get_mut(row - 1)
                 .ok_or(DocumentError::RowOutOfBounds)?;
 
+            let prev_len = previous_line.len();
             previous_line.merge(current_line);
+
+            self.changed(Change {
+                start: (row - 1, prev_len),
+                removed: "\n".to_string(),
+                inserted: String::new(),
+            });
         } else {
             let line = self
                 .lines
@@ -165,12 +426,256 @@ impl Document {
             if col > line.len() {
                 return Err(DocumentError::ColOutOfBounds);
             }
-            line.delete(col - 1);
+            let removed = line.chars().nth(col - 1).unwrap_or_default();
+            line.delete(col);
+
+            self.changed(Change {
+                start: (row, col - 1),
+                removed: removed.to_string(),
+                inserted: String::new(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the text from `start` up to, but not including, `end` and
+    /// returns it.
+    pub fn delete_range(
+        &mut self,
+        start: (usize, usize),
+        end: (usize, usize),
+    ) -> Result<String, DocumentError> {
+        if end.0 >= self.lines.len() || start.0 > end.0 {
+            return Err(DocumentError::RowOutOfBounds);
+        }
+        if start.1 > self.lines[start.0].len()
+            || end.1 > self.lines[end.0].len()
+            || (start.0 == end.0 && start.1 > end.1)
+        {
+            return Err(DocumentError::ColOutOfBounds);
+        }
+
+        let removed = self.text_range(start, end);
+        self.splice(start, end, "");
+
+        self.changed(Change {
+            start,
+            removed: removed.clone(),
+            inserted: String::new(),
+        });
+
+        Ok(removed)
+    }
+
+    /// Replaces the text from `start` up to, but not including, `end` with
+    /// `text` as a single change and returns the position just past it.
+    pub fn replace_range(
+        &mut self,
+        start: (usize, usize),
+        end: (usize, usize),
+        text: &str,
+    ) -> Result<(usize, usize), DocumentError> {
+        if end.0 >= self.lines.len() || start.0 > end.0 {
+            return Err(DocumentError::RowOutOfBounds);
+        }
+        if start.1 > self.lines[start.0].len()
+            || end.1 > self.lines[end.0].len()
+            || (start.0 == end.0 && start.1 > end.1)
+        {
+            return Err(DocumentError::ColOutOfBounds);
+        }
+
+        let removed = self.text_range(start, end);
+        let new_end = self.splice(start, end, text);
+
+        self.changed(Change {
+            start,
+            removed,
+            inserted: text.to_string(),
+        });
+
+        Ok(new_end)
+    }
+
+    /// Inserts `text`, which may span several lines, at (`row`, `col`) as a
+    /// single change and returns the position just past it.
+    pub fn insert_str(
+        &mut self,
+        row: usize,
+        col: usize,
+        text: &str,
+    ) -> Result<(usize, usize), DocumentError> {
+        let line = self.lines.get(row).ok_or(DocumentError::RowOutOfBounds)?;
+        if col > line.len() {
+            return Err(DocumentError::ColOutOfBounds);
+        }
+
+        let end = self.splice((row, col), (row, col), text);
+
+        self.changed(Change {
+            start: (row, col),
+            removed: String::new(),
+            inserted: text.to_string(),
+        });
+
+        Ok(end)
+    }
+
+    /// Changes the text to `text` by replacing only the lines that differ,
+    /// so marks, signs and the undo history of the rest are kept. Returns
+    /// the hunks that were replaced.
+    pub fn set_text(&mut self, text: &str) -> Vec<Hunk> {
+        let old: Vec<String> = self.lines.iter().map(|l| l.to_string()).collect();
+        let new: Vec<&str> = text.split('\n').collect();
+        let hunks = diff::hunks(&old, &new);
+
+        // from the bottom, so the rows of the hunks above stay valid
+        for hunk in hunks.iter().rev() {
+            let lines = new[hunk.new.clone()].join("\n");
+            let last = self.lines.len() - 1;
+            let end_of = |row: usize| (row, self.lines[row].len());
+            let (start, end, text) = if hunk.new.is_empty() {
+                // the line break before or after the lines goes with them
+                if hunk.old.end <= last {
+                    ((hunk.old.start, 0), (hunk.old.end, 0), String::new())
+                } else if hunk.old.start > 0 {
+                    (end_of(hunk.old.start - 1), end_of(last), String::new())
+                } else {
+                    ((0, 0), end_of(last), String::new())
+                }
+            } else if hunk.old.is_empty() {
+                if hunk.old.start <= last {
+                    let at = (hunk.old.start, 0);
+                    (at, at, format!("{}\n", lines))
+                } else {
+                    (end_of(last), end_of(last), format!("\n{}", lines))
+                }
+            } else {
+                ((hunk.old.start, 0), end_of(hunk.old.end - 1), lines)
+            };
+            let _ = self.replace_range(start, end, &text);
+        }
+        hunks
+    }
+
+    /// Replaces the text from `start` up to, but not including, `end` with
+    /// `text`, returning the position just past the inserted text.
+    fn splice(&mut self, start: (usize, usize), end: (usize, usize), text: &str) -> (usize, usize) {
+        let suffix = self.lines[end.0].split(end.1);
+        self.lines[start.0].split(start.1);
+        self.lines.drain(start.0 + 1..=end.0);
+
+        let mut pos = start;
+        for (i, segment) in text.split('\n').enumerate() {
+            if i > 0 {
+                pos = (pos.0 + 1, 0);
+                self.lines.insert(pos.0, GapBuffer::new());
+            }
+            let line = &mut self.lines[pos.0];
+            line.insert_str(line.len(), segment);
+            pos.1 += segment.chars().count();
+        }
+        self.lines[pos.0].merge(suffix);
+
+        pos
+    }
+
+    /// Closes the current undo step; the changes made after this undo
+    /// separately.
+    pub fn commit_undo(&mut self) {
+        self.history.commit();
+    }
+
+    /// Reverts the last undo step and returns where it started.
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        let step = self.history.undo()?.to_vec();
+        for change in step.iter().rev() {
+            self.apply(&change.inverted());
+        }
+        self.dirty = !self.history.at_saved();
+        step.first().map(|c| c.start)
+    }
+
+    /// Reapplies the last undone step and returns where it started.
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        let step = self.history.redo()?.to_vec();
+        for change in &step {
+            self.apply(change);
+        }
+        self.dirty = !self.history.at_saved();
+        step.first().map(|c| c.start)
+    }
+
+    /// Applies `change` without recording it in the history.
+    fn apply(&mut self, change: &Change) {
+        self.splice(change.start, change.old_end(), &change.inserted);
+        self.update(change);
+    }
+
+    /// Returns the text from `start` up to, but not including, `end`.
+    pub fn text_range(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let mut text = String::new();
+        for row in start.0..=end.0.min(self.lines.len() - 1) {
+            let from = if row == start.0 { start.1 } else { 0 };
+            let line = &self.lines[row];
+            if row == end.0 {
+                text.extend(line.chars().skip(from).take(end.1.saturating_sub(from)));
+            } else {
+                text.extend(line.chars().skip(from));
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Records `change` in the history after it was applied to the lines.
+    fn changed(&mut self, change: Change) {
+        self.update(&change);
+        self.history.record(change);
+    }
+
+    /// Updates everything that tracks the document's lines after `change`
+    /// was applied to them.
+    fn update(&mut self, change: &Change) {
+        let row = change.start.0;
+        let removed = change.removed.matches('\n').count();
+        let inserted = change.inserted.matches('\n').count();
+
+        if removed != inserted {
+            self.shift_lines(row + 1, inserted as isize - removed as isize);
+        }
+        if let Some(edits) = &mut self.edits {
+            // the line before the change is unchanged, the old end is
+            // worked out from the removed text
+            let start = (row, utf16_col(&self.lines[row], change.start.1));
+            let tail: usize = match change.removed.rsplit_once('\n') {
+                Some((_, tail)) => tail.encode_utf16().count(),
+                None => start.1 + change.removed.encode_utf16().count(),
+            };
+            edits.push(TextEdit {
+                start,
+                end: (row + removed, tail),
+                text: change.inserted.clone(),
+            });
+        }
+        self.version += 1;
+        if let Some(h) = &mut self.highlighter {
+            h.edit(row, removed + 1, inserted + 1, &self.lines);
+        }
+        #[cfg(feature = "tree-sitter")]
+        if let Some(tree) = &mut self.syntax_tree {
+            tree.edit(change, &self.lines);
         }
 
         self.dirty = true;
+    }
 
-        Ok(())
+    /// Returns the whole text of the document, with its lines joined by
+    /// `\n`.
+    pub fn text(&self) -> String {
+        let last = self.lines.len() - 1;
+        self.text_range((0, 0), (last, self.lines[last].len()))
     }
 
     pub fn lines(&self) -> &Vec<GapBuffer> {
@@ -189,17 +694,293 @@ impl Document {
         self.original_path.as_deref()
     }
 
+    /// Returns the copy of the file kept next to it while it is open.
+    pub fn swap_path(&self) -> Option<&Path> {
+        self.swap_path.as_deref()
+    }
+
+    pub fn temp_path(&self) -> Option<&Path> {
+        (!self.temp_path.as_os_str().is_empty()).then_some(self.temp_path.as_path())
+    }
+
     pub fn dirty(&self) -> bool {
         self.dirty
     }
 
-    fn swap_path_for(path: &PathBuf) -> PathBuf {
-        let mut swap = path.clone();
+    pub fn readonly(&self) -> bool {
+        self.readonly
+    }
+
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly;
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.line_ending != line_ending {
+            self.line_ending = line_ending;
+            self.dirty = true;
+        }
+    }
+
+    /// Returns the encoding the document is read and written in.
+    pub fn encoding(&self) -> &'static str {
+        "utf-8"
+    }
+
+    pub fn signs(&self) -> &SignColumn {
+        &self.signs
+    }
+
+    /// Returns the number of changes made to the document, which language
+    /// servers use to order its versions.
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Starts recording the changes made to the document as text edits.
+    pub fn track_edits(&mut self) {
+        self.edits.get_or_insert_with(Vec::new);
+    }
+
+    /// Returns the text edits made since the last call, in order.
+    pub fn take_edits(&mut self) -> Vec<TextEdit> {
+        self.edits.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Replaces the document's diagnostics and the signs showing them, with
+    /// the most severe one on each line winning.
+    pub fn set_diagnostics(&mut self, mut diagnostics: Vec<Diagnostic>) {
+        diagnostics.sort_by_key(|d| (d.start, std::cmp::Reverse(d.severity)));
+        self.signs.clear_group("diagnostic");
+        let mut by_row: BTreeMap<usize, &Diagnostic> = BTreeMap::new();
+        for d in &diagnostics {
+            let worst = by_row.entry(d.start.0).or_insert(d);
+            if d.severity > worst.severity {
+                *worst = d;
+            }
+        }
+        for (row, d) in by_row {
+            self.signs.place(
+                row,
+                Sign {
+                    text: d.sign().to_string(),
+                    group: "diagnostic".to_string(),
+                    priority: 10,
+                    scope: d.scope().to_string(),
+                },
+            );
+        }
+        self.diagnostics = diagnostics;
+    }
+
+    /// Replaces the signs marking the rows the quickfix list points at, the
+    /// worst severity on each row winning.
+    pub fn set_quickfix_signs(&mut self, rows: &[(usize, Severity)]) {
+        self.signs.clear_group("quickfix");
+        let mut worst: BTreeMap<usize, Severity> = BTreeMap::new();
+        for &(row, severity) in rows {
+            let entry = worst.entry(row).or_insert(severity);
+            *entry = (*entry).max(severity);
+        }
+        for (row, severity) in worst {
+            self.signs.place(
+                row,
+                Sign {
+                    text: diagnostic::sign(severity).to_string(),
+                    group: "quickfix".to_string(),
+                    priority: 5,
+                    scope: diagnostic::scope(severity).to_string(),
+                },
+            );
+        }
+    }
+
+    /// Sets the lines the document is compared with for the git signs,
+    /// None if the file is not tracked.
+    pub fn set_diff_base(&mut self, base: Option<Vec<String>>) {
+        self.diff_base = base;
+        self.hunks_version = None;
+        self.update_hunks();
+    }
+
+    pub fn diff_base(&self) -> Option<&[String]> {
+        self.diff_base.as_deref()
+    }
+
+    /// Returns the hunks the text differs from the diff base in, as of the
+    /// last `update_hunks`.
+    pub fn hunks(&self) -> &[Hunk] {
+        &self.hunks
+    }
+
+    /// Compares the text with the diff base again if it changed since the
+    /// last time, and marks the added, changed and removed lines with
+    /// signs.
+    pub fn update_hunks(&mut self) {
+        if self.hunks_version == Some(self.version) {
+            return;
+        }
+        self.hunks_version = Some(self.version);
+        self.signs.clear_group("git");
+        let Some(base) = &self.diff_base else {
+            self.hunks.clear();
+            return;
+        };
+
+        let lines: Vec<String> = self.lines.iter().map(|l| l.to_string()).collect();
+        self.hunks = diff::hunks(base, &lines);
+        for hunk in &self.hunks {
+            let (text, scope) = match (hunk.old.is_empty(), hunk.new.is_empty()) {
+                (true, _) => ("+", "diff.plus"),
+                (false, false) => ("~", "diff.delta"),
+                (false, true) if hunk.new.start == 0 => ("‾", "diff.minus"),
+                (false, true) => ("_", "diff.minus"),
+            };
+            for row in hunk.rows() {
+                self.signs.place(
+                    row,
+                    Sign {
+                        text: text.to_string(),
+                        group: "git".to_string(),
+                        priority: 1,
+                        scope: scope.to_string(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Returns the commit, author and date of each line, if shown.
+    pub fn blame(&self) -> Option<&[String]> {
+        self.blame.as_deref()
+    }
+
+    pub fn set_blame(&mut self, blame: Option<Vec<String>>) {
+        self.blame = blame;
+    }
+
+    pub fn set_grammar(&mut self, grammar: Option<Rc<Grammar>>) {
+        #[cfg(feature = "tree-sitter")]
+        {
+            self.syntax_tree = grammar
+                .as_ref()
+                .and_then(|g| SyntaxTree::new(&g.name, &self.lines));
+        }
+        self.highlighter = grammar.map(|g| Highlighter::new(g, &self.lines));
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    pub fn syntax_tree(&self) -> Option<&SyntaxTree> {
+        self.syntax_tree.as_ref()
+    }
+
+    /// Returns the name of the language the document is highlighted as.
+    pub fn file_type(&self) -> Option<&str> {
+        self.highlighter.as_ref().map(|h| h.grammar().name.as_str())
+    }
+
+    pub fn highlights(&self, row: usize) -> &[HighlightSpan] {
+        #[cfg(feature = "tree-sitter")]
+        if let Some(tree) = &self.syntax_tree {
+            return tree.spans(row);
+        }
+
+        match &self.highlighter {
+            Some(h) => h.spans(row),
+            None => &[],
+        }
+    }
+
+    pub fn mark(&self, name: char) -> Option<(usize, usize)> {
+        self.marks.get(&name).copied()
+    }
+
+    pub fn set_mark(&mut self, name: char, pos: (usize, usize)) {
+        self.marks.insert(name, pos);
+        self.place_mark_signs();
+    }
+
+    fn place_mark_signs(&mut self) {
+        self.signs.clear_group("marks");
+        // only the marks set with `m` are shown, not the editor's own
+        for (&name, &(row, _)) in self.marks.iter().filter(|(n, _)| n.is_ascii_lowercase()) {
+            self.signs.place(
+                row,
+                Sign {
+                    text: name.to_string(),
+                    group: "marks".to_string(),
+                    priority: 0,
+                    scope: "sign.mark".to_string(),
+                },
+            );
+        }
+    }
+
+    /// Keeps signs, diagnostics and marks on `from` and later rows attached to their
+    /// lines after `delta` lines were inserted or removed.
+    fn shift_lines(&mut self, from: usize, delta: isize) {
+        self.signs.shift(from, delta);
+        // new lines have no blame until it is read again
+        if let Some(blame) = &mut self.blame {
+            let from = from.min(blame.len());
+            let n = delta.unsigned_abs();
+            if delta > 0 {
+                blame.splice(from..from, vec![String::new(); n]);
+            } else {
+                blame.drain(from..(from + n).min(blame.len()));
+            }
+        }
+        let rows = self
+            .diagnostics
+            .iter_mut()
+            .flat_map(|d| [&mut d.start.0, &mut d.end.0]);
+        for row in rows.chain(self.marks.values_mut().map(|(row, _)| row)) {
+            if *row >= from {
+                *row = row.saturating_add_signed(delta).max(from.saturating_sub(1));
+            }
+        }
+    }
+
+    /// Points the buffer at `path` after its file was moved there, moving
+    /// its swap and temp files along if they were not moved with it.
+    pub fn set_path(&mut self, path: &Path) {
+        if let Some(swap) = &self.swap_path {
+            let moved = Self::swap_path_for(path);
+            let _ = fs::rename(swap, &moved);
+            self.swap_path = Some(moved);
+        }
+        if !self.temp_path.as_os_str().is_empty() {
+            let moved = path.with_extension("tmp");
+            let _ = fs::rename(&self.temp_path, &moved);
+            self.temp_path = moved;
+        }
+        self.original_path = Some(path.to_path_buf());
+    }
+
+    pub fn swap_path_for(path: &Path) -> PathBuf {
+        let mut swap = path.to_path_buf();
         let name = swap.file_name().unwrap().to_string_lossy().to_string();
         swap.set_file_name(format!(".{}.swp", name));
         swap
     }
 
+    pub fn swap_due(&self) -> bool {
+        self.dirty
+            && self
+                .last_swap
+                .elapsed()
+                .map(|e| e >= SWAP_INTERVAL)
+                .unwrap_or(true)
+    }
+
     pub fn write_swap(&mut self, cursor: (usize, usize)) -> io::Result<()> {
         if !self.dirty {
             return Ok(());
@@ -213,39 +994,59 @@ impl Document {
         let mut swap_file = File::create(swap_path)?;
 
         writeln!(swap_file, "# SWAP")?;
+        writeln!(swap_file, "# pid={}", std::process::id())?;
         if let Some(ref orig) = self.original_path {
             writeln!(swap_file, "# path={}", orig.display())?;
         }
         writeln!(swap_file, "# cursor={},{}", cursor.0, cursor.1)?;
+        writeln!(swap_file, "# fileformat={}", self.line_ending.name())?;
+        // every line after this one is text, whatever it starts with
+        writeln!(swap_file, "# text")?;
         for line in &self.lines {
-            writeln!(swap_file, "{}", line.to_string())?;
+            writeln!(swap_file, "{}", line)?;
         }
         swap_file.sync_all()?;
         self.last_swap = SystemTime::now();
         Ok(())
     }
 
-    pub fn recover_from_swap(path: &PathBuf) -> io::Result<Document> {
+    /// Reads the text a swap file at `path` saved back into a document,
+    /// failing if the editor that wrote it is still running.
+    pub fn recover_from_swap(path: &Path) -> io::Result<(Document, (usize, usize))> {
         let file = File::open(path)?;
         let reader = BufReader::new(file);
 
         let mut lines = Vec::new();
         let mut cursor = (0, 0);
         let mut original_path: Option<PathBuf> = None;
+        let mut line_ending = LineEnding::Unix;
+        let mut in_text = false;
 
         for line in reader.lines() {
             let line = line?;
-            if let Some(rest) = line.strip_prefix("# path=") {
+            if in_text {
+                let mut gb = GapBuffer::new();
+                gb.insert_str(0, &line);
+                lines.push(gb);
+            } else if line == "# text" {
+                in_text = true;
+            } else if let Some(pid) = line.strip_prefix("# pid=") {
+                let pid: u32 = pid.parse().unwrap_or(0);
+                if pid != std::process::id() && process_running(pid) {
+                    return Err(io::Error::other(format!(
+                        "Swap file in use by process {}",
+                        pid
+                    )));
+                }
+            } else if let Some(rest) = line.strip_prefix("# path=") {
                 original_path = Some(PathBuf::from(rest));
             } else if let Some(rest) = line.strip_prefix("# cursor=") {
                 let mut parts = rest.split(',');
                 let row = parts.next().unwrap().parse().unwrap();
                 let col = parts.next().unwrap().parse().unwrap();
                 cursor = (row, col);
-            } else if !line.starts_with('#') {
-                let mut gb = GapBuffer::new();
-                gb.insert_str(0, &line);
-                lines.push(gb);
+            } else if line == "# fileformat=dos" {
+                line_ending = LineEnding::Dos;
             }
         }
 
@@ -268,17 +1069,46 @@ impl Document {
             }
         }
 
-        Ok(Document {
+        let doc = Document {
             lines,
             dirty: true,
+            readonly: false,
+            line_ending,
+            modified: None,
             original_path: Some(original),
             temp_path: temp,
-            swap_path: Some(path.clone()),
+            swap_path: Some(path.to_path_buf()),
             last_swap: SystemTime::now(),
-        })
+            signs: SignColumn::new(),
+            marks: BTreeMap::new(),
+            highlighter: None,
+            #[cfg(feature = "tree-sitter")]
+            syntax_tree: None,
+            history: History::unsaved(),
+            version: 0,
+            edits: None,
+            diagnostics: Vec::new(),
+            diff_base: None,
+            hunks: Vec::new(),
+            hunks_version: None,
+            blame: None,
+        };
+
+        Ok((doc, cursor))
     }
 }
 
+/// Whether process `pid` is still running, and with it the editor that
+/// wrote a swap file.
+fn process_running(pid: u32) -> bool {
+    pid != 0
+        && std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+}
+
 impl Drop for Document {
     fn drop(&mut self) {
         let _ = std::fs::remove_file(&self.temp_path);
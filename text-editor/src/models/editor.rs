@@ -1,49 +1,565 @@
-use crate::models::document::Document;
-use crossterm::event::{KeyCode, KeyEvent};
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
+};
+
+use crate::models::{
+    completion::{self, Completion, Source},
+    diff::{self, Hunk},
+    document::{Document, LineEnding},
+    explorer::{Action, Explorer, Prompt},
+    finder::Finder,
+    git, grep,
+    gutter::Gutter,
+    jump::{FileArg, Jump},
+    language::Languages,
+    layout::{self, Layout},
+    lsp::{self, Event, Lsp, Request},
+    make::{self, Build, ErrorFormat},
+    message::{Message, Severity},
+    notation::parse_keys,
+    options::{self, Options},
+    popup::Popup,
+    quickfix::Quickfix,
+    range, shell,
+    syntax::SyntaxSet,
+    tab::{SplitDirection, Tab},
+    theme::Theme,
+    window::Window,
+    wrap,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use serde_json::{Value, json};
 use tui::layout::Rect;
 
 const TAB_WIDTH: usize = 4;
+/// How many keys a single key press may replay through macros before the
+/// replay is considered runaway and stopped.
+const MAX_REPLAY: usize = 10_000;
+/// Clicks on the same cell within this time count as a double or triple
+/// click.
+const MULTI_CLICK: Duration = Duration::from_millis(400);
+const SCROLL_LINES: isize = 3;
 
 pub struct Editor {
-    doc: Document,
-    cursor: (usize, usize), // (row, col)
-    pref_col: usize,
+    buffers: Vec<Document>,
+    tabs: Vec<Tab>,
+    active_tab: usize,
     mode: Mode,
     cmd_buf: String,
-    status_msg: Option<String>,
+    pending: String,
+    selection_history: Vec<((usize, usize), (usize, usize))>,
+    message: Option<Message>,
+    messages: Vec<Message>,
+    show_messages: bool,
+    popup: Option<Popup>,
+    completion: Option<Completion>,
+    finder: Option<Finder>,
+    explorer: Option<Explorer>,
+    quickfix: Quickfix,
+    code_actions: Vec<Value>, // from the last code action request, for `:ca N`
+    build: Option<(Build, ErrorFormat)>, // started by `:make`, while it runs
+    recording: Option<char>,
+    registers: HashMap<char, Vec<KeyEvent>>,
+    last_macro: Option<char>,
+    replay: VecDeque<KeyEvent>,
+    last_click: Option<(Instant, (u16, u16), u8)>,
     should_quit: bool,
+    aborted: bool,         // quit with :cq
+    output: Option<usize>, // buffer written to stdout on quit
+    diff: Option<usize>,   // buffer that `:diff` shows its changes in
+    root: PathBuf,         // of the project, where files are looked for
+    options: Options,
+    syntax: SyntaxSet,
+    theme: Theme,
+    languages: Languages,
+    lsp: Lsp,
 }
 
 impl Editor {
     pub fn new(doc: Document) -> Self {
-        Self {
-            doc,
-            cursor: (0, 0),
-            pref_col: 0,
+        let (syntax, mut errors) = SyntaxSet::load();
+        let (languages, language_errors) = Languages::load();
+        errors.extend(language_errors);
+        let options = Options::default();
+        let theme = Theme::load("default", options.termguicolors).unwrap_or_else(|e| {
+            errors.push(e);
+            Theme::parse("default", "", false).expect("empty theme is valid")
+        });
+        let mut editor = Self {
+            buffers: vec![doc],
+            tabs: vec![Tab::new(Window::new(0))],
+            active_tab: 0,
             mode: Mode::Normal,
             cmd_buf: String::new(),
-            status_msg: None,
+            pending: String::new(),
+            selection_history: Vec::new(),
+            message: None,
+            messages: Vec::new(),
+            show_messages: false,
+            popup: None,
+            completion: None,
+            finder: None,
+            explorer: None,
+            quickfix: Quickfix::default(),
+            code_actions: Vec::new(),
+            build: None,
+            recording: None,
+            registers: HashMap::new(),
+            last_macro: None,
+            replay: VecDeque::new(),
+            last_click: None,
             should_quit: false,
+            aborted: false,
+            output: None,
+            diff: None,
+            root: std::env::current_dir().unwrap_or_default(),
+            options,
+            syntax,
+            theme,
+            languages,
+            lsp: Lsp::new(),
+        };
+        editor.detect_syntax(0);
+        editor.load_diff_base(0);
+        for e in errors {
+            editor.error(e);
         }
+        editor.warn_readonly(0);
+        editor
     }
 
     pub fn handle_key(&mut self, key: KeyEvent, rect: &Rect) {
-        self.status_msg = None;
+        self.message = None;
+        self.show_messages = false;
+        self.popup = None;
+        if let Some(reg) = self.recording {
+            self.registers.entry(reg).or_default().push(key);
+        }
+
+        self.replay.push_back(key);
+        let mut replayed = 0;
+        while let Some(key) = self.replay.pop_front() {
+            replayed += 1;
+            if replayed > MAX_REPLAY {
+                self.replay.clear();
+                self.error("Macro replay stopped: too many keys");
+                break;
+            }
+            self.dispatch_key(key, rect);
+        }
+        // an insert session is undone as a whole
+        if self.mode != Mode::Insert {
+            self.completion = None;
+            for doc in &mut self.buffers {
+                doc.commit_undo();
+            }
+        }
+        self.scroll_to_cursor(self.tab().active(), rect);
+        self.sync_lsp();
+        self.sync_explorer();
+        self.sync_git();
+
+        let cursor = self.cursor();
+        let doc = self.doc_mut();
+        if doc.swap_due() {
+            let _ = doc.write_swap(cursor);
+        }
+    }
+
+    /// Runs the Ex command `cmd` as if it was typed after `:` in normal
+    /// mode, returning the first error it reported. A build it started is
+    /// waited for.
+    pub fn run_command(&mut self, cmd: &str, area: &Rect) -> Result<(), String> {
+        let seen = self.messages.len();
+        let keys = [KeyCode::Esc, KeyCode::Char(':')]
+            .into_iter()
+            .chain(cmd.chars().map(KeyCode::Char))
+            .chain([KeyCode::Enter]);
+        for code in keys {
+            self.handle_key(KeyEvent::new(code, KeyModifiers::NONE), area);
+        }
+        self.wait_for_make();
+
+        match self.messages[seen..]
+            .iter()
+            .find(|m| m.severity == Severity::Error)
+        {
+            Some(msg) => Err(msg.text.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn dispatch_key(&mut self, key: KeyEvent, rect: &Rect) {
+        if self.finder.is_some() {
+            self.handle_finder_key(key);
+            return;
+        }
+        if self.quickfix.focused {
+            self.handle_quickfix_key(key);
+            return;
+        }
+        if self.explorer.as_ref().is_some_and(|e| e.focused) {
+            self.handle_explorer_key(key);
+            return;
+        }
+        let rect = self.text_rect(self.window(), &self.tab().active_rect(*rect));
         match self.mode {
             Mode::Normal => {
-                self.handle_normal_mode(key, rect);
+                self.handle_normal_mode(key, &rect);
             }
             Mode::Insert => {
-                self.handle_insert_mode(key, rect);
+                self.handle_insert_mode(key, &rect);
+            }
+            Mode::Visual => {
+                self.handle_visual_mode(key, &rect);
             }
             Mode::Command => {
-                self.handle_command_mode(key, rect);
+                self.handle_command_mode(key);
+            }
+        }
+    }
+
+    /// Inserts pasted `text` as a single change that undoes in one step,
+    /// bypassing the key handling so nothing is re-indented or remapped.
+    pub fn handle_paste(&mut self, text: &str, area: &Rect) {
+        self.message = None;
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        match self.mode {
+            Mode::Command => {
+                let line = text.lines().next().unwrap_or_default();
+                self.cmd_buf.push_str(line);
+                return;
+            }
+            Mode::Visual => {
+                if let Some((start, end)) = self.selection() {
+                    let _ = self.doc_mut().delete_range(start, end);
+                    self.set_cursor(start);
+                }
+                self.exit_visual();
+            }
+            Mode::Normal | Mode::Insert => {}
+        }
+
+        let (row, col) = self.cursor();
+        let doc = self.doc_mut();
+        doc.commit_undo();
+        match doc.insert_str(row, col, &text) {
+            Ok(end) => {
+                doc.commit_undo();
+                self.set_cursor(end);
+            }
+            Err(e) => self.error(format!("{:?}", e)),
+        }
+
+        self.scroll_to_cursor(self.tab().active(), area);
+        self.sync_lsp();
+        self.sync_git();
+    }
+
+    fn undo(&mut self) {
+        match self.doc_mut().undo() {
+            Some(pos) => self.set_cursor(self.clamp_pos(pos)),
+            None => self.warn("Already at oldest change"),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.doc_mut().redo() {
+            Some(pos) => self.set_cursor(self.clamp_pos(pos)),
+            None => self.warn("Already at newest change"),
+        }
+    }
+
+    fn clamp_pos(&self, (row, col): (usize, usize)) -> (usize, usize) {
+        let lines = self.doc().lines();
+        let row = row.min(lines.len() - 1);
+        (row, col.min(lines[row].len()))
+    }
+
+    pub fn handle_mouse(&mut self, event: MouseEvent, area: &Rect) {
+        if !self.options.mouse || self.mode == Mode::Command {
+            return;
+        }
+
+        let (x, y) = (event.column, event.row);
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(index) = self.tab().window_at(*area, x, y) else {
+                    return;
+                };
+                if index != self.tab().active() {
+                    if self.mode == Mode::Visual {
+                        self.exit_visual();
+                    }
+                    self.tab_mut().set_active(index);
+                }
+
+                let (row, col) = self.screen_to_doc(area, x, y);
+                match self.count_click(x, y) {
+                    1 => {
+                        if self.mode == Mode::Visual {
+                            self.exit_visual();
+                        }
+                        self.set_cursor((row, col));
+                    }
+                    2 => {
+                        let (start, end) = self.word_at((row, col));
+                        self.select_inclusive(start, end);
+                    }
+                    _ => {
+                        let len = self.doc().lines()[row].len();
+                        self.select_inclusive((row, 0), (row, len));
+                    }
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let pos = self.screen_to_doc(area, x, y);
+                if self.mode != Mode::Visual {
+                    self.enter_visual();
+                }
+                self.set_cursor(pos);
+            }
+            MouseEventKind::ScrollUp => {
+                if let Some(index) = self.tab().window_at(*area, x, y) {
+                    self.scroll_window(index, area, -SCROLL_LINES);
+                }
+            }
+            MouseEventKind::ScrollDown => {
+                if let Some(index) = self.tab().window_at(*area, x, y) {
+                    self.scroll_window(index, area, SCROLL_LINES);
+                }
+            }
+            _ => {}
+        }
+
+        self.scroll_to_cursor(self.tab().active(), area);
+    }
+
+    /// Records a click on `(x, y)` and returns whether it is a single,
+    /// double or triple click.
+    fn count_click(&mut self, x: u16, y: u16) -> u8 {
+        let now = Instant::now();
+        let count = match self.last_click {
+            Some((at, cell, n)) if cell == (x, y) && now.duration_since(at) < MULTI_CLICK => {
+                n % 3 + 1
+            }
+            _ => 1,
+        };
+        self.last_click = Some((now, (x, y), count));
+        count
+    }
+
+    /// Returns the first and last column of the run of word, blank or
+    /// punctuation chars at `pos`.
+    fn word_at(&self, (row, col): (usize, usize)) -> ((usize, usize), (usize, usize)) {
+        let chars: Vec<char> = self.doc().lines()[row].chars().collect();
+        if chars.is_empty() {
+            return ((row, 0), (row, 0));
+        }
+
+        let class = |c: char| {
+            if c.is_alphanumeric() || c == '_' {
+                0
+            } else if c.is_whitespace() {
+                1
+            } else {
+                2
+            }
+        };
+        let col = col.min(chars.len() - 1);
+        let same = |i: &usize| class(chars[*i]) == class(chars[col]);
+        let start = (0..col).rev().take_while(same).last().unwrap_or(col);
+        let end = (col..chars.len()).take_while(same).last().unwrap_or(col);
+        ((row, start), (row, end))
+    }
+
+    /// Scrolls window `index` by `delta` screen rows, moving its cursor
+    /// along if it would leave the screen.
+    fn scroll_window(&mut self, index: usize, area: &Rect, delta: isize) {
+        let rect = self.tab().window_rects(*area)[index];
+        let window = &self.tab().windows()[index];
+        let text = self.text_rect(window, &rect);
+        let lines = self.buffers[window.buffer()].lines();
+        let top = layout::scroll_by(lines, window.top, delta, text.width as usize, &self.options);
+
+        let mut moved = Window::new(window.buffer());
+        moved.top = top;
+        moved.hscroll = window.hscroll;
+        let layout = self.layout(&moved, &rect);
+        let (cursor, pref_col) = (window.cursor, window.pref_col);
+        let shown = layout.lines();
+        let cursor = match (shown.first(), shown.last()) {
+            (Some(first), _) if cursor < (first.row, first.start) => {
+                layout.to_doc(0, pref_col).unwrap_or(cursor)
+            }
+            (_, Some(last)) if layout.to_screen(cursor).is_none() && cursor.0 >= last.row => {
+                layout.to_doc(shown.len() - 1, pref_col).unwrap_or(cursor)
+            }
+            _ => cursor,
+        };
+
+        let win = &mut self.tab_mut().windows_mut()[index];
+        win.top = top;
+        win.cursor = cursor;
+    }
+
+    /// Scrolls window `index` of the active tab just enough for its cursor
+    /// to be on screen, with `area` the editor area the tab is laid out in.
+    fn scroll_to_cursor(&mut self, index: usize, area: &Rect) {
+        let window = &self.tab().windows()[index];
+        let rect = self.text_rect(window, &self.tab().window_rects(*area)[index]);
+        let (width, height) = (rect.width as usize, rect.height as usize);
+        let lines = self.buffers[window.buffer()].lines();
+
+        let top = layout::scroll_to(
+            lines,
+            window.top,
+            window.cursor,
+            width,
+            height,
+            &self.options,
+        );
+        let hscroll = if self.options.wrap {
+            0
+        } else {
+            wrap::side_scroll(window.hscroll, window.cursor.1, width, &self.options)
+        };
+
+        let win = &mut self.tab_mut().windows_mut()[index];
+        win.top = top;
+        win.hscroll = hscroll;
+    }
+
+    /// Re-lays out the active tab in the resized editor `area`, keeping each
+    /// window's cursor on screen.
+    pub fn handle_resize(&mut self, area: &Rect) {
+        for index in 0..self.tab().windows().len() {
+            self.scroll_to_cursor(index, area);
+        }
+    }
+
+    /// Reloads the current buffer if its file changed on disk while the
+    /// editor was in the background, or warns if that would lose changes.
+    pub fn handle_focus_gained(&mut self) {
+        // the index may have changed too, with git run outside the editor
+        for buffer in 0..self.buffers.len() {
+            self.load_diff_base(buffer);
+        }
+        let doc = self.doc();
+        if !doc.changed_on_disk() {
+            return;
+        }
+
+        if doc.dirty() {
+            let text = format!(
+                "\"{}\" changed on disk since editing started (:e! to reload)",
+                doc.file_name()
+            );
+            self.warn(text);
+        } else {
+            self.reload();
+        }
+    }
+
+    /// Writes every modified buffer with a file name when `autosave` is set.
+    pub fn handle_focus_lost(&mut self) {
+        if !self.options.autosave {
+            return;
+        }
+
+        let mut errors = Vec::new();
+        let mut saved = Vec::new();
+        for i in 0..self.buffers.len() {
+            let doc = &self.buffers[i];
+            if doc.dirty() && doc.full_path().is_some() && !doc.readonly() {
+                errors.extend(self.format_on_save(i, None));
+                let doc = &mut self.buffers[i];
+                match doc.save(None) {
+                    Ok(()) => saved.push(i),
+                    Err(e) => errors.push(format!("\"{}\": {}", doc.file_name(), e)),
+                }
+            }
+        }
+        for buffer in saved {
+            self.saved(buffer);
+        }
+        for e in errors {
+            self.error(e);
+        }
+    }
+
+    fn reload(&mut self) {
+        match self.doc_mut().reload() {
+            Ok(()) => {
+                self.clamp_cursors();
+                self.load_diff_base(self.window().buffer());
+                let text = format!("\"{}\" reloaded", self.doc().file_name());
+                self.info(text);
+            }
+            Err(e) => self.error(e.to_string()),
+        }
+    }
+
+    /// Moves every window's cursor and selection anchor back inside its
+    /// buffer, after the buffer shrank underneath it.
+    fn clamp_cursors(&mut self) {
+        for tab in &mut self.tabs {
+            for window in tab.windows_mut() {
+                let lines = self.buffers[window.buffer()].lines();
+                let clamp = |(row, col): (usize, usize)| {
+                    let row = row.min(lines.len() - 1);
+                    (row, col.min(lines[row].len()))
+                };
+                window.cursor = clamp(window.cursor);
+                window.anchor = clamp(window.anchor);
+                window.top = (window.top.0.min(lines.len() - 1), 0);
             }
         }
     }
 
+    fn notify(&mut self, text: impl Into<String>, severity: Severity) {
+        let message = Message::new(text, severity);
+        self.messages.push(message.clone());
+        self.message = Some(message);
+    }
+
+    fn info(&mut self, text: impl Into<String>) {
+        self.notify(text, Severity::Info);
+    }
+
+    fn warn(&mut self, text: impl Into<String>) {
+        self.notify(text, Severity::Warn);
+    }
+
+    fn error(&mut self, text: impl Into<String>) {
+        self.notify(text, Severity::Error);
+    }
+
     fn handle_normal_mode(&mut self, key: KeyEvent, rect: &Rect) {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.handle_pending(&pending, key, rect);
+            return;
+        }
+
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
         match key.code {
+            KeyCode::Char('w') if ctrl => {
+                self.pending.push_str("^W");
+            }
+            KeyCode::Char('r') if ctrl => {
+                self.redo();
+            }
+            KeyCode::Char('p') if ctrl => {
+                self.open_finder(None);
+            }
+            KeyCode::Char('e') if ctrl => {
+                self.focus_explorer();
+            }
             KeyCode::Char('w') => {
                 self.move_cursor(0, -1, rect);
             }
@@ -56,9 +572,38 @@ impl Editor {
             KeyCode::Char('d') => {
                 self.move_cursor(1, 0, rect);
             }
+            KeyCode::Char('j') => {
+                self.move_line(1);
+            }
+            KeyCode::Char('k') => {
+                self.move_line(-1);
+            }
+            KeyCode::Char('u') => {
+                self.undo();
+            }
+            KeyCode::Char('K') => {
+                self.hover();
+            }
+            KeyCode::Char('q') if self.recording.is_some() => {
+                if let Some(reg) = self.recording.take() {
+                    // drop the `q` that stopped the recording
+                    self.registers.entry(reg).or_default().pop();
+                }
+            }
+            KeyCode::Char(c @ ('g' | 'm' | '\'' | ']' | '[' | 'q' | '@' | '!')) => {
+                self.pending.push(c);
+            }
             KeyCode::Char('i') => {
                 self.mode = Mode::Insert;
             }
+            KeyCode::Char('v') => {
+                self.enter_visual();
+            }
+            #[cfg(feature = "tree-sitter")]
+            KeyCode::Char('+') => {
+                self.enter_visual();
+                self.expand_selection();
+            }
             KeyCode::Char(':') => {
                 self.mode = Mode::Command;
                 self.cmd_buf.clear();
@@ -67,204 +612,2714 @@ impl Editor {
         }
     }
 
-    fn handle_insert_mode(&mut self, key: KeyEvent, rect: &Rect) {
-        match key.code {
-            KeyCode::Esc => {
-                self.mode = Mode::Normal;
+    fn handle_pending(&mut self, pending: &str, key: KeyEvent, rect: &Rect) {
+        if let Some(motion) = pending.strip_prefix('!') {
+            self.filter_motion(motion, key, rect);
+            return;
+        }
+        let visual = self.mode == Mode::Visual;
+        match (pending, key.code) {
+            ("g", KeyCode::Char('t')) => {
+                self.next_tab();
             }
-            KeyCode::Char(c) => {
-                self.doc.insert_char(self.cursor.0, self.cursor.1, c);
-                self.move_cursor(1, 0, rect);
+            ("g", KeyCode::Char('T')) => {
+                self.prev_tab();
             }
-            KeyCode::Backspace => {
-                if self.cursor.1 > 0 {
-                    self.move_cursor(-1, 0, rect);
-                    self.doc.delete(self.cursor.0, self.cursor.1);
-                } else if self.cursor.0 > 0 {
-                    let prev_len = self.doc.lines()[self.cursor.0 - 1].len();
-                    self.doc.delete(self.cursor.0, 0);
-                    self.cursor.0 -= 1;
-                    self.cursor.1 = prev_len;
-                    self.pref_col = self.cursor.1;
-                }
+            ("g", KeyCode::Char('j')) => {
+                self.move_cursor(0, 1, rect);
             }
-            KeyCode::Enter => {
-                self.doc.insert_newline(self.cursor.0, self.cursor.1);
-                self.cursor.0 += 1;
-                self.cursor.1 = 0;
-                self.pref_col = 0;
+            ("g", KeyCode::Char('k')) => {
+                self.move_cursor(0, -1, rect);
             }
-            KeyCode::Tab => {
-                for _ in 0..TAB_WIDTH {
-                    self.doc.insert_char(self.cursor.0, self.cursor.1, ' ');
-                    self.move_cursor(1, 0, rect)
+            ("g", KeyCode::Char('d')) => {
+                self.goto_definition();
+            }
+            ("g", KeyCode::Char('r')) => {
+                self.find_references();
+            }
+            ("]", KeyCode::Char('c')) => {
+                self.jump_hunk(true);
+            }
+            ("[", KeyCode::Char('c')) => {
+                self.jump_hunk(false);
+            }
+            ("g", KeyCode::Char('h')) => {
+                self.preview_hunk();
+            }
+            ("m", KeyCode::Char(c @ ('i' | 'a'))) if visual => {
+                self.pending = format!("m{}", c);
+            }
+            #[cfg(feature = "tree-sitter")]
+            ("mi" | "ma", KeyCode::Char(c @ ('f' | 'c' | 'a'))) => {
+                let object = match c {
+                    'f' => "function",
+                    'c' => "class",
+                    _ => "parameter",
+                };
+                self.select_text_object(object, pending == "mi");
+            }
+            #[cfg(feature = "tree-sitter")]
+            ("]", KeyCode::Char('f')) => {
+                self.jump_function(true);
+            }
+            #[cfg(feature = "tree-sitter")]
+            ("[", KeyCode::Char('f')) => {
+                self.jump_function(false);
+            }
+            ("m", KeyCode::Char(c)) if c.is_ascii_lowercase() => {
+                let cursor = self.cursor();
+                self.doc_mut().set_mark(c, cursor);
+            }
+            ("'", KeyCode::Char(c)) => match self.doc().mark(c) {
+                Some((row, _)) => {
+                    let row = row.min(self.doc().lines().len() - 1);
+                    let win = self.window_mut();
+                    win.cursor = (row, 0);
+                    win.pref_col = 0;
+                }
+                None => self.error("Mark not set"),
+            },
+            ("q", KeyCode::Char(c)) if c.is_ascii_lowercase() => {
+                self.recording = Some(c);
+                self.registers.insert(c, Vec::new());
+            }
+            ("@", KeyCode::Char(c)) if c.is_ascii_lowercase() || c == '@' => {
+                let reg = if c == '@' { self.last_macro } else { Some(c) };
+                match reg.and_then(|r| self.registers.get(&r).map(|keys| (r, keys))) {
+                    Some((reg, keys)) => {
+                        // replayed keys run before anything queued after them
+                        for key in keys.iter().rev() {
+                            self.replay.push_front(*key);
+                        }
+                        self.last_macro = Some(reg);
+                    }
+                    None => self.error("Register is empty"),
                 }
             }
+            ("^W", KeyCode::Char('w')) => {
+                self.tab_mut().next_window();
+            }
+            ("^W", KeyCode::Char('s')) => {
+                self.split(None, SplitDirection::Horizontal);
+            }
+            ("^W", KeyCode::Char('v')) => {
+                self.split(None, SplitDirection::Vertical);
+            }
+            ("^W", KeyCode::Char('q')) | ("^W", KeyCode::Char('c')) => {
+                self.close_window();
+            }
             _ => {}
         }
     }
 
-    fn handle_command_mode(&mut self, key: KeyEvent, rect: &Rect) {
+    fn handle_visual_mode(&mut self, key: KeyEvent, rect: &Rect) {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.handle_pending(&pending, key, rect);
+            return;
+        }
+
         match key.code {
-            KeyCode::Esc => {
-                self.mode = Mode::Normal;
-                self.cmd_buf.clear();
+            KeyCode::Esc | KeyCode::Char('v') => {
+                self.exit_visual();
             }
-            KeyCode::Enter => {
-                self.execute_command();
-                self.mode = Mode::Normal;
-                self.cmd_buf.clear();
+            KeyCode::Char('w') => {
+                self.move_cursor(0, -1, rect);
             }
-            KeyCode::Backspace => {
-                self.cmd_buf.pop();
+            KeyCode::Char('a') => {
+                self.move_cursor(-1, 0, rect);
             }
-
-            KeyCode::Char(c) => {
-                self.cmd_buf.push(c);
+            KeyCode::Char('s') => {
+                self.move_cursor(0, 1, rect);
             }
-            _ => {}
-        }
-    }
-
-    fn execute_command(&mut self) {
-        match self.cmd_buf.as_str() {
-            "q" => {
-                // quit
-                if self.doc.dirty() {
-                    self.status_msg =
-                        Some("No write since last change (add ! to override)".to_string());
-                } else {
-                    self.should_quit = true;
+            KeyCode::Char('d') => {
+                self.move_cursor(1, 0, rect);
+            }
+            KeyCode::Char('j') => {
+                self.move_line(1);
+            }
+            KeyCode::Char('k') => {
+                self.move_line(-1);
+            }
+            KeyCode::Char('x') => {
+                if let Some((start, end)) = self.selection() {
+                    let _ = self.doc_mut().delete_range(start, end);
+                    self.set_cursor(start);
                 }
+                self.exit_visual();
             }
-            "q!" => {
-                self.should_quit = true;
+            KeyCode::Char(c @ ('g' | 'm' | ']' | '[')) => {
+                self.pending.push(c);
             }
-            "s" => {
-                // save
-                self.doc.save(None);
+            KeyCode::Char(c @ (':' | '!')) => {
+                // the command line starts with the selected lines as range
+                let (start, end) = {
+                    let win = self.window();
+                    (win.anchor.min(win.cursor), win.anchor.max(win.cursor))
+                };
+                self.doc_mut().set_mark('<', start);
+                self.doc_mut().set_mark('>', end);
+                self.exit_visual();
+                self.set_cursor(start);
+                self.mode = Mode::Command;
+                self.cmd_buf = format!("'<,'>{}", if c == '!' { "!" } else { "" });
             }
-            cmd if cmd.starts_with("s ") => {
-                // save as
-                let name = cmd[2..].trim();
-                self.doc.save(Some(name));
+            #[cfg(feature = "tree-sitter")]
+            KeyCode::Char('+') => {
+                self.expand_selection();
             }
-            "sq" => {
-                // save quit
-                if self.doc.save(None).is_ok() {
-                    self.should_quit = true;
-                }
+            #[cfg(feature = "tree-sitter")]
+            KeyCode::Char('-') => {
+                self.shrink_selection();
             }
             _ => {}
         }
     }
 
-    fn wraps(len: usize, width: usize) -> usize {
-        if len == 0 {
-            1
-        } else {
-            (len + width - 1) / width
-        }
+    fn enter_visual(&mut self) {
+        self.mode = Mode::Visual;
+        let win = self.window_mut();
+        win.anchor = win.cursor;
     }
 
-    fn move_cursor(&mut self, dx: i32, dy: i32, rect: &Rect) {
-        let width = rect.width as usize;
-        let mut row = self.cursor.0;
-        let mut col = self.cursor.1;
-
-        if dx != 0 {
-            let dx = dx.signum();
-            if dx < 0 {
-                if col > 0 {
-                    col -= 1;
-                } else if row > 0 {
-                    row -= 1;
-                    col = self.doc.lines()[row].len();
-                }
-            } else {
-                let len = self.doc.lines()[row].len();
-                if col < len {
-                    col += 1;
-                } else if row + 1 < self.doc.lines().len() {
-                    row += 1;
-                    col = 0;
-                }
-            }
-            self.pref_col = col;
-        }
+    fn exit_visual(&mut self) {
+        self.mode = Mode::Normal;
+        self.selection_history.clear();
+    }
 
-        if dy != 0 {
-            let dy = dy.signum();
-            if dy < 0 {
-                if col >= width {
-                    col -= width;
-                } else if row > 0 {
-                    row -= 1;
-                    let prev_len = self.doc.lines()[row].len();
-                    let line_wraps = Self::wraps(prev_len, width);
-                    let last_wrap = line_wraps - 1;
-                    col = (last_wrap * width + self.pref_col.min(width - 1)).min(prev_len);
-                } else {
-                    col = 0;
-                }
-            } else {
-                let len = self.doc.lines()[row].len();
-                if col + width < len {
-                    col += width;
-                } else if row + 1 < self.doc.lines().len() {
-                    row += 1;
-                    col = self.pref_col.min(self.doc.lines()[row].len());
-                } else {
-                    col = len;
-                }
-            }
+    /// Returns the visual selection as a range from its start up to, but not
+    /// including, its end.
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        if self.mode != Mode::Visual {
+            return None;
         }
+        let win = self.window();
+        let start = win.anchor.min(win.cursor);
+        let end = win.anchor.max(win.cursor);
+        Some((start, self.pos_after(end)))
+    }
 
-        self.cursor = (row, col);
+    /// Selects `start..end`, given in the form `selection` returns.
+    #[cfg(feature = "tree-sitter")]
+    fn select(&mut self, start: (usize, usize), end: (usize, usize)) {
+        let last = self.pos_before(end).max(start);
+        let win = self.window_mut();
+        win.anchor = start;
+        win.cursor = last;
+        win.pref_col = last.1;
     }
 
-    pub fn doc_to_screen(&self, rect: &Rect) -> (u16, u16) {
-        let width = rect.width as usize;
-        let mut screen_row = 0;
-        for i in 0..self.cursor.0 {
-            let len = self.doc.lines()[i].len();
-            screen_row += (len / width).max(1);
+    fn pos_after(&self, (row, col): (usize, usize)) -> (usize, usize) {
+        let lines = self.doc().lines();
+        if col < lines[row].len() {
+            (row, col + 1)
+        } else if row + 1 < lines.len() {
+            (row + 1, 0)
+        } else {
+            (row, lines[row].len())
         }
-        let wrapped_row = self.cursor.1 / width;
-        let wrapped_col = self.cursor.1 % width;
+    }
 
-        ((screen_row + wrapped_row) as u16, wrapped_col as u16)
+    /// Selects from `anchor` to `cursor`, both included.
+    fn select_inclusive(&mut self, anchor: (usize, usize), cursor: (usize, usize)) {
+        if self.mode != Mode::Visual {
+            self.enter_visual();
+        }
+        self.window_mut().anchor = anchor;
+        self.set_cursor(cursor);
     }
 
-    pub fn should_quit(&self) -> bool {
-        self.should_quit
+    #[cfg(feature = "tree-sitter")]
+    fn pos_before(&self, (row, col): (usize, usize)) -> (usize, usize) {
+        if col > 0 {
+            (row, col - 1)
+        } else if row > 0 {
+            (row - 1, self.doc().lines()[row - 1].len())
+        } else {
+            (row, col)
+        }
     }
 
-    pub fn doc(&self) -> &Document {
-        &self.doc
+    fn set_cursor(&mut self, pos: (usize, usize)) {
+        let win = self.window_mut();
+        win.cursor = pos;
+        win.pref_col = pos.1;
     }
 
-    pub fn cursor(&self) -> (usize, usize) {
-        self.cursor
+    #[cfg(feature = "tree-sitter")]
+    fn select_text_object(&mut self, object: &str, inner: bool) {
+        let cursor = self.cursor();
+        let range = self
+            .doc()
+            .syntax_tree()
+            .and_then(|tree| tree.text_object(object, inner, cursor));
+        match range {
+            Some((start, end)) => {
+                self.selection_history.clear();
+                self.select(start, end);
+            }
+            None => self.warn(format!("No {} here", object)),
+        }
     }
 
-    pub fn mode(&self) -> &Mode {
-        &self.mode
+    #[cfg(feature = "tree-sitter")]
+    fn expand_selection(&mut self) {
+        let Some((start, end)) = self.selection() else {
+            return;
+        };
+        let range = self
+            .doc()
+            .syntax_tree()
+            .and_then(|tree| tree.expand(start, end));
+        if let Some((new_start, new_end)) = range {
+            self.selection_history.push((start, end));
+            self.select(new_start, new_end);
+        }
     }
 
-    pub fn command_buffer(&self) -> &String {
-        &self.cmd_buf
+    #[cfg(feature = "tree-sitter")]
+    fn shrink_selection(&mut self) {
+        if let Some((start, end)) = self.selection_history.pop() {
+            self.select(start, end);
+        }
     }
 
-    pub fn status_msg(&self) -> &Option<String> {
-        &self.status_msg
+    #[cfg(feature = "tree-sitter")]
+    fn jump_function(&mut self, forward: bool) {
+        let cursor = self.cursor();
+        let target = self
+            .doc()
+            .syntax_tree()
+            .and_then(|tree| tree.function_start(cursor, forward));
+        match target {
+            Some(pos) => self.set_cursor(pos),
+            None => self.warn("No more functions"),
+        }
     }
+
+    fn handle_insert_mode(&mut self, key: KeyEvent, rect: &Rect) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if std::mem::take(&mut self.pending) == "^X" && ctrl {
+            let source = match key.code {
+                KeyCode::Char('o') => Some(Source::Lsp),
+                KeyCode::Char('n') => Some(Source::Buffer),
+                KeyCode::Char('f') => Some(Source::Path),
+                _ => None,
+            };
+            if let Some(source) = source {
+                self.start_completion(&[source], true);
+                if let Some(completion) = &mut self.completion {
+                    completion.select(1);
+                }
+                self.update_preview();
+                return;
+            }
+        }
+        if self.completion.is_some() && self.handle_completion_key(key) {
+            return;
+        }
+
+        let (row, col) = self.cursor();
+        let mut typed = None;
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+            }
+            KeyCode::Char('x') if ctrl => {
+                self.pending.push_str("^X");
+            }
+            KeyCode::Char(c @ ('n' | 'p')) if ctrl => {
+                let sources = self.options.complete.clone();
+                self.start_completion(&sources, true);
+                if let Some(completion) = &mut self.completion {
+                    completion.select(if c == 'n' { 1 } else { -1 });
+                }
+                self.update_preview();
+                return;
+            }
+            KeyCode::Char(c) => {
+                let _ = self.doc_mut().insert_char(row, col, c);
+                self.move_cursor(1, 0, rect);
+                typed = Some(c);
+            }
+            KeyCode::Backspace => {
+                if col > 0 {
+                    let _ = self.doc_mut().delete(row, col);
+                    self.move_cursor(-1, 0, rect);
+                } else if row > 0 {
+                    let prev_len = self.doc().lines()[row - 1].len();
+                    let _ = self.doc_mut().delete(row, 0);
+                    let win = self.window_mut();
+                    win.cursor = (row - 1, prev_len);
+                    win.pref_col = prev_len;
+                }
+            }
+            KeyCode::Enter => {
+                let _ = self.doc_mut().insert_newline(row, col);
+                let win = self.window_mut();
+                win.cursor = (row + 1, 0);
+                win.pref_col = 0;
+            }
+            KeyCode::Tab => {
+                for _ in 0..TAB_WIDTH {
+                    let (row, col) = self.cursor();
+                    let _ = self.doc_mut().insert_char(row, col, ' ');
+                    self.move_cursor(1, 0, rect)
+                }
+            }
+            _ => {}
+        }
+        self.refresh_completion(typed);
+    }
+
+    /// Handles a key while the completion popup is open, returning whether
+    /// it was used up.
+    fn handle_completion_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(completion) = &mut self.completion else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Char('n') if ctrl => completion.select(1),
+            KeyCode::Down => completion.select(1),
+            KeyCode::Char('p') if ctrl => completion.select(-1),
+            KeyCode::Up => completion.select(-1),
+            KeyCode::Char('y') if ctrl => self.accept_completion(),
+            KeyCode::Enter | KeyCode::Tab if completion.selected().is_some() => {
+                self.accept_completion()
+            }
+            KeyCode::Char('e') if ctrl => self.completion = None,
+            _ => return false,
+        }
+        self.update_preview();
+        true
+    }
+
+    /// Returns where the text being completed at the cursor starts, and
+    /// the directory it is in if it is a path to an existing directory.
+    fn completion_context(&self) -> ((usize, usize), Option<String>) {
+        let (row, col) = self.cursor();
+        let before: Vec<char> = self.doc().lines()[row].chars().take(col).collect();
+        let token: String = before
+            .iter()
+            .rev()
+            .take_while(|c| !c.is_whitespace() && !"\"'`()[]{}<>,;=".contains(**c))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .collect();
+        if let Some((dir, name)) = token.rsplit_once('/') {
+            let dir = format!("{}/", dir);
+            if completion::resolve_dir(&dir, &self.root).is_some() {
+                return ((row, col - name.chars().count()), Some(dir));
+            }
+        }
+
+        let word = before.iter().rev();
+        let len = word
+            .take_while(|c| c.is_alphanumeric() || **c == '_')
+            .count();
+        ((row, col - len), None)
+    }
+
+    /// Opens the completion popup with candidates from `sources` for the
+    /// text before the cursor. Path candidates are offered only for paths,
+    /// and only they are.
+    fn start_completion(&mut self, sources: &[Source], explicit: bool) {
+        let (start, dir) = self.completion_context();
+        let mut completion = Completion::new(start, self.options.complete.clone());
+        match dir {
+            Some(dir) if sources.contains(&Source::Path) => {
+                completion.add(completion::path_candidates(&dir, &self.root));
+            }
+            _ => {
+                if sources.contains(&Source::Buffer) {
+                    let buffer = self.window().buffer();
+                    completion.add(completion::buffer_words(&self.buffers, buffer, start));
+                }
+                // only asking for LSP completion says why there is none
+                if sources.contains(&Source::Lsp)
+                    && (sources == [Source::Lsp] && explicit
+                        || self.lsp_supports("completionProvider"))
+                {
+                    completion.waiting = self.complete_lsp(start);
+                }
+            }
+        }
+
+        let prefix = self.doc().text_range(start, self.cursor());
+        completion.filter(&prefix);
+        if completion.is_empty() && !completion.waiting {
+            self.completion = None;
+            if explicit {
+                self.warn("No completions");
+            }
+        } else {
+            self.completion = Some(completion);
+        }
+    }
+
+    /// Filters the open completion by the text typed since it opened,
+    /// closing it once the cursor leaves the completed text. Typing a
+    /// trigger opens it: `/` in a path, or a language server's trigger
+    /// characters.
+    fn refresh_completion(&mut self, typed: Option<char>) {
+        if self.mode != Mode::Insert {
+            self.completion = None;
+            return;
+        }
+
+        let (start, dir) = self.completion_context();
+        if let Some(completion) = &self.completion
+            && completion.start == start
+        {
+            let prefix = self.doc().text_range(start, self.cursor());
+            if let Some(completion) = &mut self.completion {
+                completion.filter(&prefix);
+                if completion.is_empty() && !completion.waiting {
+                    self.completion = None;
+                }
+            }
+            self.update_preview();
+            return;
+        }
+
+        self.completion = None;
+        if !self.options.autocomplete {
+            return;
+        }
+        match typed {
+            Some('/') if dir.is_some() && self.options.complete.contains(&Source::Path) => {
+                self.start_completion(&[Source::Path], false);
+            }
+            Some(c) if self.is_lsp_trigger(c) => {
+                self.start_completion(&[Source::Lsp], false);
+            }
+            _ => {}
+        }
+        self.update_preview();
+    }
+
+    /// Replaces the completed text with the selected candidate, as an undo
+    /// step of its own.
+    fn accept_completion(&mut self) {
+        let Some(completion) = self.completion.take() else {
+            return;
+        };
+        let Some(candidate) = completion.selected_candidate() else {
+            return;
+        };
+
+        let cursor = self.cursor();
+        let doc = self.doc_mut();
+        doc.commit_undo();
+        let result = doc.replace_range(completion.start, cursor, &candidate.text);
+        doc.commit_undo();
+        if let Ok(end) = result {
+            self.set_cursor(end);
+        }
+
+        // a directory is completed into next
+        if candidate.source == Source::Path
+            && candidate.text.ends_with('/')
+            && self.options.autocomplete
+        {
+            self.start_completion(&[Source::Path], false);
+        }
+    }
+
+    /// Shows the selected candidate in the completion preview.
+    fn update_preview(&mut self) {
+        if let Some(completion) = &mut self.completion {
+            completion.preview = match completion.selected_candidate() {
+                Some(candidate) => match &candidate.path {
+                    Some(path) => completion::path_preview(path, completion::PREVIEW_LINES),
+                    None => candidate.info.clone(),
+                },
+                None => Vec::new(),
+            };
+        }
+    }
+
+    fn handle_command_mode(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.mode = Mode::Normal;
+                self.cmd_buf.clear();
+            }
+            KeyCode::Enter => {
+                self.execute_command();
+                self.mode = Mode::Normal;
+                self.cmd_buf.clear();
+            }
+            KeyCode::Backspace => {
+                self.cmd_buf.pop();
+            }
+
+            KeyCode::Char(c) => {
+                self.cmd_buf.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    fn execute_command(&mut self) {
+        let cmd = self.cmd_buf.clone();
+        let (range, cmd) = match range::parse(&cmd, self.doc(), self.cursor().0) {
+            Ok(parsed) => parsed,
+            Err(e) => return self.error(e),
+        };
+        // line 0 only means something to `:r`, and is line 1 to the rest
+        let rows = range.map(|(start, end)| (start.max(1) - 1, end.max(1) - 1));
+        if let Some(shell_cmd) = cmd.strip_prefix('!') {
+            return match rows {
+                Some(rows) => self.filter_lines(rows, shell_cmd.trim()),
+                None => self.run_shell(shell_cmd.trim()),
+            };
+        }
+        let (name, arg) = match cmd.split_once(' ') {
+            Some((name, arg)) => (name, Some(arg.trim()).filter(|a| !a.is_empty())),
+            None => (cmd, None),
+        };
+        if let Some((_, end)) = range {
+            match (name, arg) {
+                // a range alone goes to its last line
+                ("", _) => {
+                    let row = end.max(1) - 1;
+                    let indent = self.doc().lines()[row]
+                        .chars()
+                        .take_while(|c| c.is_whitespace());
+                    let col = indent.count();
+                    self.set_cursor((row, col));
+                }
+                ("r" | "read", Some(arg)) => self.read_into_buffer(arg, end),
+                ("r" | "read", None) => self.error("Argument required"),
+                _ => self.error("No range allowed"),
+            }
+            return;
+        }
+
+        match name {
+            "" => {}
+            "q" => {
+                // close window, tab, then quit
+                if self.tab().windows().len() > 1 {
+                    self.close_window();
+                } else if self.tabs.len() > 1 {
+                    self.close_tab();
+                } else if self.unsaved() {
+                    self.error("No write since last change (add ! to override)");
+                } else {
+                    self.should_quit = true;
+                }
+            }
+            "q!" => {
+                self.should_quit = true;
+            }
+            "cq" | "cquit" => {
+                // quit with an error, so that whatever started the editor
+                // discards the result
+                self.should_quit = true;
+                self.aborted = true;
+            }
+            "s" | "s!" => {
+                // save, or save as
+                let buffer = self.window().buffer();
+                if name == "s!" {
+                    self.doc_mut().set_readonly(false);
+                }
+                // the formatter is the one of the file type of a new name
+                let format_error = self.format_on_save(buffer, arg.map(Path::new));
+                let old_path = self.doc().full_path().map(Path::to_path_buf);
+                let old_type = self.doc().file_type().map(str::to_string);
+                match self.doc_mut().save(arg) {
+                    Ok(()) => {
+                        if arg.is_some() {
+                            if let Some(path) = old_path {
+                                self.lsp_close_path(&path, old_type.as_deref());
+                            }
+                            self.detect_syntax(buffer);
+                            self.load_diff_base(buffer);
+                        }
+                        self.saved(buffer);
+                        let doc = self.doc();
+                        let written =
+                            format!("\"{}\" {}L written", doc.file_name(), doc.lines().len());
+                        self.info(written);
+                    }
+                    Err(e) => self.error(e.to_string()),
+                }
+                // shown last, so the message is not hidden by the one above
+                if let Some(e) = format_error {
+                    self.error(e);
+                }
+            }
+            "sq" => {
+                // save quit, staying to show why the formatter failed
+                let buffer = self.window().buffer();
+                let format_error = self.format_on_save(buffer, None);
+                match self.doc_mut().save(None) {
+                    Ok(()) => {
+                        self.saved(buffer);
+                        match format_error {
+                            Some(e) => self.error(e),
+                            None => self.should_quit = true,
+                        }
+                    }
+                    Err(e) => self.error(e.to_string()),
+                }
+            }
+            "set" | "se" => {
+                let truecolor = self.options.termguicolors;
+                for opt in options::split_args(arg.unwrap_or_default()) {
+                    let opt = opt.as_str();
+                    // the line ending belongs to the buffer, not the editor
+                    if let Some(("fileformat" | "ff", value)) = opt.split_once('=') {
+                        match value {
+                            "unix" => self.doc_mut().set_line_ending(LineEnding::Unix),
+                            "dos" => self.doc_mut().set_line_ending(LineEnding::Dos),
+                            _ => {
+                                self.error(format!("Invalid argument: {}", opt));
+                                break;
+                            }
+                        }
+                        continue;
+                    }
+                    if let Err(e) = self.options.set(opt) {
+                        self.error(e);
+                        break;
+                    }
+                }
+                if self.options.termguicolors != truecolor {
+                    let name = self.theme.name.clone();
+                    self.set_colorscheme(&name);
+                }
+            }
+            "colo" | "colorscheme" => match arg {
+                Some(name) => self.set_colorscheme(name),
+                None => self.info(self.theme.name.clone()),
+            },
+            "e!" => {
+                self.reload();
+            }
+            "u" | "undo" => {
+                self.undo();
+            }
+            "red" | "redo" => {
+                self.redo();
+            }
+            "mes" | "messages" => {
+                self.show_messages = true;
+            }
+            "sp" | "split" => {
+                self.split(arg, SplitDirection::Horizontal);
+            }
+            "vs" | "vsplit" => {
+                self.split(arg, SplitDirection::Vertical);
+            }
+            "tabnew" => {
+                self.new_tab(arg);
+            }
+            "tabn" | "tabnext" => {
+                self.next_tab();
+            }
+            "tabp" | "tabprevious" => {
+                self.prev_tab();
+            }
+            "tabc" | "tabclose" => {
+                if self.tabs.len() > 1 {
+                    self.close_tab();
+                } else {
+                    self.error("Cannot close last tab page");
+                }
+            }
+            "bn" | "bnext" => {
+                self.cycle_buffer(1);
+            }
+            "bp" | "bprevious" => {
+                self.cycle_buffer(-1);
+            }
+            "b" | "buffer" => match arg.and_then(|a| a.parse::<usize>().ok()) {
+                Some(n) if (1..=self.buffers.len()).contains(&n) => self.switch_buffer(n - 1),
+                Some(_) => self.error("Buffer does not exist"),
+                None => self.error("Buffer number required"),
+            },
+            "ls" | "buffers" => {
+                let active = self.window().buffer();
+                for i in 0..self.buffers.len() {
+                    let doc = &self.buffers[i];
+                    let line = format!(
+                        "{:>3}{} \"{}\"{}",
+                        i + 1,
+                        if i == active { " %" } else { "  " },
+                        doc.file_name(),
+                        if doc.dirty() { " [+]" } else { "" }
+                    );
+                    self.info(line);
+                }
+                self.show_messages = true;
+            }
+            "norm" | "normal" => match arg.map(parse_keys) {
+                Some(Ok(keys)) => {
+                    // run before anything queued after the command, ending
+                    // an unfinished command as Esc would
+                    let esc = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+                    for key in keys.into_iter().chain([esc]).rev() {
+                        self.replay.push_front(key);
+                    }
+                }
+                Some(Err(e)) => self.error(e),
+                None => self.error("Argument required"),
+            },
+            "hover" => {
+                self.hover();
+            }
+            "def" | "definition" => {
+                self.goto_definition();
+            }
+            "refs" | "references" => {
+                self.find_references();
+            }
+            "rename" => match arg {
+                Some(name) => self.rename(name),
+                None => self.error("Argument required"),
+            },
+            "ca" | "codeaction" => match arg {
+                Some(n) => self.run_code_action(n),
+                None => self.code_actions(),
+            },
+            "find" => {
+                self.open_finder(arg);
+            }
+            "Ex" | "explorer" => {
+                self.toggle_explorer();
+            }
+            "gr" | "grep" => match arg.map(grep::split_args) {
+                Some(Ok(args)) if !args.is_empty() => self.grep(&args[0], &args[1..]),
+                Some(Err(e)) => self.error(e),
+                _ => self.error("Argument required"),
+            },
+            "r" | "read" => match arg {
+                Some(arg) => self.read_into_buffer(arg, self.cursor().0 + 1),
+                None => self.error("Argument required"),
+            },
+            "fmt" | "format" => match self.format_buffer(self.window().buffer(), None) {
+                Ok(changed) => {
+                    let name = self.doc().file_name();
+                    let text = if changed {
+                        format!("\"{}\" formatted", name)
+                    } else {
+                        format!("\"{}\" already formatted", name)
+                    };
+                    self.info(text);
+                }
+                Err(e) => self.error(e),
+            },
+            "hunk" => {
+                self.preview_hunk();
+            }
+            "stagehunk" => {
+                self.stage_hunk();
+            }
+            "resethunk" => {
+                self.reset_hunk();
+            }
+            "diff" => {
+                self.diff_with_disk();
+            }
+            "blame" => {
+                self.toggle_blame();
+            }
+            "mak" | "make" => {
+                self.make(arg);
+            }
+            "cn" | "cnext" => {
+                self.step_quickfix(1);
+            }
+            "cp" | "cprev" | "cprevious" => {
+                self.step_quickfix(-1);
+            }
+            "cc" => match arg.map(|a| a.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 && self.quickfix.set_current(n - 1) => self.goto_quickfix(),
+                Some(_) => self.error("No such entry"),
+                None => self.goto_quickfix(),
+            },
+            "cope" | "copen" => {
+                self.quickfix.open = true;
+                self.quickfix.focused = true;
+            }
+            "ccl" | "cclose" => {
+                self.quickfix.open = false;
+                self.quickfix.focused = false;
+            }
+            _ => {
+                self.error(format!("Not an editor command: {}", cmd));
+            }
+        }
+    }
+
+    /// Returns the index of the buffer for `path`, opening it if it is not
+    /// already loaded.
+    pub fn open_buffer(&mut self, path: &str) -> io::Result<usize> {
+        if let Some(i) = self.buffer_at(Path::new(path)) {
+            return Ok(i);
+        }
+
+        let (doc, recovered) = match Document::open(path) {
+            Ok(doc) => (doc, false),
+            // a swap file no buffer here owns was left by an editor that
+            // went away with changes, unless it is still running
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                let swap = Document::swap_path_for(Path::new(path));
+                let canonical = swap.canonicalize()?;
+                if self.buffers.iter().any(|b| {
+                    b.swap_path()
+                        .is_some_and(|s| s.canonicalize().is_ok_and(|s| s == canonical))
+                }) {
+                    return Err(e);
+                }
+                match Document::recover_from_swap(&swap) {
+                    Ok((doc, _)) => (doc, true),
+                    Err(_) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+
+        self.buffers.push(doc);
+        if recovered {
+            self.warn(format!("\"{}\" recovered from swap file", path));
+        }
+        self.detect_syntax(self.buffers.len() - 1);
+        self.warn_readonly(self.buffers.len() - 1);
+        self.place_quickfix_signs(self.buffers.len() - 1);
+        self.load_diff_base(self.buffers.len() - 1);
+        Ok(self.buffers.len() - 1)
+    }
+
+    fn warn_readonly(&mut self, buffer: usize) {
+        let doc = &self.buffers[buffer];
+        if doc.readonly() {
+            let text = format!("\"{}\" [readonly]", doc.file_name());
+            self.warn(text);
+        }
+    }
+
+    fn set_colorscheme(&mut self, name: &str) {
+        match Theme::load(name, self.options.termguicolors) {
+            Ok(theme) => self.theme = theme,
+            Err(e) => self.error(e),
+        }
+    }
+
+    fn detect_syntax(&mut self, buffer: usize) {
+        let doc = &mut self.buffers[buffer];
+        let first_line = doc.lines()[0].to_string();
+        let grammar = self.syntax.detect(doc.full_path(), &first_line);
+        doc.set_grammar(grammar);
+    }
+
+    /// Resolves an optional file argument to a buffer index, falling back to
+    /// a new empty buffer.
+    fn buffer_for(&mut self, file: Option<&str>) -> Option<usize> {
+        match file {
+            Some(path) => match self.open_buffer(path) {
+                Ok(i) => Some(i),
+                Err(e) => {
+                    self.error(format!("\"{}\": {}", path, e));
+                    None
+                }
+            },
+            None => {
+                self.buffers.push(Document::new());
+                Some(self.buffers.len() - 1)
+            }
+        }
+    }
+
+    /// Splits the active window, showing `file` in the new one. Returns
+    /// whether the file could be opened.
+    fn split(&mut self, file: Option<&str>, direction: SplitDirection) -> bool {
+        let buffer = match file {
+            Some(_) => match self.buffer_for(file) {
+                Some(i) => i,
+                None => return false,
+            },
+            None => self.window().buffer(),
+        };
+
+        let mut window = Window::new(buffer);
+        if buffer == self.window().buffer() {
+            window.cursor = self.window().cursor;
+            window.pref_col = self.window().pref_col;
+            window.top = self.window().top;
+            window.hscroll = self.window().hscroll;
+        }
+        self.tab_mut().split(window, direction);
+        true
+    }
+
+    fn close_window(&mut self) {
+        if !self.tab_mut().close_window() && self.tabs.len() > 1 {
+            self.close_tab();
+        }
+    }
+
+    /// Opens a tab after the active one showing `file`. Returns whether the
+    /// file could be opened.
+    fn new_tab(&mut self, file: Option<&str>) -> bool {
+        let Some(buffer) = self.buffer_for(file) else {
+            return false;
+        };
+        self.tabs
+            .insert(self.active_tab + 1, Tab::new(Window::new(buffer)));
+        self.active_tab += 1;
+        true
+    }
+
+    /// Opens the files given on the command line after the first one, which
+    /// the editor was created with, as `open_in` says. The first file keeps
+    /// the focus.
+    pub fn open_files(&mut self, files: &[FileArg], open_in: OpenIn) {
+        for file in files {
+            let path = Some(file.path.as_str());
+            let shown = match open_in {
+                OpenIn::Buffers => {
+                    if let Some(buffer) = self.buffer_for(path)
+                        && let Some(jump) = &file.jump
+                    {
+                        // the position is taken up when the buffer is shown
+                        match jump.resolve(&self.buffers[buffer]) {
+                            Ok(pos) => self.buffers[buffer].set_mark('"', pos),
+                            Err(e) => self.error(e),
+                        }
+                    }
+                    false
+                }
+                OpenIn::Splits(direction) => self.split(path, direction),
+                OpenIn::Tabs => self.new_tab(path),
+            };
+            if shown && let Some(jump) = &file.jump {
+                self.jump_to(jump);
+            }
+        }
+
+        self.active_tab = 0;
+        self.tab_mut().set_active(0);
+    }
+
+    /// Moves the cursor of the active window to `jump`.
+    pub fn jump_to(&mut self, jump: &Jump) {
+        match jump.resolve(self.doc()) {
+            Ok(pos) => self.set_cursor(pos),
+            Err(e) => self.error(e),
+        }
+    }
+
+    /// Shows buffer `index` in the active window, where the cursor was when
+    /// it was last shown.
+    fn switch_buffer(&mut self, index: usize) {
+        let cursor = self.cursor();
+        self.doc_mut().set_mark('"', cursor);
+
+        *self.window_mut() = Window::new(index);
+        let lines = self.doc().lines();
+        let (row, col) = self.doc().mark('"').unwrap_or_default();
+        let row = row.min(lines.len() - 1);
+        let col = col.min(lines[row].len());
+        self.set_cursor((row, col));
+    }
+
+    /// Opens the fuzzy file finder over the project root, with `query`
+    /// typed in already.
+    fn open_finder(&mut self, query: Option<&str>) {
+        let mut finder = Finder::new(&self.root);
+        if let Some(query) = query {
+            finder.set_query(query);
+        }
+        self.finder = Some(finder);
+    }
+
+    /// Handles a key while the file finder is open: typing edits the query,
+    /// Enter opens the highlighted file in the active window, Ctrl-S, Ctrl-V
+    /// and Ctrl-T in a split, a vertical split or a tab.
+    fn handle_finder_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(finder) = &mut self.finder else {
+            return;
+        };
+        let open_in = match key.code {
+            KeyCode::Esc => {
+                self.finder = None;
+                return;
+            }
+            KeyCode::Char('n') if ctrl => return finder.select(1),
+            KeyCode::Down => return finder.select(1),
+            KeyCode::Char('p') if ctrl => return finder.select(-1),
+            KeyCode::Up => return finder.select(-1),
+            KeyCode::Backspace => return finder.pop(),
+            KeyCode::Enter => OpenIn::Buffers,
+            KeyCode::Char('s') if ctrl => OpenIn::Splits(SplitDirection::Horizontal),
+            KeyCode::Char('v') if ctrl => OpenIn::Splits(SplitDirection::Vertical),
+            KeyCode::Char('t') if ctrl => OpenIn::Tabs,
+            KeyCode::Char(c) if !ctrl => return finder.push(c),
+            _ => return,
+        };
+
+        let Some(file) = finder.selected_file() else {
+            return;
+        };
+        let path = finder.root.join(file).to_string_lossy().to_string();
+        self.finder = None;
+        match open_in {
+            OpenIn::Buffers => {
+                if let Some(buffer) = self.buffer_for(Some(&path)) {
+                    self.switch_buffer(buffer);
+                }
+            }
+            OpenIn::Splits(direction) => {
+                self.split(Some(&path), direction);
+            }
+            OpenIn::Tabs => {
+                self.new_tab(Some(&path));
+            }
+        }
+    }
+
+    /// Opens the file explorer sidebar on the project root, or closes it.
+    fn toggle_explorer(&mut self) {
+        if self.explorer.take().is_none() {
+            self.explorer = Some(Explorer::new(&self.root));
+        }
+    }
+
+    /// Keeps the temp files of the open buffers out of the file explorer.
+    fn sync_explorer(&mut self) {
+        let Some(explorer) = &mut self.explorer else {
+            return;
+        };
+        let temp = self
+            .buffers
+            .iter()
+            .filter_map(|doc| std::path::absolute(doc.temp_path()?).ok())
+            .collect();
+        explorer.hide(temp);
+    }
+
+    /// Moves the focus to the file explorer, opening it if it is closed.
+    fn focus_explorer(&mut self) {
+        match &mut self.explorer {
+            Some(explorer) => explorer.focused = true,
+            None => self.toggle_explorer(),
+        }
+    }
+
+    /// Handles a key while the file explorer has the focus: w/s move,
+    /// Enter or d opens a file or expands a directory, a collapses it, n
+    /// creates, r renames or moves and x deletes. Esc or Ctrl-E goes back
+    /// to the editor, q closes the explorer.
+    fn handle_explorer_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let Some(explorer) = &mut self.explorer else {
+            return;
+        };
+        if explorer.prompt.is_some() {
+            return self.handle_explorer_prompt(key);
+        }
+        match key.code {
+            KeyCode::Esc => explorer.focused = false,
+            KeyCode::Char('e') if ctrl => explorer.focused = false,
+            KeyCode::Char('q') => self.explorer = None,
+            KeyCode::Char('w') | KeyCode::Char('k') | KeyCode::Up => explorer.select(-1),
+            KeyCode::Char('s') | KeyCode::Char('j') | KeyCode::Down => explorer.select(1),
+            KeyCode::Char('a') | KeyCode::Left => explorer.collapse(),
+            KeyCode::Char('d') | KeyCode::Right | KeyCode::Enter => {
+                let Some(entry) = explorer.selected_entry() else {
+                    return;
+                };
+                if entry.is_dir {
+                    return explorer.toggle();
+                }
+                let path = entry.path.to_string_lossy().to_string();
+                explorer.focused = false;
+                if let Some(buffer) = self.buffer_for(Some(&path)) {
+                    self.switch_buffer(buffer);
+                }
+            }
+            KeyCode::Char('R') => explorer.refresh(),
+            KeyCode::Char('n') => {
+                explorer.prompt = Some(Prompt {
+                    action: Action::Create(explorer.target_dir()),
+                    input: String::new(),
+                });
+            }
+            KeyCode::Char('r') => {
+                if let Some(entry) = explorer.selected_entry() {
+                    let input = entry
+                        .path
+                        .strip_prefix(&explorer.root)
+                        .unwrap_or(&entry.path);
+                    explorer.prompt = Some(Prompt {
+                        input: input.to_string_lossy().to_string(),
+                        action: Action::Move(entry.path.clone()),
+                    });
+                }
+            }
+            KeyCode::Char('x') | KeyCode::Delete => {
+                if let Some(entry) = explorer.selected_entry() {
+                    let modified = buffers_under(&self.buffers, &entry.path)
+                        .into_iter()
+                        .filter(|&i| self.buffers[i].dirty())
+                        .count();
+                    explorer.prompt = Some(Prompt {
+                        action: Action::Delete(entry.path.clone(), modified),
+                        input: String::new(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles a key while the explorer asks for a name or a confirmation,
+    /// changing the files once it has what it asked for.
+    fn handle_explorer_prompt(&mut self, key: KeyEvent) {
+        let Some(explorer) = &mut self.explorer else {
+            return;
+        };
+        let Some(prompt) = &mut explorer.prompt else {
+            return;
+        };
+        if prompt.is_confirmation() {
+            let prompt = explorer.prompt.take();
+            if key.code != KeyCode::Char('y') {
+                return;
+            }
+            match prompt.map(|p| p.action) {
+                Some(Action::Delete(path, _)) => self.explorer_delete(&path),
+                Some(Action::Overwrite(from, to)) => self.explorer_move(&from, &to),
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            KeyCode::Esc => explorer.prompt = None,
+            KeyCode::Backspace => {
+                prompt.input.pop();
+            }
+            KeyCode::Char(c) => prompt.input.push(c),
+            KeyCode::Enter => {
+                let Some(Prompt { action, input }) = explorer.prompt.take() else {
+                    return;
+                };
+                if input.is_empty() {
+                    return;
+                }
+                match action {
+                    Action::Create(dir) => match explorer.create(&dir, &input) {
+                        Ok(path) => self.info(format!("Created {}", self.relative_path(&path))),
+                        Err(e) => self.error(format!("\"{}\": {}", input, e)),
+                    },
+                    Action::Move(from) => {
+                        let to = explorer.root.join(&input);
+                        if to == from {
+                            return;
+                        }
+                        if to.exists() {
+                            explorer.prompt = Some(Prompt {
+                                action: Action::Overwrite(from, to),
+                                input: String::new(),
+                            });
+                            return;
+                        }
+                        self.explorer_move(&from, &to);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves `from` to `to`, and the buffers showing files there along with
+    /// it.
+    fn explorer_move(&mut self, from: &Path, to: &Path) {
+        let Some(explorer) = &mut self.explorer else {
+            return;
+        };
+        if let Err(e) = explorer.rename(from, to) {
+            self.error(format!("\"{}\": {}", self.relative_path(from), e));
+            return;
+        }
+        for buffer in buffers_under(&self.buffers, from) {
+            let path = self.buffers[buffer].full_path().map(std::path::absolute);
+            let Some(Ok(path)) = path else {
+                continue;
+            };
+            let rest = path.strip_prefix(from).unwrap_or(&path);
+            // joining an empty path would add a trailing slash
+            let moved = if rest.as_os_str().is_empty() {
+                to.to_path_buf()
+            } else {
+                to.join(rest)
+            };
+            // the server opens it again under the new path on the next sync
+            self.lsp_close(buffer);
+            self.buffers[buffer].set_path(&moved);
+        }
+        let text = format!(
+            "Moved {} to {}",
+            self.relative_path(from),
+            self.relative_path(to)
+        );
+        self.info(text);
+    }
+
+    fn explorer_delete(&mut self, path: &Path) {
+        let Some(explorer) = &mut self.explorer else {
+            return;
+        };
+        if let Err(e) = explorer.delete(path) {
+            self.error(format!("\"{}\": {}", self.relative_path(path), e));
+            return;
+        }
+        // the buffers would write the files back, so they go too
+        for buffer in buffers_under(&self.buffers, path).into_iter().rev() {
+            self.close_buffer(buffer);
+        }
+        self.info(format!("Deleted {}", self.relative_path(path)));
+    }
+
+    /// Closes buffer `buffer`, showing another one in the windows that
+    /// showed it.
+    fn close_buffer(&mut self, buffer: usize) {
+        self.lsp_close(buffer);
+        self.buffers.remove(buffer);
+        if self.buffers.is_empty() {
+            self.buffers.push(Document::new());
+        }
+        let shown = buffer.min(self.buffers.len() - 1);
+        for tab in &mut self.tabs {
+            for window in tab.windows_mut() {
+                if window.buffer() == buffer {
+                    *window = Window::new(shown);
+                } else if window.buffer() > buffer {
+                    window.set_buffer(window.buffer() - 1);
+                }
+            }
+        }
+        self.output = match self.output {
+            Some(output) if output > buffer => Some(output - 1),
+            Some(output) if output == buffer => None,
+            output => output,
+        };
+        self.diff = match self.diff {
+            Some(diff) if diff > buffer => Some(diff - 1),
+            Some(diff) if diff == buffer => None,
+            diff => diff,
+        };
+        // a completion request answers for a buffer by its index
+        self.completion = None;
+    }
+
+    /// Searches the files under `paths` for `pattern`, filling the quickfix
+    /// list with the matches and jumping to the first.
+    fn grep(&mut self, pattern: &str, paths: &[String]) {
+        // the copies kept of open files would match as well
+        let temp: Vec<PathBuf> = self
+            .buffers
+            .iter()
+            .filter_map(|doc| std::path::absolute(doc.temp_path()?).ok())
+            .collect();
+        match grep::grep(pattern, paths, &self.root, &temp) {
+            Ok(entries) if entries.is_empty() => {
+                self.error(format!("No match: {}", pattern));
+            }
+            Ok(entries) => {
+                let title = format!("grep {}", pattern);
+                self.set_quickfix(Quickfix::new(title, entries));
+                self.goto_quickfix();
+            }
+            Err(e) => self.error(e),
+        }
+    }
+
+    /// Reads what buffer `buffer`'s file is in the git index, to mark the
+    /// lines changed from it.
+    fn load_diff_base(&mut self, buffer: usize) {
+        let doc = &mut self.buffers[buffer];
+        let base = doc.full_path().and_then(git::base_text);
+        doc.set_diff_base(base.map(|text| text.lines().map(str::to_string).collect()));
+    }
+
+    /// Brings the git signs of the buffers up to date with their text.
+    fn sync_git(&mut self) {
+        for doc in &mut self.buffers {
+            doc.update_hunks();
+        }
+    }
+
+    /// Returns the git hunk shown on the cursor line, if any.
+    fn hunk_at_cursor(&mut self) -> Option<Hunk> {
+        let row = self.cursor().0;
+        self.doc_mut().update_hunks();
+        let hunk = self
+            .doc()
+            .hunks()
+            .iter()
+            .find(|h| h.rows().contains(&row))
+            .cloned();
+        if hunk.is_none() {
+            self.error("No hunk on this line");
+        }
+        hunk
+    }
+
+    /// Moves the cursor to the start of the next or previous git hunk.
+    fn jump_hunk(&mut self, forward: bool) {
+        let row = self.cursor().0;
+        self.doc_mut().update_hunks();
+        let mut hunks = self.doc().hunks().iter().map(|h| h.rows().start);
+        let target = if forward {
+            hunks.find(|&start| start > row)
+        } else {
+            hunks.rev().find(|&start| start < row)
+        };
+        match target {
+            Some(start) => self.set_cursor((start, 0)),
+            None => self.error("No more hunks"),
+        }
+    }
+
+    /// Shows the lines of the hunk on the cursor line as they are in git
+    /// and in the buffer.
+    fn preview_hunk(&mut self) {
+        let Some(hunk) = self.hunk_at_cursor() else {
+            return;
+        };
+        let doc = self.doc();
+        let base = doc.diff_base().unwrap_or_default();
+        let mut lines = vec![diff::hunk_header(&hunk.old, &hunk.new)];
+        lines.extend(base[hunk.old.clone()].iter().map(|l| format!("-{}", l)));
+        lines.extend(
+            doc.lines()[hunk.new.clone()]
+                .iter()
+                .map(|l| format!("+{}", l)),
+        );
+        self.popup = Some(Popup::new("Hunk", lines));
+    }
+
+    /// Stages the hunk on the cursor line, as it is in the buffer, in the
+    /// git index.
+    fn stage_hunk(&mut self) {
+        let Some(hunk) = self.hunk_at_cursor() else {
+            return;
+        };
+        let doc = self.doc();
+        let Some(path) = doc.full_path() else {
+            return self.error("No file name");
+        };
+        let lines: Vec<String> = doc.lines()[hunk.new.clone()]
+            .iter()
+            .map(|l| l.to_string())
+            .collect();
+        match git::stage_hunk(path, &hunk, &lines) {
+            Ok(()) => {
+                self.load_diff_base(self.window().buffer());
+                self.info("Hunk staged");
+            }
+            Err(e) => self.error(e),
+        }
+    }
+
+    /// Puts the lines of the hunk on the cursor line back as they are in
+    /// git, as one undoable change.
+    fn reset_hunk(&mut self) {
+        let Some(hunk) = self.hunk_at_cursor() else {
+            return;
+        };
+        let doc = self.doc();
+        let base = doc.diff_base().unwrap_or_default();
+        let mut lines: Vec<String> = doc.lines().iter().map(|l| l.to_string()).collect();
+        lines.splice(hunk.new.clone(), base[hunk.old.clone()].iter().cloned());
+        let text = lines.join("\n");
+
+        let doc = self.doc_mut();
+        doc.commit_undo();
+        doc.set_text(&text);
+        doc.commit_undo();
+        let row = hunk.new.start.min(self.doc().lines().len() - 1);
+        self.set_cursor((row, 0));
+    }
+
+    /// Opens a window below showing what saving the buffer would change in
+    /// its file, as a unified diff. A later diff reuses its buffer and
+    /// window.
+    fn diff_with_disk(&mut self) {
+        let doc = self.doc();
+        let Some(path) = doc.full_path() else {
+            return self.error("No file name");
+        };
+        let disk = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => return self.error(format!("\"{}\": {}", path.display(), e)),
+        };
+        let disk: Vec<&str> = disk.lines().collect();
+        let lines: Vec<String> = doc.lines().iter().map(|l| l.to_string()).collect();
+        let hunks = diff::unified(&disk, &lines, 3);
+        if hunks.is_empty() {
+            return self.info("No changes");
+        }
+
+        let name = self.relative_path(path);
+        let mut text = vec![
+            format!("--- {} (on disk)", name),
+            format!("+++ {} (buffer)", name),
+        ];
+        text.extend(hunks);
+        let mut diff = Document::from_text(&text.join("\n"));
+        diff.set_grammar(self.syntax.get("diff"));
+        diff.set_readonly(true);
+        // an earlier diff is replaced, in the window that shows it
+        let Some(buffer) = self.diff else {
+            self.buffers.push(diff);
+            self.diff = Some(self.buffers.len() - 1);
+            let window = Window::new(self.buffers.len() - 1);
+            return self.tab_mut().split(window, SplitDirection::Horizontal);
+        };
+        self.buffers[buffer] = diff;
+        for tab in &mut self.tabs {
+            for window in tab.windows_mut() {
+                if window.buffer() == buffer {
+                    *window = Window::new(buffer);
+                }
+            }
+        }
+        match self
+            .tab()
+            .windows()
+            .iter()
+            .position(|w| w.buffer() == buffer)
+        {
+            Some(index) => self.tab_mut().set_active(index),
+            None => self
+                .tab_mut()
+                .split(Window::new(buffer), SplitDirection::Horizontal),
+        }
+    }
+
+    /// Shows or hides the commit, author and date of each line after it.
+    fn toggle_blame(&mut self) {
+        let buffer = self.window().buffer();
+        if self.doc().blame().is_some() {
+            self.doc_mut().set_blame(None);
+        } else {
+            self.load_blame(buffer);
+        }
+    }
+
+    fn load_blame(&mut self, buffer: usize) {
+        let doc = &self.buffers[buffer];
+        let Some(path) = doc.full_path() else {
+            return self.error("No file name");
+        };
+        let lines: Vec<String> = doc.lines().iter().map(|l| l.to_string()).collect();
+        match git::blame(path, &lines) {
+            Ok(blame) => self.buffers[buffer].set_blame(Some(blame)),
+            Err(e) => {
+                self.buffers[buffer].set_blame(None);
+                self.error(e);
+            }
+        }
+    }
+
+    /// Runs the formatter of the language of buffer `buffer` over its text
+    /// and edits in only the lines that changed, as one undoable change,
+    /// moving the cursors of its windows along. Returns whether the buffer
+    /// changed; the formatter's error output goes to the message history.
+    fn format_buffer(&mut self, buffer: usize, path: Option<&Path>) -> Result<bool, String> {
+        let doc = &self.buffers[buffer];
+        let language = self.language_for(buffer, path);
+        let config = language.as_deref().and_then(|l| self.languages.get(l));
+        let Some(formatter) = config.and_then(|c| c.formatter.clone()) else {
+            return Err(format!(
+                "No formatter for {}",
+                language.as_deref().unwrap_or("this buffer")
+            ));
+        };
+        let text = doc.text();
+
+        // the last line is ended as in a file, and the formatter's is taken
+        // off again
+        let mut command = process::Command::new(&formatter.command);
+        command.args(&formatter.args);
+        let output = shell::pipe(&mut command, Some(&format!("{}\n", text)))
+            .map_err(|e| format!("{}: {}", formatter.command, e))?;
+        if !output.success {
+            for line in output.stderr.lines() {
+                self.warn(line);
+            }
+            return Err(format!("{}: {}", formatter.command, shell_failure(&output)));
+        }
+        // a formatter writing nothing back would empty the buffer
+        if output.stdout.is_empty() {
+            return Err(format!("{}: no output", formatter.command));
+        }
+        let formatted = output.stdout.replace("\r\n", "\n");
+        let formatted = formatted.strip_suffix('\n').unwrap_or(&formatted);
+        if formatted == text {
+            return Ok(false);
+        }
+
+        let doc = &mut self.buffers[buffer];
+        doc.commit_undo();
+        let hunks = doc.set_text(formatted);
+        doc.commit_undo();
+        for tab in &mut self.tabs {
+            for window in tab.windows_mut() {
+                if window.buffer() == buffer {
+                    window.cursor.0 = diff::map_row(&hunks, window.cursor.0);
+                    window.anchor.0 = diff::map_row(&hunks, window.anchor.0);
+                }
+            }
+        }
+        self.clamp_cursors();
+        Ok(true)
+    }
+
+    /// Formats buffer `buffer` before it is written, if its language asks
+    /// for that. The buffer is written all the same if formatting fails, so
+    /// the error is returned for showing once it is. `path` is the new name
+    /// it is written under, if any, whose file type picks the language.
+    fn format_on_save(&mut self, buffer: usize, path: Option<&Path>) -> Option<String> {
+        let language = self.language_for(buffer, path);
+        let config = language.as_deref().and_then(|l| self.languages.get(l));
+        if self.buffers[buffer].readonly()
+            || !config.is_some_and(|c| c.auto_format && c.formatter.is_some())
+        {
+            return None;
+        }
+        self.format_buffer(buffer, path).err()
+    }
+
+    /// Returns the file type of buffer `buffer`, or the one it would have
+    /// under the name `path`.
+    fn language_for(&self, buffer: usize, path: Option<&Path>) -> Option<String> {
+        let doc = &self.buffers[buffer];
+        match path {
+            Some(path) => {
+                let first_line = doc.lines()[0].to_string();
+                let grammar = self.syntax.detect(Some(path), &first_line)?;
+                Some(grammar.name.clone())
+            }
+            None => doc.file_type().map(str::to_string),
+        }
+    }
+
+    /// Runs the shell command `cmd` and shows what it wrote in the message
+    /// history.
+    fn run_shell(&mut self, cmd: &str) {
+        if cmd.is_empty() {
+            return self.error("Argument required");
+        }
+        let output = match shell::run(cmd, None) {
+            Ok(output) => output,
+            Err(e) => return self.error(format!("{}: {}", cmd, e)),
+        };
+        for line in output.stdout.lines() {
+            self.info(line);
+        }
+        for line in output.stderr.lines() {
+            self.warn(line);
+        }
+        if !output.success {
+            self.error(shell_failure(&output));
+        }
+        self.show_messages = !(output.stdout.is_empty() && output.stderr.is_empty());
+    }
+
+    /// Inserts the output of `!cmd`, or the text of file `arg`, below line
+    /// `line` (counting from 1, so 0 is the top) as one undoable change.
+    fn read_into_buffer(&mut self, arg: &str, line: usize) {
+        let text = match arg.strip_prefix('!') {
+            Some(cmd) => match shell::run(cmd.trim(), None) {
+                Ok(output) if output.success => output.stdout,
+                Ok(output) => return self.error(shell_failure(&output)),
+                Err(e) => return self.error(format!("{}: {}", cmd.trim(), e)),
+            },
+            None => match std::fs::read_to_string(arg) {
+                Ok(text) => text,
+                Err(e) => return self.error(format!("\"{}\": {}", arg, e)),
+            },
+        };
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        if text.is_empty() {
+            return;
+        }
+
+        // line 0 puts the text above the first line
+        let (row, col, text) = match line.checked_sub(1) {
+            Some(row) => (row, self.doc().lines()[row].len(), format!("\n{}", text)),
+            None => (0, 0, format!("{}\n", text)),
+        };
+        let doc = self.doc_mut();
+        doc.commit_undo();
+        match doc.insert_str(row, col, &text) {
+            Ok(_) => {
+                doc.commit_undo();
+                self.set_cursor((line, 0));
+            }
+            Err(e) => self.error(format!("{:?}", e)),
+        }
+    }
+
+    /// Pipes rows `start..=end` through the shell command `cmd` and puts
+    /// its output in their place as one undoable change. The lines are
+    /// kept if the command fails.
+    fn filter_lines(&mut self, (start, end): (usize, usize), cmd: &str) {
+        if cmd.is_empty() {
+            return self.error("Argument required");
+        }
+        let lines = &self.doc().lines()[start..=end];
+        let input: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+        let output = match shell::run(cmd, Some(&input)) {
+            Ok(output) if output.success => output.stdout,
+            Ok(output) => return self.error(shell_failure(&output)),
+            Err(e) => return self.error(format!("{}: {}", cmd, e)),
+        };
+        let text = output.strip_suffix('\n').unwrap_or(&output);
+
+        let lines = self.doc().lines();
+        let (mut from, mut to) = ((start, 0), (end, lines[end].len()));
+        // no output deletes the lines along with a line break
+        if text.is_empty() {
+            if end + 1 < lines.len() {
+                to = (end + 1, 0);
+            } else if start > 0 {
+                from = (start - 1, lines[start - 1].len());
+            }
+        }
+        let doc = self.doc_mut();
+        doc.commit_undo();
+        match doc.replace_range(from, to, text) {
+            Ok(_) => {
+                doc.commit_undo();
+                let row = start.min(self.doc().lines().len() - 1);
+                self.set_cursor((row, 0));
+                self.info(format!("{} lines filtered", end - start + 1));
+            }
+            Err(e) => self.error(format!("{:?}", e)),
+        }
+    }
+
+    /// Handles the key after `!` and the motion keys typed so far: once
+    /// the motion is complete, the command line opens with the lines it
+    /// moved over as range, ready for the filter command. `!!` takes the
+    /// cursor line alone, `!G` the lines to the end of the buffer, and a
+    /// count before the motion repeats it, as in `!3j`.
+    fn filter_motion(&mut self, motion: &str, key: KeyEvent, rect: &Rect) {
+        let origin = self.cursor();
+        let last = self.doc().lines().len() - 1;
+        let digits = motion.len()
+            - motion
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let (count, motion) = motion.split_at(digits);
+        let times: Option<usize> = count.parse().ok();
+        match (motion, key.code) {
+            ("", KeyCode::Char(c)) if c.is_ascii_digit() && (c != '0' || times.is_some()) => {
+                self.pending = format!("!{}{}", count, c);
+                return;
+            }
+            // a count with `!!` takes that many lines from the cursor on
+            ("", KeyCode::Char('!')) => {
+                let n = times.unwrap_or(1);
+                self.set_cursor(((origin.0 + n - 1).min(last), 0));
+            }
+            // `G` goes to the last line, or to the line of its count
+            ("", KeyCode::Char('G')) => {
+                let row = times.map_or(last, |n| (n - 1).min(last));
+                self.set_cursor((row, 0));
+            }
+            ("", KeyCode::Char('w' | 'a' | 's' | 'd' | 'j' | 'k')) => {
+                for _ in 0..times.unwrap_or(1) {
+                    self.handle_normal_mode(key, rect);
+                }
+            }
+            ("", KeyCode::Char('g' | '\'' | ']' | '[')) => self.handle_normal_mode(key, rect),
+            ("", _) => return,
+            (motion, _) => {
+                for _ in 0..times.unwrap_or(1) {
+                    self.handle_pending(motion, key, rect);
+                }
+            }
+        }
+        // the motion goes on with another key, which gets the count too
+        if !self.pending.is_empty() {
+            self.pending.insert_str(0, &format!("!{}", count));
+            return;
+        }
+
+        let row = self.cursor().0;
+        self.set_cursor(origin);
+        self.mode = Mode::Command;
+        self.cmd_buf = match row as isize - origin.0 as isize {
+            0 => ".!".to_string(),
+            n if n > 0 => format!(".,.+{}!", n),
+            n => format!(".{},.!", n),
+        };
+    }
+
+    /// Starts the build command `makeprg` with `args` added in the project
+    /// root. It runs in the background, and once it is done `poll_make`
+    /// fills the quickfix list with the errors and warnings it reported and
+    /// jumps to the first.
+    fn make(&mut self, args: Option<&str>) {
+        if self.build.is_some() {
+            return self.error("A build is already running");
+        }
+        let efm = match ErrorFormat::parse(&self.options.errorformat) {
+            Ok(efm) => efm,
+            Err(e) => return self.error(e),
+        };
+        let words = make::command_line(&self.options.makeprg, args);
+        self.info(format!("Running {}", words.join(" ")));
+        self.build = Some((Build::start(words, &self.root), efm));
+    }
+
+    /// Fills the quickfix list once the build started by `:make` is done,
+    /// returning whether it was.
+    pub fn poll_make(&mut self, area: &Rect) -> bool {
+        let Some(result) = self.build.as_ref().and_then(|(build, _)| build.poll()) else {
+            return false;
+        };
+        self.finish_make(result);
+        self.scroll_to_cursor(self.tab().active(), area);
+        true
+    }
+
+    /// Waits for the build started by `:make`, if there is one, and fills
+    /// the quickfix list.
+    fn wait_for_make(&mut self) {
+        if let Some((build, _)) = &self.build {
+            let result = build.wait();
+            self.finish_make(result);
+        }
+    }
+
+    /// Fills the quickfix list from the `result` of the running build.
+    fn finish_make(&mut self, result: io::Result<(bool, String)>) {
+        let Some((Build { words, .. }, efm)) = self.build.take() else {
+            return;
+        };
+        let (success, output) = match result {
+            Ok(result) => result,
+            Err(e) => return self.error(format!("{}: {}", words.join(" "), e)),
+        };
+
+        let entries = make::parse_output(&output, &efm);
+        let found = !entries.is_empty();
+        self.set_quickfix(Quickfix::new(words.join(" "), entries));
+        if found {
+            self.goto_quickfix();
+        } else if success {
+            self.info("Build succeeded");
+        } else {
+            self.error(format!("{} failed", words.join(" ")));
+        }
+    }
+
+    /// Replaces the quickfix list, keeping its panel as it was, and marks
+    /// the rows the new one points at.
+    fn set_quickfix(&mut self, mut quickfix: Quickfix) {
+        quickfix.open = self.quickfix.open;
+        quickfix.focused = self.quickfix.focused;
+        self.quickfix = quickfix;
+        for buffer in 0..self.buffers.len() {
+            self.place_quickfix_signs(buffer);
+        }
+    }
+
+    /// Places the quickfix signs on buffer `buffer`, for the entries with
+    /// a severity pointing into its file.
+    fn place_quickfix_signs(&mut self, buffer: usize) {
+        let doc = &self.buffers[buffer];
+        let Some(path) = doc.full_path().and_then(|p| std::path::absolute(p).ok()) else {
+            return;
+        };
+        let rows: Vec<(usize, Severity)> = self
+            .quickfix
+            .entries()
+            .iter()
+            .filter(|e| std::path::absolute(self.root.join(&e.path)).is_ok_and(|p| p == path))
+            .filter_map(|e| Some((e.pos.0, e.severity?)))
+            .collect();
+        self.buffers[buffer].set_quickfix_signs(&rows);
+    }
+
+    /// Moves to the quickfix entry `delta` places on the current one.
+    fn step_quickfix(&mut self, delta: isize) {
+        if self.quickfix.is_empty() {
+            self.error("No entries");
+        } else if !self.quickfix.step(delta) {
+            self.error("No more items");
+        } else {
+            self.goto_quickfix();
+        }
+    }
+
+    /// Shows the file of the current quickfix entry with the cursor on it.
+    fn goto_quickfix(&mut self) {
+        let Some(entry) = self.quickfix.current_entry().cloned() else {
+            self.error("No entries");
+            return;
+        };
+        let path = self.root.join(&entry.path);
+        if !self.show_file(&path) {
+            return;
+        }
+        let pos = self.clamp_pos(entry.pos);
+        self.set_cursor(pos);
+        let text = format!(
+            "({} of {}): {}",
+            self.quickfix.current() + 1,
+            self.quickfix.len(),
+            entry.text
+        );
+        self.info(text);
+    }
+
+    /// Handles a key while the quickfix panel has the focus: w/s move,
+    /// Enter jumps to the entry, Esc goes back to the editor and q closes
+    /// the panel.
+    fn handle_quickfix_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.quickfix.focused = false,
+            KeyCode::Char('q') => {
+                self.quickfix.open = false;
+                self.quickfix.focused = false;
+            }
+            KeyCode::Char('w') | KeyCode::Char('k') | KeyCode::Up => {
+                self.quickfix.step(-1);
+            }
+            KeyCode::Char('s') | KeyCode::Char('j') | KeyCode::Down => {
+                self.quickfix.step(1);
+            }
+            KeyCode::Enter => {
+                self.quickfix.focused = false;
+                self.goto_quickfix();
+            }
+            _ => {}
+        }
+    }
+
+    /// Shows the buffer `delta` places after the active one, wrapping
+    /// around.
+    fn cycle_buffer(&mut self, delta: isize) {
+        let n = self.buffers.len() as isize;
+        let index = (self.window().buffer() as isize + delta).rem_euclid(n);
+        self.switch_buffer(index as usize);
+    }
+
+    /// Sends what changed in the buffers to their language servers, starting
+    /// a server the first time a buffer of its language needs it.
+    fn sync_lsp(&mut self) {
+        if !self.options.lsp {
+            return;
+        }
+
+        let mut errors = Vec::new();
+        for doc in &mut self.buffers {
+            let (Some(path), Some(language)) = (doc.full_path(), doc.file_type()) else {
+                continue;
+            };
+            let (path, language) = (path.to_path_buf(), language.to_string());
+            let config = self.languages.get(&language);
+            let command = config.and_then(|c| c.language_server.as_ref());
+            let server = match self.lsp.server(&language, command, &self.root) {
+                Ok(Some(server)) if server.is_ready() => server,
+                Ok(_) => continue,
+                // a server that is not installed is not worth a message
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    let name = command.map(|c| c.command.as_str()).unwrap_or(&language);
+                    errors.push(format!("{}: {}", name, e));
+                    continue;
+                }
+            };
+
+            if server.is_open(&path) {
+                let edits = doc.take_edits();
+                if !edits.is_empty() {
+                    server.did_change(&path, doc, edits);
+                }
+            } else {
+                doc.track_edits();
+                doc.take_edits();
+                server.did_open(&path, doc);
+            }
+        }
+        for e in errors {
+            self.error(e);
+        }
+    }
+
+    /// Handles what the language servers sent since the last call, returning
+    /// whether anything did.
+    pub fn poll_lsp(&mut self, area: &Rect) -> bool {
+        self.sync_lsp();
+        let events = self.lsp.poll();
+        if events.is_empty() {
+            return false;
+        }
+
+        for (language, event) in events {
+            self.handle_lsp_event(&language, event);
+        }
+        self.sync_lsp();
+        self.scroll_to_cursor(self.tab().active(), area);
+        true
+    }
+
+    fn handle_lsp_event(&mut self, language: &str, event: Event) {
+        match event {
+            Event::Response { result: Err(e), .. } => self.error(format!("{}: {}", language, e)),
+            Event::Response {
+                request,
+                result: Ok(result),
+            } => self.handle_lsp_response(request, result),
+            Event::Diagnostics { path, diagnostics } => {
+                if let Some(buffer) = self.buffer_at(&path) {
+                    let doc = &mut self.buffers[buffer];
+                    let diagnostics = diagnostics
+                        .iter()
+                        .filter_map(|d| lsp::to_diagnostic(doc, d))
+                        .collect();
+                    doc.set_diagnostics(diagnostics);
+                }
+            }
+            Event::ApplyEdit(edit) => self.apply_workspace_edit(&edit),
+            Event::Message { text, severity } => self.notify(text, severity),
+            Event::Exited => {
+                self.warn(format!("Language server for {} exited", language));
+                self.lsp.remove(language);
+            }
+        }
+    }
+
+    fn handle_lsp_response(&mut self, request: Request, result: Value) {
+        match request {
+            Request::Hover => {
+                let text = lsp::hover_text(&result);
+                if text.is_empty() {
+                    self.info("No hover information");
+                } else {
+                    let lines = text.lines().map(str::to_string).collect();
+                    self.popup = Some(Popup::new("Hover", lines));
+                }
+            }
+            Request::Definition => match lsp::locations(&result).first() {
+                Some((path, position)) => self.goto_location(path, position),
+                None => self.warn("No definition found"),
+            },
+            Request::References => {
+                let root = &self.root;
+                let lines: Vec<String> = lsp::locations(&result)
+                    .iter()
+                    .map(|(path, position)| {
+                        format!(
+                            "{}:{}:{}",
+                            path.strip_prefix(root).unwrap_or(path).display(),
+                            position["line"].as_u64().unwrap_or_default() + 1,
+                            position["character"].as_u64().unwrap_or_default() + 1
+                        )
+                    })
+                    .collect();
+                if lines.is_empty() {
+                    self.warn("No references found");
+                } else {
+                    self.popup = Some(Popup::new("References", lines));
+                }
+            }
+            Request::Rename => self.apply_workspace_edit(&result),
+            Request::Completion { buffer, start } => {
+                if self.window().buffer() != buffer {
+                    return;
+                }
+                let prefix = self.doc().text_range(start, self.cursor());
+                if let Some(completion) = &mut self.completion
+                    && completion.waiting
+                    && completion.start == start
+                {
+                    completion.waiting = false;
+                    completion.add(completion::lsp_candidates(&result));
+                    completion.filter(&prefix);
+                    if completion.is_empty() {
+                        self.completion = None;
+                    }
+                    self.update_preview();
+                }
+            }
+            Request::CodeAction => {
+                self.code_actions = result.as_array().cloned().unwrap_or_default();
+                let lines: Vec<String> = self
+                    .code_actions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, action)| {
+                        format!(
+                            "{}. {}",
+                            i + 1,
+                            action["title"].as_str().unwrap_or_default()
+                        )
+                    })
+                    .collect();
+                if lines.is_empty() {
+                    self.info("No code actions");
+                } else {
+                    self.popup = Some(Popup::new("Code actions (:ca N)", lines));
+                }
+            }
+            Request::Initialize | Request::Shutdown | Request::ExecuteCommand => {}
+        }
+    }
+
+    /// Returns the buffer holding the file at `path`.
+    fn buffer_at(&self, path: &Path) -> Option<usize> {
+        let absolute = std::path::absolute(path).ok();
+        let canonical = path.canonicalize().ok();
+        self.buffers.iter().position(|b| {
+            b.full_path().is_some_and(|p| {
+                (absolute.is_some() && std::path::absolute(p).ok() == absolute)
+                    || (canonical.is_some() && p.canonicalize().ok() == canonical)
+            })
+        })
+    }
+
+    /// Shows the file at `path` in the active window, with the cursor at the
+    /// LSP `position`.
+    fn goto_location(&mut self, path: &Path, position: &Value) {
+        if !self.show_file(path) {
+            return;
+        }
+        if let Some(pos) = lsp::from_position(self.doc(), position) {
+            self.set_cursor(pos);
+        }
+    }
+
+    /// Shows the file at `path` in the active window, in the buffer already
+    /// holding it if there is one. Returns whether the file could be opened.
+    fn show_file(&mut self, path: &Path) -> bool {
+        let buffer = match self.buffer_at(path) {
+            Some(buffer) => buffer,
+            None => match self.open_buffer(&path.to_string_lossy()) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    self.error(format!("\"{}\": {}", path.display(), e));
+                    return false;
+                }
+            },
+        };
+        if buffer != self.window().buffer() {
+            self.switch_buffer(buffer);
+        }
+        true
+    }
+
+    /// Applies an LSP workspace edit, each file's edits as one undo step.
+    fn apply_workspace_edit(&mut self, edit: &Value) {
+        let files = lsp::workspace_edits(edit);
+        let mut count = 0;
+        for (path, edits) in &files {
+            let buffer = match self.buffer_at(path) {
+                Some(buffer) => buffer,
+                None => match self.open_buffer(&path.to_string_lossy()) {
+                    Ok(buffer) => buffer,
+                    Err(e) => {
+                        self.error(format!("\"{}\": {}", path.display(), e));
+                        continue;
+                    }
+                },
+            };
+
+            let doc = &mut self.buffers[buffer];
+            let mut edits: Vec<_> = edits
+                .iter()
+                .filter_map(|e| {
+                    let text = e["newText"].as_str()?.to_string();
+                    Some((lsp::from_range(doc, &e["range"])?, text))
+                })
+                .collect();
+            // the ranges are all in the text before the edit, so the last
+            // one is applied first
+            edits.sort_by_key(|((start, _), _)| *start);
+            doc.commit_undo();
+            for ((start, end), text) in edits.into_iter().rev() {
+                if doc.replace_range(start, end, &text).is_ok() {
+                    count += 1;
+                }
+            }
+            doc.commit_undo();
+        }
+        self.clamp_cursors();
+        if count > 0 {
+            self.info(format!("{} changes in {} files", count, files.len()));
+        }
+    }
+
+    /// Returns the parameters of a request about the cursor position.
+    fn lsp_params(&self) -> Value {
+        let path = self.doc().full_path().unwrap_or(Path::new(""));
+        json!({
+            "textDocument": { "uri": lsp::path_to_uri(path) },
+            "position": lsp::to_position(self.doc(), self.cursor()),
+        })
+    }
+
+    /// Sends the request `method` to the language server of the current
+    /// buffer, if it has one that announced `provider`.
+    fn request_lsp(
+        &mut self,
+        provider: &str,
+        method: &str,
+        request: Request,
+        params: Value,
+    ) -> bool {
+        let doc = self.doc();
+        let language = match (doc.full_path(), doc.file_type()) {
+            (Some(_), Some(language)) => language.to_string(),
+            _ => {
+                self.error("No language server for this buffer");
+                return false;
+            }
+        };
+
+        self.sync_lsp();
+        let error = match self.lsp.get_mut(&language) {
+            Some(server) if !server.is_ready() => format!("{} is still starting", server.name()),
+            Some(server) if !server.supports(provider) => {
+                format!("{} does not support {}", server.name(), method)
+            }
+            Some(server) => {
+                server.request(method, params, request);
+                return true;
+            }
+            None => format!("No language server for {}", language),
+        };
+        self.error(error);
+        false
+    }
+
+    fn hover(&mut self) {
+        let params = self.lsp_params();
+        self.request_lsp(
+            "hoverProvider",
+            "textDocument/hover",
+            Request::Hover,
+            params,
+        );
+    }
+
+    fn goto_definition(&mut self) {
+        let params = self.lsp_params();
+        self.request_lsp(
+            "definitionProvider",
+            "textDocument/definition",
+            Request::Definition,
+            params,
+        );
+    }
+
+    fn find_references(&mut self) {
+        let mut params = self.lsp_params();
+        params["context"] = json!({ "includeDeclaration": true });
+        self.request_lsp(
+            "referencesProvider",
+            "textDocument/references",
+            Request::References,
+            params,
+        );
+    }
+
+    fn rename(&mut self, name: &str) {
+        let mut params = self.lsp_params();
+        params["newName"] = json!(name);
+        self.request_lsp(
+            "renameProvider",
+            "textDocument/rename",
+            Request::Rename,
+            params,
+        );
+    }
+
+    /// Asks for the code actions at the cursor, passing the diagnostics on
+    /// its line.
+    fn code_actions(&mut self) {
+        let params = self.lsp_params();
+        let doc = self.doc();
+        let (row, _) = self.cursor();
+        let diagnostics: Vec<Value> = doc
+            .diagnostics()
+            .iter()
+            .filter(|d| (d.start.0..=d.end.0).contains(&row))
+            .map(|d| lsp::from_diagnostic(doc, d))
+            .collect();
+        let params = json!({
+            "textDocument": params["textDocument"],
+            "range": { "start": params["position"], "end": params["position"] },
+            "context": { "diagnostics": diagnostics },
+        });
+        self.request_lsp(
+            "codeActionProvider",
+            "textDocument/codeAction",
+            Request::CodeAction,
+            params,
+        );
+    }
+
+    /// Applies code action `arg`, numbered as the last `:ca` listed them.
+    fn run_code_action(&mut self, arg: &str) {
+        let action = arg
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| self.code_actions.get(n.checked_sub(1)?))
+            .cloned();
+        let Some(action) = action else {
+            self.error("No such code action");
+            return;
+        };
+
+        // an action is either a command, or an edit and a command to run
+        // after it
+        if action["command"].is_string() {
+            self.execute_lsp_command(&action);
+            return;
+        }
+        if action["edit"].is_object() {
+            self.apply_workspace_edit(&action["edit"]);
+        }
+        if action["command"].is_object() {
+            self.execute_lsp_command(&action["command"]);
+        }
+    }
+
+    fn execute_lsp_command(&mut self, command: &Value) {
+        let mut params = json!({ "command": command["command"] });
+        if let Some(arguments) = command.get("arguments") {
+            params["arguments"] = arguments.clone();
+        }
+        self.request_lsp(
+            "executeCommandProvider",
+            "workspace/executeCommand",
+            Request::ExecuteCommand,
+            params,
+        );
+    }
+
+    /// Asks for completions of the text at `start`, returning whether the
+    /// request was sent.
+    fn complete_lsp(&mut self, start: (usize, usize)) -> bool {
+        let mut params = self.lsp_params();
+        params["context"] = json!({ "triggerKind": 1 });
+        let request = Request::Completion {
+            buffer: self.window().buffer(),
+            start,
+        };
+        self.request_lsp(
+            "completionProvider",
+            "textDocument/completion",
+            request,
+            params,
+        )
+    }
+
+    /// Returns whether the language server of the current buffer is ready
+    /// and announced `provider`.
+    fn lsp_supports(&mut self, provider: &str) -> bool {
+        self.sync_lsp();
+        let language = self.doc().file_type().map(str::to_string);
+        language
+            .and_then(|l| self.lsp.get_mut(&l))
+            .is_some_and(|server| server.is_ready() && server.supports(provider))
+    }
+
+    /// Returns whether typing `c` should ask the language server of the
+    /// current buffer for completions.
+    fn is_lsp_trigger(&mut self, c: char) -> bool {
+        let language = self.doc().file_type().map(str::to_string);
+        self.lsp_supports("completionProvider")
+            && language
+                .and_then(|l| self.lsp.get_mut(&l))
+                .is_some_and(|server| server.is_trigger(c))
+    }
+
+    /// Tells the language server of `buffer` that it was written, and
+    /// reloads its blame if that is shown.
+    fn saved(&mut self, buffer: usize) {
+        if self.buffers[buffer].blame().is_some() {
+            self.load_blame(buffer);
+        }
+        self.sync_lsp();
+        let doc = &self.buffers[buffer];
+        if let (Some(path), Some(language)) = (doc.full_path(), doc.file_type())
+            && let Some(server) = self.lsp.get_mut(language)
+            && server.is_open(path)
+        {
+            server.did_save(path, doc);
+        }
+    }
+
+    /// Tells the language server of `buffer` that it no longer edits the
+    /// file at its path. `sync_lsp` opens it again under its new one.
+    fn lsp_close(&mut self, buffer: usize) {
+        let doc = &self.buffers[buffer];
+        if let Some(path) = doc.full_path().map(Path::to_path_buf) {
+            let language = doc.file_type().map(str::to_string);
+            self.lsp_close_path(&path, language.as_deref());
+        }
+    }
+
+    /// Tells the language server of `language` that it no longer edits the
+    /// file at `path`, as `lsp_close` does for a buffer that already left it.
+    fn lsp_close_path(&mut self, path: &Path, language: Option<&str>) {
+        if let Some(language) = language
+            && let Some(server) = self.lsp.get_mut(language)
+            && server.is_open(path)
+        {
+            server.did_close(path);
+        }
+    }
+
+    fn next_tab(&mut self) {
+        self.active_tab = (self.active_tab + 1) % self.tabs.len();
+    }
+
+    fn prev_tab(&mut self) {
+        self.active_tab = (self.active_tab + self.tabs.len() - 1) % self.tabs.len();
+    }
+
+    fn close_tab(&mut self) {
+        self.tabs.remove(self.active_tab);
+        if self.active_tab >= self.tabs.len() {
+            self.active_tab = self.tabs.len() - 1;
+        }
+    }
+
+    /// Moves the cursor by `dx` chars or by `dy` screen rows, so a wrapped
+    /// line is walked through one row at a time.
+    fn move_cursor(&mut self, dx: i32, dy: i32, rect: &Rect) {
+        let width = rect.width as usize;
+        let (mut row, mut col) = self.cursor();
+        let mut pref_col = self.window().pref_col;
+        let lines = self.doc().lines();
+
+        if dx != 0 {
+            let dx = dx.signum();
+            if dx < 0 {
+                if col > 0 {
+                    col -= 1;
+                } else if row > 0 {
+                    row -= 1;
+                    col = lines[row].len();
+                }
+            } else {
+                let len = lines[row].len();
+                if col < len {
+                    col += 1;
+                } else if row + 1 < lines.len() {
+                    row += 1;
+                    col = 0;
+                }
+            }
+            pref_col = col;
+        }
+
+        if dy != 0 {
+            ((row, col), pref_col) = layout::screen_motion(
+                lines,
+                (row, col),
+                pref_col,
+                dy.signum() as isize,
+                width,
+                &self.options,
+            );
+        }
+
+        let win = self.window_mut();
+        win.cursor = (row, col);
+        win.pref_col = pref_col;
+    }
+
+    /// Moves the cursor by `dy` lines, however many screen rows they take.
+    fn move_line(&mut self, dy: i32) {
+        let lines = self.doc().lines();
+        let row = (self.cursor().0 as i32 + dy).clamp(0, lines.len() as i32 - 1) as usize;
+        let col = self.window().pref_col.min(lines[row].len());
+        self.window_mut().cursor = (row, col);
+    }
+
+    pub fn gutter(&self, window: &Window) -> Gutter {
+        Gutter::new(&self.options, &self.buffers[window.buffer()])
+    }
+
+    /// Returns the part of `window`'s `rect` to the right of its gutter.
+    pub fn text_rect(&self, window: &Window, rect: &Rect) -> Rect {
+        let gutter = (self.gutter(window).width() as u16).min(rect.width.saturating_sub(1));
+        Rect {
+            x: rect.x + gutter,
+            width: rect.width - gutter,
+            ..*rect
+        }
+    }
+
+    /// Lays out `window`'s buffer in the text area of `rect`, the window's
+    /// rect.
+    pub fn layout(&self, window: &Window, rect: &Rect) -> Layout {
+        let text = self.text_rect(window, rect);
+        Layout::new(
+            self.buffers[window.buffer()].lines(),
+            window.top,
+            window.hscroll,
+            text.width as usize,
+            text.height as usize,
+            &self.options,
+        )
+    }
+
+    /// Maps the cursor to a position relative to the active window's `rect`.
+    pub fn doc_to_screen(&self, rect: &Rect) -> (u16, u16) {
+        let text = self.text_rect(self.window(), rect);
+        let (y, x) = self
+            .layout(self.window(), rect)
+            .to_screen(self.cursor())
+            .unwrap_or_default();
+        (y as u16, (text.x - rect.x) + x as u16)
+    }
+
+    /// Maps the screen cell `(x, y)` back to a position in the active
+    /// window's buffer; the inverse of `doc_to_screen`. Cells outside the
+    /// window's text are clamped to its edges.
+    pub fn screen_to_doc(&self, area: &Rect, x: u16, y: u16) -> (usize, usize) {
+        let rect = self.tab().active_rect(*area);
+        let text = self.text_rect(self.window(), &rect);
+        let x = x
+            .min(text.x + text.width.saturating_sub(1))
+            .saturating_sub(text.x) as usize;
+        let y = y
+            .min(rect.y + rect.height.saturating_sub(1))
+            .saturating_sub(rect.y) as usize;
+
+        let layout = self.layout(self.window(), &rect);
+        layout.to_doc(y, x).unwrap_or_else(|| {
+            let lines = self.doc().lines();
+            let last = lines.len() - 1;
+            (last, lines[last].len())
+        })
+    }
+
+    /// Returns whether any buffer has changes that would be lost on quit.
+    /// The output buffer is written to stdout then, so it never does.
+    fn unsaved(&self) -> bool {
+        self.buffers
+            .iter()
+            .enumerate()
+            .any(|(i, b)| b.dirty() && self.output != Some(i))
+    }
+
+    /// Sets the buffer that is written to stdout when the editor quits.
+    pub fn set_output(&mut self, buffer: Option<usize>) {
+        self.output = buffer;
+    }
+
+    /// Returns the buffer to write to stdout now that the editor quit, or
+    /// `None` if there is none or the editor quit with `:cq`.
+    pub fn output(&self) -> Option<&Document> {
+        self.output
+            .filter(|_| !self.aborted)
+            .map(|i| &self.buffers[i])
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.should_quit
+    }
+
+    pub fn buffers(&self) -> &Vec<Document> {
+        &self.buffers
+    }
+
+    pub fn tabs(&self) -> &Vec<Tab> {
+        &self.tabs
+    }
+
+    pub fn active_tab(&self) -> usize {
+        self.active_tab
+    }
+
+    pub fn tab(&self) -> &Tab {
+        &self.tabs[self.active_tab]
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active_tab]
+    }
+
+    pub fn window(&self) -> &Window {
+        self.tab().window()
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        self.tab_mut().window_mut()
+    }
+
+    pub fn doc(&self) -> &Document {
+        &self.buffers[self.window().buffer()]
+    }
+
+    fn doc_mut(&mut self) -> &mut Document {
+        let buffer = self.window().buffer();
+        &mut self.buffers[buffer]
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.window().cursor
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    pub fn command_buffer(&self) -> &String {
+        &self.cmd_buf
+    }
+
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    pub fn recording(&self) -> Option<char> {
+        self.recording
+    }
+
+    /// Returns the message to show in the message line, if any.
+    pub fn message(&self) -> Option<&Message> {
+        self.message.as_ref()
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// Returns whether the `:messages` history should be shown.
+    pub fn show_messages(&self) -> bool {
+        self.show_messages
+    }
+
+    /// Returns the open completion popup, if any.
+    pub fn completion(&self) -> Option<&Completion> {
+        self.completion.as_ref()
+    }
+
+    /// Returns the open file finder, if any.
+    pub fn finder(&self) -> Option<&Finder> {
+        self.finder.as_ref()
+    }
+
+    pub fn quickfix(&self) -> &Quickfix {
+        &self.quickfix
+    }
+
+    /// Returns the file explorer sidebar, if it is open.
+    pub fn explorer(&self) -> Option<&Explorer> {
+        self.explorer.as_ref()
+    }
+
+    /// Returns the popup to show over the editor, if any.
+    pub fn popup(&self) -> Option<&Popup> {
+        self.popup.as_ref()
+    }
+
+    pub fn lsp_mut(&mut self) -> &mut Lsp {
+        &mut self.lsp
+    }
+
+    pub fn languages_mut(&mut self) -> &mut Languages {
+        &mut self.languages
+    }
+
+    /// Returns the root of the project, which starts as the working
+    /// directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn set_root(&mut self, root: impl Into<PathBuf>) {
+        self.root = root.into();
+    }
+
+    /// Returns `path` relative to the project root if it is under it, so
+    /// files opened from the finder, explorer and quickfix list keep short
+    /// names.
+    pub fn relative_path(&self, path: &Path) -> String {
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let root = std::path::absolute(&self.root).unwrap_or_else(|_| self.root.clone());
+        let relative = absolute.strip_prefix(&root).unwrap_or(path);
+        relative.to_string_lossy().to_string()
+    }
+}
+
+/// Describes how a shell command failed, by the first line of its error
+/// output or else its exit status.
+fn shell_failure(output: &shell::Output) -> String {
+    match output.stderr.lines().next() {
+        Some(line) => line.to_string(),
+        None => match output.code {
+            Some(code) => format!("shell returned {}", code),
+            None => "shell command was killed".to_string(),
+        },
+    }
+}
+
+/// Returns the indices of the buffers whose files are `path` or in it.
+fn buffers_under(buffers: &[Document], path: &Path) -> Vec<usize> {
+    (0..buffers.len())
+        .filter(|&i| {
+            buffers[i]
+                .full_path()
+                .and_then(|p| std::path::absolute(p).ok())
+                .is_some_and(|p| p.starts_with(path))
+        })
+        .collect()
+}
+
+/// Where files opened from the command line after the first are shown.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OpenIn {
+    Buffers, // loaded, but only shown with :bn and friends
+    Splits(SplitDirection),
+    Tabs,
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Mode {
     Normal,
     Insert,
+    Visual,
     Command,
 }
 
@@ -273,6 +3328,7 @@ impl Mode {
         match self {
             Mode::Normal => "NORMAL",
             Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
             Mode::Command => "COMMAND",
         }
     }
@@ -0,0 +1,324 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ignore::WalkBuilder;
+
+/// An `Explorer` is the file tree shown in the sidebar: the files under
+/// `root`, with the directories in `expanded` listed as well.
+pub struct Explorer {
+    pub root: PathBuf,
+    pub focused: bool,
+    pub prompt: Option<Prompt>,
+    entries: Vec<Entry>,
+    expanded: HashSet<PathBuf>,
+    hidden: HashSet<PathBuf>, // the temp files of open buffers
+    selected: usize,
+}
+
+pub struct Entry {
+    pub path: PathBuf,
+    pub depth: usize,
+    pub is_dir: bool,
+}
+
+impl Entry {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// What the explorer is asking for before it changes the files.
+pub struct Prompt {
+    pub action: Action,
+    pub input: String,
+}
+
+pub enum Action {
+    Create(PathBuf), // a file in this directory, or a directory with a trailing /
+    Move(PathBuf),   // to the path typed, relative to the root
+    Overwrite(PathBuf, PathBuf), // confirms moving onto an existing file
+    Delete(PathBuf, usize), // confirms deleting the file or directory, and its modified buffers
+}
+
+impl Prompt {
+    /// Returns the question shown to the user.
+    pub fn text(&self, root: &Path) -> String {
+        let relative = |path: &Path| {
+            path.strip_prefix(root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+        match &self.action {
+            Action::Create(_) => format!("New: {}", self.input),
+            Action::Move(_) => format!("Move to: {}", self.input),
+            Action::Overwrite(_, to) => format!("Overwrite {}? (y/n)", relative(to)),
+            Action::Delete(path, 0) => format!("Delete {}? (y/n)", relative(path)),
+            Action::Delete(path, modified) => {
+                format!("Delete {} ({} modified)? (y/n)", relative(path), modified)
+            }
+        }
+    }
+
+    /// Returns whether the prompt only asks for a yes or no.
+    pub fn is_confirmation(&self) -> bool {
+        matches!(self.action, Action::Overwrite(..) | Action::Delete(..))
+    }
+}
+
+impl Explorer {
+    pub fn new(root: &Path) -> Self {
+        let mut explorer = Self {
+            root: root.to_path_buf(),
+            focused: true,
+            prompt: None,
+            entries: Vec::new(),
+            expanded: HashSet::new(),
+            hidden: HashSet::new(),
+            selected: 0,
+        };
+        explorer.refresh();
+        explorer
+    }
+
+    /// Lists the files again, keeping the selection on the same path if it
+    /// is still there.
+    pub fn refresh(&mut self) {
+        let selected = self.selected_entry().map(|e| e.path.clone());
+        self.expanded.retain(|dir| dir.is_dir());
+        self.entries.clear();
+        self.list(&self.root.clone(), 0);
+        self.selected = selected
+            .and_then(|path| self.entries.iter().position(|e| e.path == path))
+            .unwrap_or(self.selected)
+            .min(self.entries.len().saturating_sub(1));
+    }
+
+    /// Lists `dir` and its expanded subdirectories, directories first,
+    /// skipping hidden and ignored files as the file finder does.
+    fn list(&mut self, dir: &Path, depth: usize) {
+        let mut children: Vec<(bool, PathBuf)> = WalkBuilder::new(dir)
+            .require_git(false)
+            .max_depth(Some(1))
+            .build()
+            .flatten()
+            .filter(|entry| entry.depth() == 1 && !self.hidden.contains(entry.path()))
+            .map(|entry| {
+                let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+                (!is_dir, entry.into_path())
+            })
+            .collect();
+        children.sort();
+
+        for (is_file, path) in children {
+            let expanded = !is_file && self.expanded.contains(&path);
+            self.entries.push(Entry {
+                path: path.clone(),
+                depth,
+                is_dir: !is_file,
+            });
+            if expanded {
+                self.list(&path, depth + 1);
+            }
+        }
+    }
+
+    /// Leaves `paths` out of the tree, listing the files again if they
+    /// changed.
+    pub fn hide(&mut self, paths: HashSet<PathBuf>) {
+        if paths != self.hidden {
+            self.hidden = paths;
+            self.refresh();
+        }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn is_expanded(&self, dir: &Path) -> bool {
+        self.expanded.contains(dir)
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    /// Moves the selection `delta` entries on, stopping at either end.
+    pub fn select(&mut self, delta: isize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Selects the entry for `path`, if it is listed.
+    pub fn select_path(&mut self, path: &Path) {
+        if let Some(i) = self.entries.iter().position(|e| e.path == path) {
+            self.selected = i;
+        }
+    }
+
+    /// Expands or collapses the selected directory.
+    pub fn toggle(&mut self) {
+        let Some(entry) = self.selected_entry().filter(|e| e.is_dir) else {
+            return;
+        };
+        let dir = entry.path.clone();
+        if !self.expanded.remove(&dir) {
+            self.expanded.insert(dir);
+        }
+        self.refresh();
+    }
+
+    /// Collapses the selected directory, or else selects the directory the
+    /// selected entry is in.
+    pub fn collapse(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            return;
+        };
+        if entry.is_dir && self.expanded.contains(&entry.path) {
+            return self.toggle();
+        }
+        if let Some(parent) = entry.path.parent().map(Path::to_path_buf) {
+            self.select_path(&parent);
+        }
+    }
+
+    /// Returns the directory new files are created in: the selected one, or
+    /// the one the selected file is in.
+    pub fn target_dir(&self) -> PathBuf {
+        match self.selected_entry() {
+            Some(entry) if entry.is_dir => entry.path.clone(),
+            Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+
+    /// Creates `name` in `dir`, as a directory if it ends with a slash, and
+    /// selects it.
+    pub fn create(&mut self, dir: &Path, name: &str) -> io::Result<PathBuf> {
+        let path = dir.join(name.trim_end_matches('/'));
+        if path.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "File exists"));
+        }
+        if name.ends_with('/') {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::File::create_new(&path)?;
+        }
+        self.reveal(&path);
+        Ok(path)
+    }
+
+    /// Moves `from` to `to`, creating the directories `to` is in, and
+    /// selects it.
+    pub fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(from, to)?;
+        // what was expanded inside a moved directory stays expanded
+        self.expanded = std::mem::take(&mut self.expanded)
+            .into_iter()
+            .map(|dir| match dir.strip_prefix(from) {
+                Ok(rest) => to.join(rest),
+                Err(_) => dir,
+            })
+            .collect();
+        self.reveal(to);
+        Ok(())
+    }
+
+    /// Deletes `path`, with everything in it if it is a directory.
+    pub fn delete(&mut self, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+        self.refresh();
+        Ok(())
+    }
+
+    /// Expands the directories down to `path` and selects it.
+    fn reveal(&mut self, path: &Path) {
+        for dir in path.ancestors().skip(1) {
+            if dir == self.root || !dir.starts_with(&self.root) {
+                break;
+            }
+            self.expanded.insert(dir.to_path_buf());
+        }
+        self.refresh();
+        self.select_path(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(explorer: &Explorer) -> Vec<String> {
+        explorer
+            .entries()
+            .iter()
+            .map(|e| format!("{}{}", "  ".repeat(e.depth), e.name()))
+            .collect()
+    }
+
+    #[test]
+    fn lists_directories_first_and_expands_them() {
+        let root =
+            std::env::temp_dir().join(format!("text-editor-explorer-{}", std::process::id()));
+        fs::create_dir_all(root.join("src/models")).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+
+        let mut explorer = Explorer::new(&root);
+        assert_eq!(names(&explorer), ["src", "Cargo.toml"]);
+        explorer.toggle();
+        assert_eq!(
+            names(&explorer),
+            ["src", "  models", "  main.rs", "Cargo.toml"]
+        );
+
+        // new files show up expanded and selected
+        let path = explorer
+            .create(&root.join("src"), "models/editor.rs")
+            .unwrap();
+        assert_eq!(explorer.selected_entry().unwrap().path, path);
+        assert_eq!(explorer.selected(), 2);
+
+        explorer
+            .rename(&root.join("src"), &root.join("lib"))
+            .unwrap();
+        assert_eq!(
+            names(&explorer),
+            [
+                "lib",
+                "  models",
+                "    editor.rs",
+                "  main.rs",
+                "Cargo.toml"
+            ]
+        );
+        explorer.collapse();
+        assert_eq!(names(&explorer), ["lib", "Cargo.toml"]);
+
+        explorer.delete(&root.join("lib")).unwrap();
+        assert_eq!(names(&explorer), ["Cargo.toml"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
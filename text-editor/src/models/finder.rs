@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+use crate::models::completion::{fuzzy_score, path_preview};
+
+/// How many files the finder lists at most, so a huge tree cannot stall
+/// the editor.
+const MAX_FILES: usize = 50_000;
+/// How many lines of the highlighted file the preview shows.
+const PREVIEW_LINES: usize = 100;
+/// Extra score for matching within the file name rather than across the
+/// whole path.
+const FILE_NAME_BONUS: i64 = 10;
+
+/// A `Finder` is the fuzzy file finder overlay: the files under `root`,
+/// ranked by how well their paths match the query typed so far.
+pub struct Finder {
+    pub root: PathBuf,
+    pub preview: Vec<String>,
+    query: String,
+    files: Vec<String>,  // relative to `root`
+    matches: Vec<usize>, // indices of the matching files, best first
+    selected: usize,     // index into `matches`
+}
+
+impl Finder {
+    /// Lists the files under `root`, skipping hidden files and those
+    /// ignored by `.gitignore` and `.ignore` files.
+    pub fn new(root: &Path) -> Self {
+        let mut files: Vec<String> = WalkBuilder::new(root)
+            .require_git(false)
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter_map(|entry| {
+                let path = entry.path().strip_prefix(root).ok()?;
+                Some(path.to_string_lossy().to_string())
+            })
+            .take(MAX_FILES)
+            .collect();
+        files.sort();
+        Self::with_files(root, files)
+    }
+
+    /// Creates a finder over `files`, given relative to `root`.
+    pub fn with_files(root: &Path, files: Vec<String>) -> Self {
+        let mut finder = Self {
+            root: root.to_path_buf(),
+            preview: Vec::new(),
+            query: String::new(),
+            matches: (0..files.len()).collect(),
+            files,
+            selected: 0,
+        };
+        finder.update_preview();
+        finder
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Ranks the files matching `query`. When it only extends the previous
+    /// query, just the files that matched before are scored again.
+    pub fn set_query(&mut self, query: &str) {
+        let candidates: Vec<usize> = if query.starts_with(&self.query) {
+            std::mem::take(&mut self.matches)
+        } else {
+            (0..self.files.len()).collect()
+        };
+        let mut scored: Vec<(i64, usize)> = candidates
+            .into_iter()
+            .filter_map(|i| Some((score(query, &self.files[i])?, i)))
+            .collect();
+        // files were sorted by path, which breaks ties
+        scored.sort_by_key(|&(score, i)| (-score, i));
+
+        self.query = query.to_string();
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+        self.update_preview();
+    }
+
+    pub fn push(&mut self, c: char) {
+        let query = format!("{}{}", self.query, c);
+        self.set_query(&query);
+    }
+
+    pub fn pop(&mut self) {
+        let mut query = self.query.clone();
+        query.pop();
+        self.set_query(&query);
+    }
+
+    /// Moves the highlight `delta` matches on, wrapping around.
+    pub fn select(&mut self, delta: isize) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let n = self.matches.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(n) as usize;
+        self.update_preview();
+    }
+
+    /// Returns the matching files, best first.
+    pub fn matches(&self) -> impl Iterator<Item = &str> {
+        self.matches.iter().map(|&i| self.files[i].as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.matches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Returns how many files there are to match.
+    pub fn total(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns the index of the highlighted match.
+    pub fn selected(&self) -> Option<usize> {
+        (!self.matches.is_empty()).then_some(self.selected)
+    }
+
+    /// Returns the highlighted file, relative to `root`.
+    pub fn selected_file(&self) -> Option<&str> {
+        self.selected()
+            .map(|i| self.files[self.matches[i]].as_str())
+    }
+
+    fn update_preview(&mut self) {
+        self.preview = match self.selected_file() {
+            Some(file) => path_preview(&self.root.join(file), PREVIEW_LINES),
+            None => Vec::new(),
+        };
+    }
+}
+
+/// Scores `path` against `query`, preferring matches within its file name.
+fn score(query: &str, path: &str) -> Option<i64> {
+    let whole = fuzzy_score(query, path)?;
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let in_name = fuzzy_score(query, name).map(|s| s + FILE_NAME_BONUS);
+    Some(whole.max(in_name.unwrap_or(whole)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finder(files: &[&str]) -> Finder {
+        let files = files.iter().map(|f| f.to_string()).collect();
+        Finder::with_files(Path::new("/nonexistent"), files)
+    }
+
+    #[test]
+    fn file_names_rank_above_directories() {
+        let mut finder = finder(&["main/lib.rs", "src/main.rs", "src/models/editor.rs"]);
+        finder.set_query("main");
+        let matches: Vec<&str> = finder.matches().collect();
+        assert_eq!(matches, ["src/main.rs", "main/lib.rs"]);
+    }
+
+    #[test]
+    fn queries_filter_incrementally_and_widen_again() {
+        let mut finder = finder(&["src/editor.rs", "src/document.rs", "LICENSE"]);
+        finder.push('e');
+        finder.push('d');
+        assert_eq!(finder.matches().collect::<Vec<_>>(), ["src/editor.rs"]);
+        finder.pop();
+        assert_eq!(finder.len(), 3);
+        finder.set_query("xyz");
+        assert_eq!(finder.selected_file(), None);
+    }
+
+    #[test]
+    fn walking_respects_gitignore() {
+        let root = std::env::temp_dir().join(format!("text-editor-finder-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        for file in [
+            "src/main.rs",
+            "target/out.rs",
+            "debug.log",
+            "README.md",
+            ".hidden",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        let finder = Finder::new(&root);
+        let files: Vec<&str> = finder.matches().collect();
+        assert_eq!(files, ["README.md", "src/main.rs"]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
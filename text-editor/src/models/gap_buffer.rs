@@ -1,9 +1,17 @@
+use std::fmt;
+
 pub struct GapBuffer {
     data: Vec<char>,
     gap_start: usize,
     gap_size: usize,
 }
 
+impl Default for GapBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GapBuffer {
     pub fn new() -> Self {
         let cap = 10;
@@ -63,6 +71,10 @@ impl GapBuffer {
         self.data.len() - self.gap_size
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
         self.data[..self.gap_start]
             .iter()
@@ -123,11 +135,11 @@ impl GapBuffer {
     }
 }
 
-impl ToString for GapBuffer {
-    fn to_string(&self) -> String {
+impl fmt::Display for GapBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s = String::with_capacity(self.len());
         s.extend(&self.data[..self.gap_start]);
         s.extend(&self.data[self.gap_start + self.gap_size..]);
-        s
+        f.write_str(&s)
     }
 }
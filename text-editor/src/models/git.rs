@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use git2::{Repository, Time};
+
+use crate::models::diff::Hunk;
+
+/// Opens the repository `path` is in, returning it with the path of the
+/// file relative to its work tree.
+fn open(path: &Path) -> Option<(Repository, PathBuf)> {
+    let path = path.canonicalize().ok()?;
+    let repo = Repository::discover(path.parent()?).ok()?;
+    let workdir = repo.workdir()?.canonicalize().ok()?;
+    let relative = path.strip_prefix(workdir).ok()?.to_path_buf();
+    Some((repo, relative))
+}
+
+/// Returns the text of the file at `path` as staged in the git index, or as
+/// committed in HEAD if it is not in the index. None if the file is not in
+/// a repository or not tracked.
+pub fn base_text(path: &Path) -> Option<String> {
+    let (repo, relative) = open(path)?;
+    let blob = match repo.index().ok()?.get_path(&relative, 0) {
+        Some(entry) => repo.find_blob(entry.id).ok()?,
+        None => repo
+            .head()
+            .ok()?
+            .peel_to_tree()
+            .ok()?
+            .get_path(&relative)
+            .ok()?
+            .to_object(&repo)
+            .ok()?
+            .peel_to_blob()
+            .ok()?,
+    };
+    Some(String::from_utf8_lossy(blob.content()).into_owned())
+}
+
+/// Stages one hunk of the changes to the file at `path`: the index version
+/// gets the hunk's old lines replaced by `lines`, the rest left as it was.
+pub fn stage_hunk(path: &Path, hunk: &Hunk, lines: &[String]) -> Result<(), String> {
+    let (repo, relative) = open(path).ok_or("Not in a git repository")?;
+    let mut index = repo.index().map_err(|e| e.message().to_string())?;
+    let mut entry = index
+        .get_path(&relative, 0)
+        .ok_or("File is not in the git index")?;
+    let blob = repo
+        .find_blob(entry.id)
+        .map_err(|e| e.message().to_string())?;
+    let base = String::from_utf8_lossy(blob.content()).into_owned();
+
+    let ending = if base.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut staged: Vec<&str> = base.lines().collect();
+    if hunk.old.end > staged.len() {
+        return Err("Hunk is out of date".to_string());
+    }
+    staged.splice(hunk.old.clone(), lines.iter().map(String::as_str));
+    let mut content = staged.join(ending);
+    if base.ends_with('\n') && !content.is_empty() {
+        content.push_str(ending);
+    }
+
+    entry.file_size = content.len() as u32;
+    index
+        .add_frombuffer(&entry, content.as_bytes())
+        .and_then(|()| index.write())
+        .map_err(|e| e.message().to_string())
+}
+
+/// Annotates each of `lines`, the buffer of the file at `path`, with the
+/// commit that last changed it: its short id, author and date. Lines
+/// changed since the last commit are marked as not committed.
+pub fn blame(path: &Path, lines: &[String]) -> Result<Vec<String>, String> {
+    let (repo, relative) = open(path).ok_or("Not in a git repository")?;
+    let committed = repo
+        .blame_file(&relative, None)
+        .map_err(|e| e.message().to_string())?;
+    let text = format!("{}\n", lines.join("\n"));
+    let blame = committed
+        .blame_buffer(text.as_bytes())
+        .map_err(|e| e.message().to_string())?;
+
+    Ok((1..=lines.len())
+        .map(|line| match blame.get_line(line) {
+            Some(hunk) if !hunk.final_commit_id().is_zero() => {
+                let author = hunk.final_signature();
+                let name = author.as_ref().and_then(|a| a.name().ok()).unwrap_or("?");
+                let when = author.as_ref().map(|a| date(a.when())).unwrap_or_default();
+                format!("{:.8} {} {}", hunk.final_commit_id(), name, when)
+            }
+            _ => "Not committed yet".to_string(),
+        })
+        .collect())
+}
+
+/// Formats `time` as a `YYYY-MM-DD` date in its own time zone.
+fn date(time: Time) -> String {
+    let seconds = time.seconds() + i64::from(time.offset_minutes()) * 60;
+    // days since 1970-01-01 to a civil date, by Howard Hinnant's algorithm
+    let days = seconds.div_euclid(86_400) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_in_the_committer_time_zone() {
+        assert_eq!(date(Time::new(0, 0)), "1970-01-01");
+        assert_eq!(date(Time::new(951_825_600, 0)), "2000-02-29");
+        // 23:30 UTC is the next day two hours east
+        assert_eq!(date(Time::new(1_700_004_600, 120)), "2023-11-15");
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use ignore::WalkBuilder;
+use regex::Regex;
+
+use crate::models::quickfix::Entry;
+
+/// How many matches a search collects at most.
+const MAX_MATCHES: usize = 10_000;
+
+/// Searches the files under `paths`, given relative to `root`, or under
+/// `root` itself if there are none, for lines matching `pattern`, skipping
+/// ignored files and those in `skip`. The entries name files relative to
+/// `root`. Patterns the regex engine does not support, such as look-around,
+/// are handed to `rg` when it is installed.
+pub fn grep(
+    pattern: &str,
+    paths: &[String],
+    root: &Path,
+    skip: &[PathBuf],
+) -> Result<Vec<Entry>, String> {
+    let paths: Vec<&str> = if paths.is_empty() {
+        vec!["."]
+    } else {
+        paths.iter().map(String::as_str).collect()
+    };
+    let mut entries = match Regex::new(pattern) {
+        Ok(regex) => search(&regex, &paths, root),
+        Err(e) => match search_rg(pattern, &paths, root) {
+            Ok(entries) => entries,
+            Err(e2) if e2.kind() == io::ErrorKind::NotFound => {
+                return Err(format!("Invalid pattern: {}", e));
+            }
+            Err(e2) => return Err(e2.to_string()),
+        },
+    };
+    entries.retain(|e| {
+        std::path::absolute(root.join(&e.path)).map_or(true, |path| !skip.contains(&path))
+    });
+    Ok(entries)
+}
+
+/// Splits the arguments of `:grep` at spaces, keeping quoted ones whole.
+pub fn split_args(arg: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = arg.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut word = String::new();
+        if c == '"' || c == '\'' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some(q) if q == c => break,
+                    Some(ch) => word.push(ch),
+                    None => return Err(format!("Missing quote: {}", c)),
+                }
+            }
+        } else {
+            while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+                word.push(ch);
+            }
+        }
+        args.push(word);
+    }
+    Ok(args)
+}
+
+fn search(regex: &Regex, paths: &[&str], root: &Path) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for path in paths {
+        let walk = WalkBuilder::new(root.join(path))
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+        for entry in walk.flatten() {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            // binary files are not text, and are skipped with the unreadable
+            let Ok(text) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            let path = display_path(entry.path().strip_prefix(root).unwrap_or(entry.path()));
+            for (row, line) in text.lines().enumerate() {
+                if let Some(m) = regex.find(line) {
+                    entries.push(Entry {
+                        path: path.clone(),
+                        pos: (row, line[..m.start()].chars().count()),
+                        text: line.trim().to_string(),
+                        severity: None,
+                    });
+                    if entries.len() >= MAX_MATCHES {
+                        return entries;
+                    }
+                }
+            }
+        }
+    }
+    entries
+}
+
+/// Runs `rg` with its PCRE2 engine, failing with `NotFound` if it is not
+/// installed. It skips the files `search` does: outside a git repository
+/// too, `.gitignore` files are followed.
+fn search_rg(pattern: &str, paths: &[&str], root: &Path) -> io::Result<Vec<Entry>> {
+    let output = Command::new("rg")
+        .current_dir(root)
+        .args(["--pcre2", "--no-heading", "--line-number", "--column"])
+        // a single file is searched without printing its name otherwise
+        .args(["--with-filename", "--no-require-git"])
+        .args([
+            "--color",
+            "never",
+            "--max-count",
+            "1000",
+            "-e",
+            pattern,
+            "--",
+        ])
+        .args(paths)
+        .output()?;
+    // rg exits with 1 when nothing matched
+    if !output.status.success() && output.status.code() != Some(1) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+
+    let line_re = Regex::new(r"^(.*?):(\d+):(\d+):(.*)$").expect("rg line regex is valid");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| {
+            let caps = line_re.captures(line)?;
+            let row: usize = caps[2].parse().ok()?;
+            let col: usize = caps[3].parse().ok()?;
+            let text = &caps[4];
+            let col = text
+                .get(..col.saturating_sub(1))
+                .map_or(0, |before| before.chars().count());
+            Some(Entry {
+                path: display_path(Path::new(&caps[1])),
+                pos: (row.saturating_sub(1), col),
+                text: text.trim().to_string(),
+                severity: None,
+            })
+        })
+        .take(MAX_MATCHES)
+        .collect())
+}
+
+/// Drops the leading `./` that searching `.` adds.
+fn display_path(path: &Path) -> PathBuf {
+    path.strip_prefix(".").unwrap_or(path).to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_arguments_keep_their_spaces() {
+        assert_eq!(
+            split_args(r#""fn main" src 'a b'"#).unwrap(),
+            ["fn main", "src", "a b"]
+        );
+        assert!(split_args("\"open").is_err());
+    }
+
+    #[test]
+    fn matches_report_the_first_match_on_each_line() {
+        let root = std::env::temp_dir().join(format!("text-editor-grep-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "fn main() {\n    let é = main();\n}\n",
+        )
+        .unwrap();
+        fs::write(root.join("target/main.rs"), "fn main() {}\n").unwrap();
+
+        let entries = grep(r"main\(", &[], &root, &[]).unwrap();
+        let found: Vec<_> = entries.iter().map(|e| (e.path.clone(), e.pos)).collect();
+        assert_eq!(
+            found,
+            [
+                (PathBuf::from("src/main.rs"), (0, 3)),
+                (PathBuf::from("src/main.rs"), (1, 12)),
+            ]
+        );
+        assert_eq!(entries[1].text, "let é = main();");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rg_reports_matches_in_a_single_file() {
+        let path = std::env::temp_dir().join(format!("text-editor-rg-{}.rs", std::process::id()));
+        fs::write(&path, "fn main() {}\nfn mainly() {}\n").unwrap();
+        let file = path.to_str().unwrap();
+        let result = search_rg(r"main(?=\()", &[file], Path::new("."));
+        fs::remove_file(&path).unwrap();
+        let entries = match result {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return, // rg is not installed
+            result => result.unwrap(),
+        };
+        let found: Vec<_> = entries.iter().map(|e| (e.path.clone(), e.pos)).collect();
+        assert_eq!(found, [(path.clone(), (0, 3))]);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::models::{
+    document::Document,
+    options::{Options, SignColumnMode},
+};
+
+const SIGN_WIDTH: usize = 2;
+const MIN_NUMBER_WIDTH: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    Relative,
+    Hybrid,
+}
+
+/// The `Gutter` is the column to the left of a window's text holding the
+/// sign column and line numbers.
+pub struct Gutter {
+    numbers: LineNumbers,
+    number_width: usize,
+    sign_width: usize,
+}
+
+impl Gutter {
+    pub fn new(options: &Options, doc: &Document) -> Self {
+        let numbers = match (options.number, options.relativenumber) {
+            (false, false) => LineNumbers::Off,
+            (true, false) => LineNumbers::Absolute,
+            (false, true) => LineNumbers::Relative,
+            (true, true) => LineNumbers::Hybrid,
+        };
+
+        let number_width = if numbers == LineNumbers::Off {
+            0
+        } else {
+            let digits = doc.lines().len().to_string().len();
+            digits.max(MIN_NUMBER_WIDTH) + 1
+        };
+
+        let sign_width = match options.signcolumn {
+            SignColumnMode::Yes => SIGN_WIDTH,
+            SignColumnMode::No => 0,
+            SignColumnMode::Auto if doc.signs().is_empty() => 0,
+            SignColumnMode::Auto => SIGN_WIDTH,
+        };
+
+        Self {
+            numbers,
+            number_width,
+            sign_width,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.number_width + self.sign_width
+    }
+
+    pub fn sign_width(&self) -> usize {
+        self.sign_width
+    }
+
+    /// Returns the padded line number label for `row`, including the
+    /// separating space.
+    pub fn number(&self, row: usize, cursor_row: usize) -> String {
+        let width = self.number_width.saturating_sub(1);
+        match self.numbers {
+            LineNumbers::Off => String::new(),
+            LineNumbers::Absolute => format!("{:>width$} ", row + 1),
+            LineNumbers::Relative => format!("{:>width$} ", row.abs_diff(cursor_row)),
+            LineNumbers::Hybrid if row == cursor_row => format!("{:<width$} ", row + 1),
+            LineNumbers::Hybrid => format!("{:>width$} ", row.abs_diff(cursor_row)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(number: bool, relativenumber: bool, signcolumn: SignColumnMode) -> Options {
+        Options {
+            number,
+            relativenumber,
+            signcolumn,
+            ..Options::default()
+        }
+    }
+
+    fn doc(lines: usize) -> Document {
+        let text: Vec<String> = (0..lines).map(|n| n.to_string()).collect();
+        Document::from_text(&text.join("\n"))
+    }
+
+    #[test]
+    fn numbering_styles() {
+        let doc = doc(20);
+        let absolute = Gutter::new(&options(true, false, SignColumnMode::No), &doc);
+        assert_eq!(absolute.number(4, 9), "  5 ");
+        let relative = Gutter::new(&options(false, true, SignColumnMode::No), &doc);
+        assert_eq!(relative.number(4, 9), "  5 ");
+        assert_eq!(relative.number(12, 9), "  3 ");
+        assert_eq!(relative.number(9, 9), "  0 ");
+        // the cursor line shows its number, on the left
+        let hybrid = Gutter::new(&options(true, true, SignColumnMode::No), &doc);
+        assert_eq!(hybrid.number(9, 9), "10  ");
+        assert_eq!(hybrid.number(12, 9), "  3 ");
+
+        let off = Gutter::new(&options(false, false, SignColumnMode::No), &doc);
+        assert_eq!(off.number(4, 9), "");
+        assert_eq!(off.width(), 0);
+    }
+
+    #[test]
+    fn number_width_follows_the_line_count() {
+        let opts = options(true, false, SignColumnMode::No);
+        assert_eq!(Gutter::new(&opts, &doc(1)).width(), MIN_NUMBER_WIDTH + 1);
+        assert_eq!(Gutter::new(&opts, &doc(999)).width(), 4);
+        let wide = Gutter::new(&opts, &doc(1000));
+        assert_eq!(wide.width(), 5);
+        assert_eq!(wide.number(0, 0), "   1 ");
+        assert_eq!(wide.number(999, 0), "1000 ");
+    }
+
+    #[test]
+    fn sign_column_modes() {
+        let mut doc = doc(3);
+        let width =
+            |mode, doc: &Document| Gutter::new(&options(false, false, mode), doc).sign_width();
+        assert_eq!(width(SignColumnMode::Yes, &doc), SIGN_WIDTH);
+        assert_eq!(width(SignColumnMode::No, &doc), 0);
+        // `auto` shows the column only while there are signs
+        assert_eq!(width(SignColumnMode::Auto, &doc), 0);
+        doc.set_mark('a', (1, 0));
+        assert_eq!(width(SignColumnMode::Auto, &doc), SIGN_WIDTH);
+        assert_eq!(width(SignColumnMode::No, &doc), 0);
+
+        let both = Gutter::new(&options(true, false, SignColumnMode::Yes), &doc);
+        assert_eq!(both.width(), SIGN_WIDTH + MIN_NUMBER_WIDTH + 1);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{ops::Range, rc::Rc};
+
+use regex::Regex;
+
+use crate::models::{
+    gap_buffer::GapBuffer,
+    syntax::{Grammar, RuleKind},
+};
+
+/// A styled run of characters within a line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HighlightSpan {
+    pub range: Range<usize>, // char columns
+    pub scope: String,
+}
+
+/// The region a line ends inside of, if any, so the next line can continue
+/// it.
+type LineState = Option<usize>;
+
+struct HighlightedLine {
+    spans: Vec<HighlightSpan>,
+    end_state: LineState,
+}
+
+/// A `Highlighter` keeps the highlighted spans for each line of a document
+/// and re-highlights only what an edit can have changed.
+pub struct Highlighter {
+    grammar: Rc<Grammar>,
+    lines: Vec<HighlightedLine>,
+}
+
+impl Highlighter {
+    pub fn new(grammar: Rc<Grammar>, lines: &[GapBuffer]) -> Self {
+        let mut highlighter = Self {
+            grammar,
+            lines: Vec::new(),
+        };
+        highlighter.edit(0, 0, lines.len(), lines);
+        highlighter
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    pub fn spans(&self, row: usize) -> &[HighlightSpan] {
+        self.lines.get(row).map(|l| &l.spans[..]).unwrap_or(&[])
+    }
+
+    /// Updates the highlights after `removed` lines starting at `row` were
+    /// replaced by `inserted` lines. Highlighting restarts at `row` and stops
+    /// once a line past the edit ends in the same state as before.
+    pub fn edit(&mut self, row: usize, removed: usize, inserted: usize, lines: &[GapBuffer]) {
+        let row = row.min(self.lines.len());
+        let removed = removed.min(self.lines.len() - row);
+        let placeholders = (0..inserted).map(|_| HighlightedLine {
+            spans: Vec::new(),
+            end_state: None,
+        });
+        self.lines.splice(row..row + removed, placeholders);
+
+        let mut state = match row {
+            0 => None,
+            _ => self.lines[row - 1].end_state,
+        };
+        for (i, line) in lines.iter().enumerate().skip(row) {
+            let (spans, end_state) = self.highlight_line(&line.to_string(), state);
+            let converged = i >= row + inserted && self.lines[i].end_state == end_state;
+            self.lines[i] = HighlightedLine { spans, end_state };
+            if converged {
+                break;
+            }
+            state = end_state;
+        }
+    }
+
+    fn highlight_line(&self, line: &str, mut state: LineState) -> (Vec<HighlightSpan>, LineState) {
+        let char_at = char_index_table(line);
+        let mut spans = Vec::new();
+        let mut push = |range: Range<usize>, scope: &str| {
+            if range.start < range.end {
+                spans.push(HighlightSpan {
+                    range: char_at[range.start]..char_at[range.end],
+                    scope: scope.to_string(),
+                });
+            }
+        };
+
+        let rules = &self.grammar.rules;
+        let mut pos = 0;
+
+        if let Some(region) = state {
+            pos = self.region_end(region, line, pos, &mut state);
+            push(0..pos, &rules[region].scope);
+            if state.is_some() {
+                return (spans, state);
+            }
+        }
+
+        // the next match of each rule at or after `pos`, found lazily
+        let mut next: Vec<Option<Option<Range<usize>>>> = vec![None; rules.len()];
+
+        loop {
+            let mut best: Option<(usize, Range<usize>)> = None;
+            for (i, rule) in rules.iter().enumerate() {
+                let stale = matches!(&next[i], Some(Some(m)) if m.start < pos);
+                if next[i].is_none() || stale {
+                    let regex = match &rule.kind {
+                        RuleKind::Match(regex) => regex,
+                        RuleKind::Region { begin, .. } => begin,
+                    };
+                    next[i] = Some(find_non_empty(regex, line, pos));
+                }
+                if let Some(Some(m)) = &next[i]
+                    && best.as_ref().is_none_or(|(_, b)| m.start < b.start)
+                {
+                    best = Some((i, m.clone()));
+                }
+            }
+
+            let Some((i, m)) = best else {
+                break;
+            };
+
+            match rules[i].kind {
+                RuleKind::Match(_) => {
+                    push(m.clone(), &rules[i].scope);
+                    pos = m.end;
+                }
+                RuleKind::Region { .. } => {
+                    state = Some(i);
+                    pos = self.region_end(i, line, m.end, &mut state);
+                    push(m.start..pos, &rules[i].scope);
+                    if state.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        (spans, state)
+    }
+
+    /// Scans for the end of `region` from `pos`, clearing `state` if the
+    /// region closes on this line, and returns the byte offset it ends at.
+    fn region_end(
+        &self,
+        region: usize,
+        line: &str,
+        mut pos: usize,
+        state: &mut LineState,
+    ) -> usize {
+        let RuleKind::Region { end, skip, .. } = &self.grammar.rules[region].kind else {
+            *state = None;
+            return pos;
+        };
+
+        loop {
+            let end_match = end.find_at(line, pos);
+            let skip_match = skip
+                .as_ref()
+                .and_then(|s| find_non_empty(s, line, pos))
+                .filter(|s| end_match.is_none_or(|e| s.start < e.start()));
+
+            if let Some(s) = skip_match {
+                pos = s.end;
+            } else if let Some(e) = end_match {
+                *state = None;
+                return e.end();
+            } else {
+                return line.len();
+            }
+        }
+    }
+}
+
+fn find_non_empty(regex: &Regex, line: &str, mut pos: usize) -> Option<Range<usize>> {
+    while pos <= line.len() {
+        let m = regex.find_at(line, pos)?;
+        if !m.is_empty() {
+            return Some(m.range());
+        }
+        pos = m.end() + line[m.end()..].chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+/// Maps each byte offset of `line` that starts a char, plus its length, to a
+/// char index.
+fn char_index_table(line: &str) -> Vec<usize> {
+    let mut table = vec![0; line.len() + 1];
+    for (i, (b, _)) in line.char_indices().enumerate() {
+        table[b] = i;
+    }
+    table[line.len()] = line.chars().count();
+    table
+}
@@ -0,0 +1,76 @@
+use crate::models::document::Change;
+
+/// The `History` of a document groups the changes applied to it into undo
+/// steps. Changes are collected into an open step until it is committed,
+/// so that for example a whole insert session undoes at once.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    open: Vec<Change>,
+    /// How many steps were undoable when the text was last saved, or `None`
+    /// if no undo or redo gets back to it.
+    saved: Option<usize>,
+}
+
+impl History {
+    /// Creates an empty history whose starting point is the saved text.
+    pub fn new() -> Self {
+        Self {
+            saved: Some(0),
+            ..Self::default()
+        }
+    }
+
+    /// Creates an empty history for text that was never saved.
+    pub fn unsaved() -> Self {
+        Self::default()
+    }
+
+    /// Adds `change` to the open step. A new change makes the undone steps
+    /// unreachable, so they are dropped.
+    pub fn record(&mut self, change: Change) {
+        if self.saved.is_some_and(|saved| saved > self.undo.len()) {
+            self.saved = None;
+        }
+        self.redo.clear();
+        self.open.push(change);
+    }
+
+    /// Closes the open step, if it has any changes.
+    pub fn commit(&mut self) {
+        if !self.open.is_empty() {
+            self.undo.push(std::mem::take(&mut self.open));
+        }
+    }
+
+    /// Commits the open step and moves the most recent step to the redo
+    /// stack, returning its changes in the order they were applied.
+    pub fn undo(&mut self) -> Option<&[Change]> {
+        self.commit();
+        let step = self.undo.pop()?;
+        self.redo.push(step);
+        self.redo.last().map(|s| &s[..])
+    }
+
+    /// Moves the most recently undone step back to the undo stack,
+    /// returning its changes in the order they were applied.
+    pub fn redo(&mut self) -> Option<&[Change]> {
+        self.commit();
+        let step = self.redo.pop()?;
+        self.undo.push(step);
+        self.undo.last().map(|s| &s[..])
+    }
+
+    /// Commits the open step and remembers the current point as the saved
+    /// text.
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.saved = Some(self.undo.len());
+    }
+
+    /// Returns whether undo and redo have come back to the saved text.
+    pub fn at_saved(&self) -> bool {
+        self.open.is_empty() && self.saved == Some(self.undo.len())
+    }
+}
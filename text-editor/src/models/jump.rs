@@ -0,0 +1,206 @@
+use std::{path::Path, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::models::document::Document;
+
+/// Matches a trailing `:line` or `:line:col`, with the colon compilers may
+/// put after it.
+static LOCATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.+?):(\d+)(?::(\d+))?:?$").expect("location regex is valid"));
+
+/// A `Jump` says where to put the cursor in a file opened from the command
+/// line.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Jump {
+    Line { line: usize, col: Option<usize> }, // 1-based, as compilers print them
+    Pattern(String),
+    LastLine,
+}
+
+/// A `FileArg` is a file named on the command line and where to open it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FileArg {
+    pub path: String,
+    pub jump: Option<Jump>,
+}
+
+impl Jump {
+    /// Returns the position of the jump in `doc`. Lines and columns past the
+    /// end are clamped to it; without a column the cursor goes to the first
+    /// non-blank char.
+    pub fn resolve(&self, doc: &Document) -> Result<(usize, usize), String> {
+        let lines = doc.lines();
+        let last = lines.len() - 1;
+        let first_non_blank =
+            |row: usize| lines[row].chars().take_while(|c| c.is_whitespace()).count();
+        match self {
+            Jump::Line { line, col } => {
+                let row = line.saturating_sub(1).min(last);
+                let col = match col {
+                    Some(col) => col.saturating_sub(1).min(lines[row].len()),
+                    None => first_non_blank(row),
+                };
+                Ok((row, col))
+            }
+            Jump::Pattern(pattern) => {
+                let re = Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+                lines
+                    .iter()
+                    .enumerate()
+                    .find_map(|(row, line)| {
+                        let text = line.to_string();
+                        let m = re.find(&text)?;
+                        Some((row, text[..m.start()].chars().count()))
+                    })
+                    .ok_or_else(|| format!("Pattern not found: {}", pattern))
+            }
+            Jump::LastLine => Ok((last, first_non_blank(last))),
+        }
+    }
+}
+
+/// Parses the file arguments of the command line. A `+N`, `+/pattern` or
+/// `+` argument applies to the file after it, or to the last file if none
+/// follows. A file that does not exist but is written as `file:line` or
+/// `file:line:col`, as in compiler messages, is opened at that position.
+pub fn parse_file_args(args: &[String]) -> Result<Vec<FileArg>, String> {
+    let mut files: Vec<FileArg> = Vec::new();
+    let mut jump = None;
+    for arg in args {
+        if let Some(cmd) = arg.strip_prefix('+') {
+            jump = Some(parse_jump(cmd)?);
+            continue;
+        }
+        let (path, at) = split_location(arg);
+        files.push(FileArg {
+            path,
+            jump: jump.take().or(at),
+        });
+    }
+
+    if let Some(jump) = jump {
+        match files.last_mut() {
+            Some(file) => file.jump = Some(jump),
+            None => return Err("No file to open at the given position".to_string()),
+        }
+    }
+    Ok(files)
+}
+
+fn parse_jump(cmd: &str) -> Result<Jump, String> {
+    if cmd.is_empty() {
+        Ok(Jump::LastLine)
+    } else if let Some(pattern) = cmd.strip_prefix('/') {
+        Ok(Jump::Pattern(pattern.to_string()))
+    } else {
+        cmd.parse()
+            .map(|line| Jump::Line { line, col: None })
+            .map_err(|_| format!("Unknown startup command: +{}", cmd))
+    }
+}
+
+/// Splits a trailing `:line` or `:line:col` off `arg`, unless `arg` names an
+/// existing file as it is.
+fn split_location(arg: &str) -> (String, Option<Jump>) {
+    match LOCATION.captures(arg).filter(|_| !Path::new(arg).exists()) {
+        Some(caps) => {
+            let number = |i: usize| caps.get(i).and_then(|m| m.as_str().parse().ok());
+            let jump = number(2).map(|line| Jump::Line {
+                line,
+                col: number(3),
+            });
+            (caps[1].to_string(), jump)
+        }
+        None => (arg.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn a_missing_file_with_a_location_opens_there() {
+        let files = parse_file_args(&args(&["no-such-file.rs:12:5", "other.rs:3:"])).unwrap();
+        assert_eq!(files[0].path, "no-such-file.rs");
+        assert_eq!(
+            files[0].jump,
+            Some(Jump::Line {
+                line: 12,
+                col: Some(5)
+            })
+        );
+        assert_eq!(files[1].path, "other.rs");
+        assert_eq!(files[1].jump, Some(Jump::Line { line: 3, col: None }));
+    }
+
+    #[test]
+    fn an_existing_file_keeps_the_colon_in_its_name() {
+        let path = env::temp_dir().join(format!("text-editor-jump-{}:3", std::process::id()));
+        fs::write(&path, "").unwrap();
+        let arg = path.to_str().unwrap();
+        let files = parse_file_args(&args(&[arg])).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            files,
+            [FileArg {
+                path: arg.to_string(),
+                jump: None
+            }]
+        );
+    }
+
+    #[test]
+    fn a_jump_applies_to_the_next_file_or_else_the_last() {
+        let files = parse_file_args(&args(&["+3", "a", "b", "c", "+/fn"])).unwrap();
+        assert_eq!(files[0].jump, Some(Jump::Line { line: 3, col: None }));
+        assert_eq!(files[1].jump, None);
+        assert_eq!(files[2].jump, Some(Jump::Pattern("fn".to_string())));
+    }
+
+    #[test]
+    fn a_jump_without_a_file_is_an_error() {
+        assert_eq!(
+            parse_file_args(&args(&["+"])),
+            Err("No file to open at the given position".to_string())
+        );
+        assert!(parse_file_args(&args(&["+x", "a"])).is_err());
+    }
+
+    #[test]
+    fn lines_and_columns_past_the_end_are_clamped() {
+        let doc = Document::from_text("one\n  two");
+        let jump = Jump::Line {
+            line: 9,
+            col: Some(9),
+        };
+        assert_eq!(jump.resolve(&doc), Ok((1, 5)));
+        let jump = Jump::Line { line: 9, col: None };
+        assert_eq!(jump.resolve(&doc), Ok((1, 2)));
+        assert_eq!(Jump::LastLine.resolve(&doc), Ok((1, 2)));
+    }
+
+    #[test]
+    fn patterns_find_the_first_match() {
+        let doc = Document::from_text("one\ntwo two");
+        assert_eq!(Jump::Pattern("wo".to_string()).resolve(&doc), Ok((1, 1)));
+        assert_eq!(
+            Jump::Pattern("six".to_string()).resolve(&doc),
+            Err("Pattern not found: six".to_string())
+        );
+    }
+
+    #[test]
+    fn an_invalid_pattern_is_an_error() {
+        let doc = Document::from_text("one");
+        let err = Jump::Pattern("(".to_string()).resolve(&doc).unwrap_err();
+        assert!(err.starts_with("Invalid pattern: "), "{}", err);
+    }
+}
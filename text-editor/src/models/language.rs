@@ -0,0 +1,65 @@
+use std::{collections::HashMap, fs, io};
+
+use serde::Deserialize;
+
+use crate::models::options::config_dir;
+
+const BUILTIN_LANGUAGES: &str = include_str!("../../languages.toml");
+
+/// A `Command` is an external program run for a language, such as its
+/// language server.
+#[derive(Clone, Deserialize, Debug)]
+pub struct Command {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// A `LanguageConfig` holds the settings of one language.
+#[derive(Clone, Default, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct LanguageConfig {
+    pub language_server: Option<Command>,
+    /// Reads the buffer on stdin and writes it back formatted.
+    pub formatter: Option<Command>,
+    /// Whether buffers are formatted when saved.
+    #[serde(default)]
+    pub auto_format: bool,
+}
+
+/// The `Languages` map grammar names to their settings.
+#[derive(Default)]
+pub struct Languages {
+    configs: HashMap<String, LanguageConfig>,
+}
+
+impl Languages {
+    /// Loads the built-in settings and overrides them with the user's
+    /// `languages.toml`, returning an error if the user's file is invalid.
+    pub fn load() -> (Self, Vec<String>) {
+        let mut configs: HashMap<String, LanguageConfig> =
+            toml::from_str(BUILTIN_LANGUAGES).expect("built-in languages are valid");
+        let mut errors = Vec::new();
+
+        if let Some(path) = config_dir().map(|dir| dir.join("languages.toml")) {
+            match fs::read_to_string(&path) {
+                Ok(source) => match toml::from_str::<HashMap<String, LanguageConfig>>(&source) {
+                    Ok(user) => configs.extend(user),
+                    Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+                },
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+
+        (Self { configs }, errors)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LanguageConfig> {
+        self.configs.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, config: LanguageConfig) {
+        self.configs.insert(name.to_string(), config);
+    }
+}
@@ -0,0 +1,391 @@
+use std::ops::Range;
+
+use crate::models::{
+    gap_buffer::GapBuffer,
+    options::Options,
+    wrap::{self, ScreenRow},
+};
+
+type Pos = (usize, usize); // (row, col)
+
+/// A `DisplayLine` is one row of a window's text area: the chars
+/// `start..end` of document row `row`, drawn after `indent` columns.
+/// `wrap_index` counts the screen rows of the same line before it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DisplayLine {
+    pub row: usize,
+    pub wrap_index: usize,
+    pub start: usize,
+    pub end: usize,
+    pub indent: usize,
+    pub last: bool, // whether it is the last screen row of its line
+}
+
+impl DisplayLine {
+    /// Returns whether the cursor at column `col` of this line's row is
+    /// drawn on this display line.
+    fn contains(&self, col: usize) -> bool {
+        self.start <= col && (col < self.end || self.last)
+    }
+}
+
+/// The `Layout` of a window maps the document lines it shows onto the rows
+/// of its text area, starting at the window's `top`. Drawing, cursor
+/// placement, mouse hit-testing and scrolling all go through it so they
+/// agree on where every char is.
+pub struct Layout {
+    width: usize,
+    hscroll: usize,
+    lines: Vec<DisplayLine>,
+}
+
+impl Layout {
+    /// Lays out `lines` in a text area `width` by `height` cells. `top` is
+    /// the first document row shown and how many of its screen rows are
+    /// scrolled off, `hscroll` the first column shown when not wrapping.
+    pub fn new(
+        lines: &[GapBuffer],
+        top: Pos,
+        hscroll: usize,
+        width: usize,
+        height: usize,
+        options: &Options,
+    ) -> Self {
+        let width = width.max(1);
+        let top = clamp_top(lines, top, width, options);
+        let mut display = Vec::new();
+
+        'rows: for (row, line) in lines.iter().enumerate().skip(top.0) {
+            let rows = line_rows(line, width, options);
+            let count = rows.len();
+            let skip = if row == top.0 { top.1 } else { 0 };
+            for (i, r) in rows.into_iter().enumerate().skip(skip) {
+                if display.len() >= height {
+                    break 'rows;
+                }
+                display.push(DisplayLine {
+                    row,
+                    wrap_index: i,
+                    start: r.start,
+                    end: r.end,
+                    indent: r.indent,
+                    last: i + 1 == count,
+                });
+            }
+        }
+
+        Self {
+            width,
+            hscroll: if options.wrap { 0 } else { hscroll },
+            lines: display,
+        }
+    }
+
+    pub fn lines(&self) -> &[DisplayLine] {
+        &self.lines
+    }
+
+    /// Returns the chars of `line` that are drawn, which under `nowrap` is
+    /// the part scrolled into view.
+    pub fn visible(&self, line: &DisplayLine) -> Range<usize> {
+        let start = (line.start + self.hscroll).min(line.end);
+        let end = (start + self.width.saturating_sub(line.indent)).min(line.end);
+        start..end
+    }
+
+    /// Maps a document position to the `(y, x)` cell it is drawn in, if it
+    /// is on screen.
+    pub fn to_screen(&self, (row, col): Pos) -> Option<(usize, usize)> {
+        let y = self
+            .lines
+            .iter()
+            .position(|l| l.row == row && l.contains(col))?;
+        let line = &self.lines[y];
+        let x = (line.indent + col - line.start).checked_sub(self.hscroll)?;
+        (x < self.width).then_some((y, x))
+    }
+
+    /// Maps the cell `(y, x)` back to the document position drawn there;
+    /// the inverse of `to_screen`. Cells past the end of a line map to its
+    /// end, cells below the last line to `None`.
+    pub fn to_doc(&self, y: usize, x: usize) -> Option<Pos> {
+        let line = self.lines.get(y)?;
+        let col = line.start + self.hscroll + x.saturating_sub(line.indent);
+        let last = if line.last { line.end } else { line.end - 1 };
+        Some((line.row, col.min(last)))
+    }
+}
+
+/// Returns the screen rows `line` is drawn on in a text area `width` wide.
+pub fn line_rows(line: &GapBuffer, width: usize, options: &Options) -> Vec<ScreenRow> {
+    let chars: Vec<char> = line.chars().collect();
+    wrap::screen_rows(&chars, width, options)
+}
+
+fn clamp_top(lines: &[GapBuffer], top: Pos, width: usize, options: &Options) -> Pos {
+    let row = top.0.min(lines.len() - 1);
+    let rows = line_rows(&lines[row], width, options).len();
+    (row, top.1.min(rows - 1))
+}
+
+/// Returns the `top` closest to the current one that shows `cursor` in a
+/// text area `width` by `height` cells.
+pub fn scroll_to(
+    lines: &[GapBuffer],
+    top: Pos,
+    cursor: Pos,
+    width: usize,
+    height: usize,
+    options: &Options,
+) -> Pos {
+    let (width, height) = (width.max(1), height.max(1));
+    let top = clamp_top(lines, top, width, options);
+    let rows = |row: usize| line_rows(&lines[row], width, options).len();
+    let r = wrap::row_of(&line_rows(&lines[cursor.0], width, options), cursor.1);
+
+    if (cursor.0, r) < top {
+        let skip = if rows(cursor.0) <= height { 0 } else { r };
+        return (cursor.0, skip);
+    }
+
+    // count the screen rows from the cursor back up to the top, stopping
+    // at the first line that no longer fits
+    let skipped = if top.0 == cursor.0 { top.1 } else { 0 };
+    let mut count = r + 1 - skipped;
+    if count > height {
+        return (cursor.0, r + 1 - height);
+    }
+    let mut row = cursor.0;
+    while row > top.0 {
+        let skipped = if row - 1 == top.0 { top.1 } else { 0 };
+        let n = rows(row - 1) - skipped;
+        if count + n > height {
+            return (row, 0);
+        }
+        count += n;
+        row -= 1;
+    }
+
+    top
+}
+
+/// Moves `top` by `delta` screen rows.
+pub fn scroll_by(
+    lines: &[GapBuffer],
+    top: Pos,
+    delta: isize,
+    width: usize,
+    options: &Options,
+) -> Pos {
+    let mut top = clamp_top(lines, top, width, options);
+    let rows = |row: usize| line_rows(&lines[row], width, options).len();
+
+    for _ in 0..delta.unsigned_abs() {
+        if delta > 0 {
+            if top.1 + 1 < rows(top.0) {
+                top.1 += 1;
+            } else if top.0 + 1 < lines.len() {
+                top = (top.0 + 1, 0);
+            }
+        } else if top.1 > 0 {
+            top.1 -= 1;
+        } else if top.0 > 0 {
+            top = (top.0 - 1, rows(top.0 - 1) - 1);
+        }
+    }
+
+    top
+}
+
+/// Moves `pos` by `dy` screen rows, aiming for the screen column that
+/// `pref_col` was at. Returns the new position and the `pref_col` that
+/// keeps aiming for the same screen column.
+pub fn screen_motion(
+    lines: &[GapBuffer],
+    (row, col): Pos,
+    pref_col: usize,
+    dy: isize,
+    width: usize,
+    options: &Options,
+) -> (Pos, usize) {
+    let rows = line_rows(&lines[row], width, options);
+    let r = wrap::row_of(&rows, col);
+    let current = rows[r];
+    let goal = current.indent + pref_col.max(col).max(current.start) - current.start;
+
+    let target = if dy < 0 {
+        if r > 0 {
+            Some((row, rows[r - 1]))
+        } else if row > 0 {
+            let rows = line_rows(&lines[row - 1], width, options);
+            Some((row - 1, rows[rows.len() - 1]))
+        } else {
+            None
+        }
+    } else if r + 1 < rows.len() {
+        Some((row, rows[r + 1]))
+    } else if row + 1 < lines.len() {
+        Some((row + 1, line_rows(&lines[row + 1], width, options)[0]))
+    } else {
+        None
+    };
+
+    match target {
+        Some((new_row, target)) => {
+            let wanted = target.start + goal.saturating_sub(target.indent);
+            let last = if target.end == lines[new_row].len() {
+                target.end
+            } else {
+                target.end - 1
+            };
+            ((new_row, wanted.min(last)), wanted)
+        }
+        None if dy < 0 => ((row, 0), pref_col),
+        None => ((row, lines[row].len()), pref_col),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn buffer(text: &str) -> GapBuffer {
+        let mut line = GapBuffer::new();
+        line.insert_str(0, text);
+        line
+    }
+
+    fn options(wrap: bool, linebreak: bool, breakindent: bool, showbreak: &str) -> Options {
+        Options {
+            wrap,
+            linebreak,
+            breakindent,
+            showbreak: showbreak.to_string(),
+            ..Options::default()
+        }
+    }
+
+    fn arb_lines() -> impl Strategy<Value = Vec<String>> {
+        prop::collection::vec("[ a-z]{0,40}", 1..12)
+    }
+
+    /// `wrap`, `linebreak`, `breakindent` and `showbreak`.
+    fn arb_options() -> impl Strategy<Value = (bool, bool, bool, String)> {
+        (any::<bool>(), any::<bool>(), any::<bool>(), "[>+]{0,3}")
+    }
+
+    fn build(lines: &[String], opts: &(bool, bool, bool, String)) -> (Vec<GapBuffer>, Options) {
+        let lines = lines.iter().map(|l| buffer(l)).collect();
+        (lines, options(opts.0, opts.1, opts.2, &opts.3))
+    }
+
+    fn positions(lines: &[GapBuffer]) -> Vec<Pos> {
+        lines
+            .iter()
+            .enumerate()
+            .flat_map(|(row, line)| (0..=line.len()).map(move |col| (row, col)))
+            .collect()
+    }
+
+    #[test]
+    fn exact_multiple_of_width_gets_its_own_row_for_the_end() {
+        let lines = vec![buffer("abcd"), buffer("ef")];
+        let opts = options(true, false, false, "");
+        let layout = Layout::new(&lines, (0, 0), 0, 4, 10, &opts);
+        assert_eq!(layout.lines().len(), 3);
+        assert_eq!(layout.to_screen((0, 4)), Some((1, 0)));
+        assert_eq!(layout.to_screen((1, 0)), Some((2, 0)));
+    }
+
+    proptest! {
+        #[test]
+        fn display_lines_cover_each_line_in_order(
+            lines in arb_lines(),
+            width in 1usize..20,
+            opts in arb_options(),
+        ) {
+            let (lines, opts) = build(&lines, &opts);
+            let layout = Layout::new(&lines, (0, 0), 0, width, usize::MAX, &opts);
+            for (row, line) in lines.iter().enumerate() {
+                let parts: Vec<&DisplayLine> =
+                    layout.lines().iter().filter(|l| l.row == row).collect();
+                prop_assert!(!parts.is_empty());
+                prop_assert_eq!(parts[0].start, 0);
+                prop_assert_eq!(parts[parts.len() - 1].end, line.len());
+                prop_assert!(parts[parts.len() - 1].last);
+                for pair in parts.windows(2) {
+                    prop_assert_eq!(pair[0].end, pair[1].start);
+                }
+                for part in parts {
+                    if opts.wrap {
+                        prop_assert!(part.indent + part.end - part.start <= width);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn cursor_maps_to_a_drawn_cell_and_back(
+            lines in arb_lines(),
+            width in 1usize..20,
+            height in 1usize..10,
+            opts in arb_options(),
+            pick in any::<prop::sample::Index>(),
+        ) {
+            let (lines, opts) = build(&lines, &opts);
+            let cursor = pick.get(&positions(&lines)).to_owned();
+            let top = scroll_to(&lines, (0, 0), cursor, width, height, &opts);
+            let hscroll = wrap::side_scroll(0, cursor.1, width, &opts);
+            let layout = Layout::new(&lines, top, hscroll, width, height, &opts);
+
+            let (y, x) = layout.to_screen(cursor).expect("cursor is on screen");
+            prop_assert!(y < height && x < width);
+            let line = layout.lines()[y];
+            prop_assert_eq!(line.row, cursor.0);
+            // the cell is where the cursor's char is drawn, or just past
+            // the drawn text at the end of the line
+            let drawn = layout.visible(&line);
+            prop_assert_eq!(x, line.indent + cursor.1 - drawn.start);
+            prop_assert_eq!(layout.to_doc(y, x), Some(cursor));
+        }
+
+        #[test]
+        fn scrolling_keeps_the_cursor_on_screen_from_any_top(
+            lines in arb_lines(),
+            width in 1usize..20,
+            height in 1usize..10,
+            opts in arb_options(),
+            pick in any::<prop::sample::Index>(),
+            top_row in any::<prop::sample::Index>(),
+        ) {
+            let (lines, opts) = build(&lines, &opts);
+            let cursor = pick.get(&positions(&lines)).to_owned();
+            let top = (top_row.index(lines.len()), 0);
+            let top = scroll_to(&lines, top, cursor, width, height, &opts);
+            let layout = Layout::new(&lines, top, 0, width, height, &opts);
+            let y = layout.lines().iter().position(|l| l.row == cursor.0 && l.contains(cursor.1));
+            prop_assert!(y.is_some());
+        }
+
+        #[test]
+        fn screen_motion_moves_one_display_line(
+            lines in arb_lines(),
+            width in 1usize..20,
+            opts in arb_options(),
+            pick in any::<prop::sample::Index>(),
+            down in any::<bool>(),
+        ) {
+            let (lines, opts) = build(&lines, &opts);
+            let opts = Options { wrap: true, ..opts };
+            let cursor = pick.get(&positions(&lines)).to_owned();
+            let layout = Layout::new(&lines, (0, 0), 0, width, usize::MAX, &opts);
+            let (y, _) = layout.to_screen(cursor).unwrap();
+            let dy = if down { 1 } else { -1 };
+            let (moved, _) = screen_motion(&lines, cursor, cursor.1, dy, width, &opts);
+            let (new_y, _) = layout.to_screen(moved).unwrap();
+            let expected = (y as isize + dy).clamp(0, layout.lines().len() as isize - 1);
+            prop_assert_eq!(new_y as isize, expected);
+        }
+    }
+}
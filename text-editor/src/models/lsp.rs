@@ -0,0 +1,742 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command as Process, Stdio},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+use serde_json::{Value, json};
+
+use crate::models::{
+    diagnostic::Diagnostic, document::Document, gap_buffer::GapBuffer, language::Command,
+    message::Severity,
+};
+
+/// What a request was sent for, so that its response can be acted on.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Request {
+    Initialize,
+    Shutdown,
+    Hover,
+    Definition,
+    References,
+    Rename,
+    Completion {
+        buffer: usize,
+        start: (usize, usize),
+    },
+    CodeAction,
+    ExecuteCommand,
+}
+
+/// An `Event` is something a language server sent that the editor acts on.
+#[derive(Debug)]
+pub enum Event {
+    Response {
+        request: Request,
+        result: Result<Value, String>,
+    },
+    Diagnostics {
+        path: PathBuf,
+        diagnostics: Vec<Value>,
+    },
+    ApplyEdit(Value),
+    Message {
+        text: String,
+        severity: Severity,
+    },
+    Exited,
+}
+
+/// A `TextEdit` is a change to a document in the positions language servers
+/// use: 0-based lines and UTF-16 columns, with `end` as it was before the
+/// change.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub text: String,
+}
+
+/// A `LanguageServer` is a connection to a server speaking the Language
+/// Server Protocol. Messages are written and read by background threads,
+/// so sending never blocks and responses are picked up with `poll`.
+pub struct LanguageServer {
+    name: String,
+    outgoing: Sender<Value>,
+    incoming: Receiver<Value>,
+    child: Option<Child>,
+    next_id: u64,
+    requests: HashMap<u64, Request>,
+    capabilities: Option<Value>, // set once the server is initialized
+    opened: HashSet<PathBuf>,
+    exited: bool,
+}
+
+impl LanguageServer {
+    /// Starts `command` as a language server for the workspace at `root`.
+    pub fn start(name: &str, command: &Command, root: &Path) -> io::Result<Self> {
+        let mut child = Process::new(&command.command)
+            .args(&command.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut server = Self::connect(name, stdout, stdin, root);
+        server.child = Some(child);
+        Ok(server)
+    }
+
+    /// Connects to a server that reads from `writer` and writes to
+    /// `reader`, and starts initializing it.
+    pub fn connect(
+        name: &str,
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        root: &Path,
+    ) -> Self {
+        let (outgoing, to_write) = mpsc::channel::<Value>();
+        let (read, incoming) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut writer = writer;
+            for message in to_write {
+                if write_message(&mut writer, &message).is_err() {
+                    break;
+                }
+            }
+        });
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            loop {
+                match read_message(&mut reader) {
+                    Ok(Some(message)) => {
+                        if read.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    // a malformed message is skipped, not fatal
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {}
+                    _ => break,
+                }
+            }
+        });
+
+        let mut server = Self {
+            name: name.to_string(),
+            outgoing,
+            incoming,
+            child: None,
+            next_id: 0,
+            requests: HashMap::new(),
+            capabilities: None,
+            opened: HashSet::new(),
+            exited: false,
+        };
+        let params = json!({
+            "processId": std::process::id(),
+            "clientInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+            "rootUri": path_to_uri(root),
+            "capabilities": client_capabilities(),
+        });
+        server.send_request("initialize", params, Request::Initialize);
+        server
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether the server answered `initialize` and can be sent
+    /// documents and requests.
+    pub fn is_ready(&self) -> bool {
+        self.capabilities.is_some() && !self.exited
+    }
+
+    /// Returns whether the server announced the capability `provider`, such
+    /// as `hoverProvider`.
+    pub fn supports(&self, provider: &str) -> bool {
+        self.capabilities
+            .as_ref()
+            .and_then(|c| c.get(provider))
+            .is_some_and(|v| !v.is_null() && *v != Value::Bool(false))
+    }
+
+    /// Sends the request `method`, whose response comes back from `poll` as
+    /// a `Response` for `request`.
+    pub fn request(&mut self, method: &str, params: Value, request: Request) {
+        self.send_request(method, params, request);
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn send_request(&mut self, method: &str, params: Value, request: Request) {
+        self.next_id += 1;
+        self.requests.insert(self.next_id, request);
+        self.send(
+            json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params }),
+        );
+    }
+
+    fn send(&mut self, message: Value) {
+        // a server that went away is reported by `poll`
+        let _ = self.outgoing.send(message);
+    }
+
+    /// Returns what the server sent since the last poll, answering its own
+    /// requests to the editor on the way.
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        loop {
+            match self.incoming.try_recv() {
+                Ok(message) => self.handle(message, &mut events),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if !self.exited {
+                        self.exited = true;
+                        events.push(Event::Exited);
+                    }
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    fn handle(&mut self, message: Value, events: &mut Vec<Event>) {
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        match (message.get("id"), method) {
+            (Some(id), Some(method)) => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = params["items"].as_array().map_or(0, Vec::len);
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    "workspace/applyEdit" => {
+                        events.push(Event::ApplyEdit(params["edit"].clone()));
+                        json!({ "applied": true })
+                    }
+                    _ => Value::Null,
+                };
+                self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+            }
+            (None, Some("textDocument/publishDiagnostics")) => {
+                if let Some(path) = params["uri"].as_str().and_then(uri_to_path) {
+                    let diagnostics = params["diagnostics"].as_array().cloned();
+                    events.push(Event::Diagnostics {
+                        path,
+                        diagnostics: diagnostics.unwrap_or_default(),
+                    });
+                }
+            }
+            (None, Some("window/showMessage")) => {
+                let severity = match params["type"].as_u64() {
+                    Some(1) => Severity::Error,
+                    Some(2) => Severity::Warn,
+                    _ => Severity::Info,
+                };
+                let text = params["message"].as_str().unwrap_or_default();
+                events.push(Event::Message {
+                    text: format!("{}: {}", self.name, text),
+                    severity,
+                });
+            }
+            (Some(id), None) => {
+                let Some(request) = id.as_u64().and_then(|id| self.requests.remove(&id)) else {
+                    return;
+                };
+                let result = match message.get("error") {
+                    Some(error) => Err(error["message"].as_str().unwrap_or("error").to_string()),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                match (request, result) {
+                    (Request::Initialize, Ok(result)) => {
+                        self.capabilities = Some(result["capabilities"].clone());
+                        self.notify("initialized", json!({}));
+                    }
+                    (Request::Shutdown, _) => {}
+                    (request, result) => events.push(Event::Response { request, result }),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns whether typing `c` should ask the server for completions.
+    pub fn is_trigger(&self, c: char) -> bool {
+        self.capabilities
+            .as_ref()
+            .and_then(|caps| caps["completionProvider"]["triggerCharacters"].as_array())
+            .is_some_and(|chars| chars.iter().any(|t| t.as_str() == Some(&c.to_string())))
+    }
+
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.opened.contains(path)
+    }
+
+    /// Sends the whole text of `doc`, stored at `path`, to the server.
+    pub fn did_open(&mut self, path: &Path, doc: &Document) {
+        self.opened.insert(path.to_path_buf());
+        let language = match doc.file_type() {
+            Some("shell") => "shellscript",
+            Some(language) => language,
+            None => "plaintext",
+        };
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": path_to_uri(path),
+                    "languageId": language,
+                    "version": doc.version(),
+                    "text": doc.text(),
+                }
+            }),
+        );
+    }
+
+    /// Sends the `edits` made to `doc` since the last sync, or its whole
+    /// text if the server does not take incremental changes.
+    pub fn did_change(&mut self, path: &Path, doc: &Document, edits: Vec<TextEdit>) {
+        let sync = self.capabilities.as_ref().map(|c| &c["textDocumentSync"]);
+        let kind = match sync {
+            Some(Value::Number(n)) => n.as_u64(),
+            Some(Value::Object(o)) => o.get("change").and_then(Value::as_u64),
+            _ => None,
+        };
+        let changes: Vec<Value> = match kind {
+            Some(2) => edits
+                .into_iter()
+                .map(|edit| {
+                    json!({
+                        "range": {
+                            "start": { "line": edit.start.0, "character": edit.start.1 },
+                            "end": { "line": edit.end.0, "character": edit.end.1 },
+                        },
+                        "text": edit.text,
+                    })
+                })
+                .collect(),
+            Some(1) => vec![json!({ "text": doc.text() })],
+            _ => return,
+        };
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": path_to_uri(path), "version": doc.version() },
+                "contentChanges": changes,
+            }),
+        );
+    }
+
+    pub fn did_save(&mut self, path: &Path, doc: &Document) {
+        let include_text = self
+            .capabilities
+            .as_ref()
+            .and_then(|c| c["textDocumentSync"]["save"]["includeText"].as_bool())
+            .unwrap_or(false);
+        let mut params = json!({ "textDocument": { "uri": path_to_uri(path) } });
+        if include_text {
+            params["text"] = Value::String(doc.text());
+        }
+        self.notify("textDocument/didSave", params);
+    }
+
+    /// Tells the server the file at `path` is no longer edited, for example
+    /// because its buffer was closed or now writes to another file.
+    pub fn did_close(&mut self, path: &Path) {
+        self.opened.remove(path);
+        self.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": path_to_uri(path) } }),
+        );
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        if !self.exited {
+            self.send_request("shutdown", Value::Null, Request::Shutdown);
+            self.notify("exit", Value::Null);
+        }
+        if let Some(mut child) = self.child.take() {
+            // give the server a moment to exit on its own before killing it,
+            // without holding up the editor
+            thread::spawn(move || {
+                thread::sleep(std::time::Duration::from_millis(50));
+                if !matches!(child.try_wait(), Ok(Some(_))) {
+                    let _ = child.kill();
+                }
+                let _ = child.wait();
+            });
+        }
+    }
+}
+
+/// The `Lsp` holds the language servers started by the editor, one per
+/// language.
+pub struct Lsp {
+    servers: HashMap<String, LanguageServer>,
+    failed: HashSet<String>, // languages whose server could not start
+}
+
+impl Lsp {
+    pub fn new() -> Self {
+        Self {
+            servers: HashMap::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    /// Returns the server for `language`, starting it with `command` for
+    /// the project at `root` the first time. A server that failed to start
+    /// is not tried again.
+    pub fn server(
+        &mut self,
+        language: &str,
+        command: Option<&Command>,
+        root: &Path,
+    ) -> io::Result<Option<&mut LanguageServer>> {
+        if !self.servers.contains_key(language) {
+            let Some(command) = command.filter(|_| !self.failed.contains(language)) else {
+                return Ok(None);
+            };
+            match LanguageServer::start(&command.command, command, root) {
+                Ok(server) => {
+                    self.servers.insert(language.to_string(), server);
+                }
+                Err(e) => {
+                    self.failed.insert(language.to_string());
+                    return Err(e);
+                }
+            }
+        }
+        Ok(self.servers.get_mut(language))
+    }
+
+    /// Uses `server` for `language` instead of starting one.
+    pub fn insert(&mut self, language: &str, server: LanguageServer) {
+        self.servers.insert(language.to_string(), server);
+    }
+
+    pub fn get_mut(&mut self, language: &str) -> Option<&mut LanguageServer> {
+        self.servers.get_mut(language)
+    }
+
+    /// Stops using the server for `language`, for example after it exited.
+    pub fn remove(&mut self, language: &str) {
+        self.servers.remove(language);
+        self.failed.insert(language.to_string());
+    }
+
+    /// Returns what every server sent since the last poll, with the language
+    /// it serves.
+    pub fn poll(&mut self) -> Vec<(String, Event)> {
+        let mut events = Vec::new();
+        for (language, server) in &mut self.servers {
+            events.extend(server.poll().into_iter().map(|e| (language.clone(), e)));
+        }
+        events
+    }
+}
+
+impl Default for Lsp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the editor tells servers it can do.
+fn client_capabilities() -> Value {
+    json!({
+        "general": { "positionEncodings": ["utf-16"] },
+        "window": { "workDoneProgress": false },
+        "workspace": {
+            "applyEdit": true,
+            "configuration": true,
+            "workspaceEdit": { "documentChanges": true },
+        },
+        "textDocument": {
+            "synchronization": { "didSave": true, "dynamicRegistration": false },
+            "publishDiagnostics": { "relatedInformation": false },
+            "hover": { "contentFormat": ["plaintext", "markdown"] },
+            "definition": { "linkSupport": true },
+            "references": {},
+            "rename": { "prepareSupport": false },
+            "completion": {
+                "completionItem": { "snippetSupport": false },
+                "contextSupport": true,
+            },
+            "codeAction": {
+                "codeActionLiteralSupport": {
+                    "codeActionKind": {
+                        "valueSet": ["", "quickfix", "refactor", "refactor.extract",
+                            "refactor.inline", "refactor.rewrite", "source",
+                            "source.organizeImports"]
+                    }
+                }
+            },
+        },
+    })
+}
+
+/// Reads one message framed by a `Content-Length` header, or `None` at the
+/// end of the stream.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes `message` with the `Content-Length` header that frames it.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Returns the `file://` URI of `path`, made absolute.
+pub fn path_to_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'.' | b'_' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Returns the path of a `file://` URI.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match hex.filter(|_| encoded[i] == b'%') {
+            Some(hex) => {
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            None => {
+                bytes.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// Returns the UTF-16 column of char column `col` of `line`.
+pub fn utf16_col(line: &GapBuffer, col: usize) -> usize {
+    line.chars().take(col).map(char::len_utf16).sum()
+}
+
+/// Returns the char column of UTF-16 column `col` of `line`, clamped to its
+/// end.
+pub fn char_col(line: &GapBuffer, col: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.chars().enumerate() {
+        if units >= col {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// Returns the LSP position of `pos` in `doc`.
+pub fn to_position(doc: &Document, (row, col): (usize, usize)) -> Value {
+    json!({ "line": row, "character": utf16_col(&doc.lines()[row], col) })
+}
+
+/// Returns the position in `doc` of the LSP `position`, clamped to the
+/// document.
+pub fn from_position(doc: &Document, position: &Value) -> Option<(usize, usize)> {
+    let lines = doc.lines();
+    let row = (position["line"].as_u64()? as usize).min(lines.len() - 1);
+    let col = char_col(&lines[row], position["character"].as_u64()? as usize);
+    Some((row, col))
+}
+
+/// Returns the start and end in `doc` of the LSP `range`.
+pub fn from_range(doc: &Document, range: &Value) -> Option<((usize, usize), (usize, usize))> {
+    Some((
+        from_position(doc, &range["start"])?,
+        from_position(doc, &range["end"])?,
+    ))
+}
+
+/// Converts an LSP diagnostic to one placed in `doc`.
+pub fn to_diagnostic(doc: &Document, diagnostic: &Value) -> Option<Diagnostic> {
+    let (start, end) = from_range(doc, &diagnostic["range"])?;
+    let severity = match diagnostic["severity"].as_u64() {
+        Some(1) | None => Severity::Error,
+        Some(2) => Severity::Warn,
+        _ => Severity::Info,
+    };
+    Some(Diagnostic {
+        start,
+        end,
+        severity,
+        message: diagnostic["message"].as_str()?.to_string(),
+        source: diagnostic["source"].as_str().map(str::to_string),
+    })
+}
+
+/// Converts `diagnostic` back to the form servers take in code action
+/// requests.
+pub fn from_diagnostic(doc: &Document, diagnostic: &Diagnostic) -> Value {
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warn => 2,
+        Severity::Info => 3,
+    };
+    json!({
+        "range": {
+            "start": to_position(doc, diagnostic.start),
+            "end": to_position(doc, diagnostic.end),
+        },
+        "severity": severity,
+        "message": diagnostic.message,
+        "source": diagnostic.source,
+    })
+}
+
+/// Returns the locations of a definition or references response, which may
+/// be a single location, a list of them or a list of location links.
+pub fn locations(result: &Value) -> Vec<(PathBuf, Value)> {
+    let items = match result {
+        Value::Array(items) => items.clone(),
+        Value::Null => Vec::new(),
+        item => vec![item.clone()],
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let uri = item.get("uri").or_else(|| item.get("targetUri"))?;
+            let range = item
+                .get("targetSelectionRange")
+                .or_else(|| item.get("range"))?;
+            Some((uri_to_path(uri.as_str()?)?, range["start"].clone()))
+        })
+        .collect()
+}
+
+/// Returns the text of a hover response, whose contents may be markup, a
+/// marked string or a list of marked strings.
+pub fn hover_text(result: &Value) -> String {
+    fn text(contents: &Value) -> String {
+        match contents {
+            Value::String(s) => s.clone(),
+            Value::Array(items) => items.iter().map(text).collect::<Vec<_>>().join("\n\n"),
+            Value::Object(o) => o
+                .get("value")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            _ => String::new(),
+        }
+    }
+    text(&result["contents"]).trim().to_string()
+}
+
+/// Returns the text edits of a workspace edit, by file.
+pub fn workspace_edits(edit: &Value) -> Vec<(PathBuf, Vec<Value>)> {
+    let mut files = Vec::new();
+    if let Some(changes) = edit["documentChanges"].as_array() {
+        // creating, renaming and deleting files is not supported
+        for change in changes {
+            if let (Some(uri), Some(edits)) = (
+                change["textDocument"]["uri"].as_str(),
+                change["edits"].as_array(),
+            ) && let Some(path) = uri_to_path(uri)
+            {
+                files.push((path, edits.clone()));
+            }
+        }
+    } else if let Some(changes) = edit["changes"].as_object() {
+        for (uri, edits) in changes {
+            if let (Some(path), Some(edits)) = (uri_to_path(uri), edits.as_array()) {
+                files.push((path, edits.clone()));
+            }
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> GapBuffer {
+        let mut gb = GapBuffer::new();
+        gb.insert_str(0, text);
+        gb
+    }
+
+    #[test]
+    fn messages_round_trip_through_framing() {
+        let message = json!({ "jsonrpc": "2.0", "id": 1, "result": { "text": "héllo" } });
+        let mut framed = Vec::new();
+        write_message(&mut framed, &message).unwrap();
+        write_message(&mut framed, &message).unwrap();
+
+        let mut reader = io::Cursor::new(framed);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn columns_convert_between_chars_and_utf16() {
+        let line = line("a😀b");
+        assert_eq!(utf16_col(&line, 2), 3);
+        assert_eq!(char_col(&line, 3), 2);
+        assert_eq!(char_col(&line, 99), 3);
+    }
+
+    #[test]
+    fn uris_round_trip() {
+        let path = PathBuf::from("/tmp/a dir/ü.rs");
+        let uri = path_to_uri(&path);
+        assert_eq!(uri, "file:///tmp/a%20dir/%C3%BC.rs");
+        assert_eq!(uri_to_path(&uri), Some(path));
+    }
+}
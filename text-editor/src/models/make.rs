@@ -0,0 +1,281 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::models::{message::Severity, quickfix::Entry};
+
+pub const DEFAULT_MAKEPRG: &str = "cargo build";
+/// Matches `file:line:col: error: message` as compilers print it, the
+/// `%t` taking the type from the first letter of `error` or `warning`.
+pub const DEFAULT_ERRORFORMAT: &str =
+    "%f:%l:%c: %trror: %m,%f:%l:%c: %tarning: %m,%f:%l:%c: %m,%f:%l: %m";
+
+/// An `ErrorFormat` reads compiler output lines into quickfix entries, with
+/// patterns written the way Vim's `errorformat` option writes them: `%f`
+/// is the file, `%l` the line, `%c` the column, `%m` the message and `%t`
+/// a letter giving the type. Patterns are separated by commas, a literal
+/// comma is written `\,` and a literal `%` is written `%%`.
+pub struct ErrorFormat {
+    patterns: Vec<(Regex, Vec<char>)>, // with the fields of the groups
+}
+
+impl ErrorFormat {
+    pub fn parse(efm: &str) -> Result<Self, String> {
+        let mut patterns = Vec::new();
+        for pattern in split_patterns(efm) {
+            let mut regex = String::from("^");
+            let mut fields = Vec::new();
+            let mut chars = pattern.chars();
+            while let Some(c) = chars.next() {
+                if c != '%' {
+                    regex.push_str(&regex::escape(&c.to_string()));
+                    continue;
+                }
+                let item = chars.next();
+                let group = match item {
+                    Some('f') => r"(.+?)",
+                    Some('l') | Some('c') => r"(\d+)",
+                    Some('m') => r"(.+)",
+                    Some('t') => r"([A-Za-z])",
+                    Some('%') => {
+                        regex.push('%');
+                        continue;
+                    }
+                    Some(other) => return Err(format!("Invalid errorformat item: %{}", other)),
+                    None => return Err("Invalid errorformat: ends with %".to_string()),
+                };
+                regex.push_str(group);
+                fields.extend(item);
+            }
+            regex.push('$');
+            let regex = Regex::new(&regex).map_err(|e| format!("Invalid errorformat: {}", e))?;
+            patterns.push((regex, fields));
+        }
+        Ok(Self { patterns })
+    }
+
+    /// Reads `line` with the first pattern that matches it all.
+    pub fn parse_line(&self, line: &str) -> Option<Entry> {
+        self.patterns.iter().find_map(|(regex, fields)| {
+            let caps = regex.captures(line)?;
+            let mut entry = Entry {
+                path: PathBuf::new(),
+                pos: (0, 0),
+                text: String::new(),
+                severity: None,
+            };
+            for (field, m) in fields.iter().zip(caps.iter().skip(1)) {
+                let text = m?.as_str();
+                match field {
+                    'f' => entry.path = PathBuf::from(text),
+                    'l' => entry.pos.0 = text.parse::<usize>().ok()?.saturating_sub(1),
+                    'c' => entry.pos.1 = text.parse::<usize>().ok()?.saturating_sub(1),
+                    't' => entry.severity = severity_for(text),
+                    _ => entry.text = text.trim().to_string(),
+                }
+            }
+            (!entry.path.as_os_str().is_empty()).then_some(entry)
+        })
+    }
+}
+
+/// Splits `efm` at the commas not escaped with a backslash.
+fn split_patterns(efm: &str) -> Vec<String> {
+    let mut patterns = vec![String::new()];
+    let mut chars = efm.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(',') => patterns.last_mut().unwrap().push(','),
+                Some(other) => {
+                    let last = patterns.last_mut().unwrap();
+                    last.push('\\');
+                    last.push(other);
+                }
+                None => patterns.last_mut().unwrap().push('\\'),
+            },
+            ',' => patterns.push(String::new()),
+            _ => patterns.last_mut().unwrap().push(c),
+        }
+    }
+    patterns.retain(|p| !p.is_empty());
+    patterns
+}
+
+/// Reads the type letter of `%t`, or a level name such as cargo's.
+fn severity_for(kind: &str) -> Option<Severity> {
+    match kind.chars().next()?.to_ascii_lowercase() {
+        'e' => Some(Severity::Error),
+        'w' => Some(Severity::Warn),
+        'i' | 'n' | 'h' => Some(Severity::Info),
+        _ => None,
+    }
+}
+
+/// Returns the words of the build command: `makeprg` split at spaces, then
+/// `args`. Cargo is asked for JSON messages, which are read more reliably
+/// than its text, before any `--` that starts the arguments of what it
+/// runs.
+pub fn command_line(makeprg: &str, args: Option<&str>) -> Vec<String> {
+    let mut words: Vec<String> = makeprg
+        .split_whitespace()
+        .chain(args.unwrap_or_default().split_whitespace())
+        .map(str::to_string)
+        .collect();
+    if words.first().is_some_and(|w| w == "cargo")
+        && !words.iter().any(|w| w.starts_with("--message-format"))
+    {
+        let at = words.iter().position(|w| w == "--").unwrap_or(words.len());
+        words.insert(at, "--message-format=json".to_string());
+    }
+    words
+}
+
+/// Runs the build command `words` in `dir`, returning whether it succeeded
+/// and what it wrote to stdout and stderr.
+pub fn run(words: &[String], dir: &Path) -> io::Result<(bool, String)> {
+    let Some((program, args)) = words.split_first() else {
+        return Err(io::Error::other("No build command"));
+    };
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok((output.status.success(), text))
+}
+
+/// A `Build` is a build command running on a background thread, so that
+/// the editor keeps taking keys until it is done.
+pub struct Build {
+    pub words: Vec<String>,
+    result: Receiver<io::Result<(bool, String)>>,
+}
+
+impl Build {
+    /// Starts running the build command `words` in `dir`; see `run`.
+    pub fn start(words: Vec<String>, dir: &Path) -> Self {
+        let (sender, result) = mpsc::channel();
+        let (command, dir) = (words.clone(), dir.to_path_buf());
+        thread::spawn(move || {
+            let _ = sender.send(run(&command, &dir));
+        });
+        Self { words, result }
+    }
+
+    /// Returns the result of the build once it is done, without waiting.
+    pub fn poll(&self) -> Option<io::Result<(bool, String)>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(io::Error::other("build thread stopped"))),
+        }
+    }
+
+    /// Waits for the build to be done and returns its result.
+    pub fn wait(&self) -> io::Result<(bool, String)> {
+        self.result
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("build thread stopped")))
+    }
+}
+
+/// Reads the build output into quickfix entries, each line either one of
+/// cargo's JSON messages or matched against `efm`.
+pub fn parse_output(output: &str, efm: &ErrorFormat) -> Vec<Entry> {
+    output
+        .lines()
+        .filter_map(|line| match serde_json::from_str::<Value>(line) {
+            Ok(value) => cargo_message(&value),
+            Err(_) => efm.parse_line(line),
+        })
+        .collect()
+}
+
+/// Reads a `compiler-message` from cargo's JSON output at its primary span.
+fn cargo_message(value: &Value) -> Option<Entry> {
+    if value["reason"] != "compiler-message" {
+        return None;
+    }
+    let message = &value["message"];
+    let span = message["spans"]
+        .as_array()?
+        .iter()
+        .find(|s| s["is_primary"] == true)?;
+    let row = span["line_start"].as_u64()? as usize;
+    let col = span["column_start"].as_u64()? as usize;
+    Some(Entry {
+        path: PathBuf::from(span["file_name"].as_str()?),
+        pos: (row.saturating_sub(1), col.saturating_sub(1)),
+        text: message["message"].as_str()?.to_string(),
+        severity: severity_for(message["level"].as_str()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errorformat_reads_file_line_column_and_type() {
+        let efm = ErrorFormat::parse(DEFAULT_ERRORFORMAT).unwrap();
+        let entry = efm
+            .parse_line("src/main.rs:2:9: warning: unused variable: `x`")
+            .unwrap();
+        assert_eq!(entry.path, PathBuf::from("src/main.rs"));
+        assert_eq!(entry.pos, (1, 8));
+        assert_eq!(entry.severity, Some(Severity::Warn));
+        assert_eq!(entry.text, "unused variable: `x`");
+
+        let entry = efm.parse_line("main.c:10: missing ;").unwrap();
+        assert_eq!((entry.pos, entry.severity), ((9, 0), None));
+        assert!(efm.parse_line("   Compiling demo v0.1.0").is_none());
+
+        let efm = ErrorFormat::parse(r"%f(%l\,%c): %m").unwrap();
+        assert_eq!(efm.parse_line("a.cs(3,4): oops").unwrap().pos, (2, 3));
+        assert!(ErrorFormat::parse("%f:%q").is_err());
+    }
+
+    #[test]
+    fn cargo_json_messages_point_at_the_primary_span() {
+        let json = r#"{"reason":"compiler-message","message":{"message":"cannot find value `y`","level":"error","spans":[{"file_name":"src/lib.rs","line_start":1,"column_start":3,"is_primary":false},{"file_name":"src/main.rs","line_start":4,"column_start":13,"is_primary":true}]}}"#;
+        let output = format!(
+            "{}\n{{\"reason\":\"build-finished\",\"success\":false}}\nerror: could not compile `demo`\n",
+            json
+        );
+        let efm = ErrorFormat::parse(DEFAULT_ERRORFORMAT).unwrap();
+        let entries = parse_output(&output, &efm);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, PathBuf::from("src/main.rs"));
+        assert_eq!(entries[0].pos, (3, 12));
+        assert_eq!(entries[0].severity, Some(Severity::Error));
+
+        assert_eq!(
+            command_line("cargo build", Some("--release")),
+            ["cargo", "build", "--release", "--message-format=json"]
+        );
+        assert_eq!(
+            command_line("cargo test", Some("-- --nocapture")),
+            [
+                "cargo",
+                "test",
+                "--message-format=json",
+                "--",
+                "--nocapture"
+            ]
+        );
+        assert_eq!(command_line("make", None), ["make"]);
+    }
+}
@@ -0,0 +1,34 @@
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Severity {
+    /// Returns the theme scope messages of this severity are drawn with.
+    pub fn scope(&self) -> &'static str {
+        match self {
+            Severity::Info => "message.info",
+            Severity::Warn => "message.warn",
+            Severity::Error => "message.error",
+        }
+    }
+}
+
+/// A `Message` is shown in the message line below the status line and kept
+/// in the editor's message history.
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub text: String,
+    pub severity: Severity,
+}
+
+impl Message {
+    pub fn new(text: impl Into<String>, severity: Severity) -> Self {
+        Self {
+            text: text.into(),
+            severity,
+        }
+    }
+}
@@ -1,3 +1,34 @@
+pub mod completion;
+pub mod diagnostic;
+pub mod diff;
 pub mod document;
 pub mod editor;
+pub mod explorer;
+pub mod finder;
 pub mod gap_buffer;
+pub mod git;
+pub mod grep;
+pub mod gutter;
+pub mod highlighter;
+pub mod history;
+pub mod jump;
+pub mod language;
+pub mod layout;
+pub mod lsp;
+pub mod make;
+pub mod message;
+pub mod notation;
+pub mod options;
+pub mod popup;
+pub mod quickfix;
+pub mod range;
+pub mod shell;
+pub mod sign;
+pub mod statusline;
+pub mod syntax;
+#[cfg(feature = "tree-sitter")]
+pub mod syntax_tree;
+pub mod tab;
+pub mod theme;
+pub mod window;
+pub mod wrap;
@@ -0,0 +1,114 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Parses keys written in vim-like notation: plain chars stand for
+/// themselves and special keys are written in angle brackets, such as
+/// `<Esc>`, `<CR>`, `<C-w>` or `<S-Tab>`. `<lt>` is a literal `<`.
+pub fn parse_keys(notation: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut keys = Vec::new();
+    let mut chars = notation.chars();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            keys.push(char_key(c, KeyModifiers::NONE));
+            continue;
+        }
+
+        let mut name = String::new();
+        loop {
+            match chars.next() {
+                Some('>') => break,
+                Some(c) => name.push(c),
+                None => return Err(format!("Unclosed key: <{}", name)),
+            }
+        }
+        keys.push(parse_special(&name).ok_or_else(|| format!("Unknown key: <{}>", name))?);
+    }
+    Ok(keys)
+}
+
+/// Parses the name of a key written in angle brackets, with any `C-`, `S-`
+/// and `A-` or `M-` modifier prefixes.
+fn parse_special(name: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while let Some((prefix, tail)) = rest.split_once('-').filter(|(_, t)| !t.is_empty()) {
+        modifiers |= match prefix.to_ascii_uppercase().as_str() {
+            "C" => KeyModifiers::CONTROL,
+            "S" => KeyModifiers::SHIFT,
+            "A" | "M" => KeyModifiers::ALT,
+            _ => return None,
+        };
+        rest = tail;
+    }
+
+    let mut single = rest.chars();
+    if let (Some(c), None) = (single.next(), single.next()) {
+        return Some(char_key(c, modifiers));
+    }
+
+    let code = match rest.to_ascii_lowercase().as_str() {
+        "esc" => KeyCode::Esc,
+        "cr" | "enter" | "return" => KeyCode::Enter,
+        "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "ins" | "insert" => KeyCode::Insert,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        "bar" => KeyCode::Char('|'),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        f => KeyCode::F(
+            f.strip_prefix('f')?
+                .parse()
+                .ok()
+                .filter(|n| (1..=12).contains(n))?,
+        ),
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// Returns the key event a terminal sends for `c`, which has `SHIFT` set for
+/// upper case letters.
+fn char_key(c: char, modifiers: KeyModifiers) -> KeyEvent {
+    let modifiers = if c.is_uppercase() {
+        modifiers | KeyModifiers::SHIFT
+    } else {
+        modifiers
+    };
+    KeyEvent::new(KeyCode::Char(c), modifiers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_and_special_keys() {
+        let keys = parse_keys("iA<Esc><C-w>v<lt><S-Tab><F5>").unwrap();
+        let expected = [
+            KeyEvent::new(KeyCode::Char('i'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT),
+            KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL),
+            KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('<'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT),
+            KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE),
+        ];
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn rejects_unknown_and_unclosed_keys() {
+        assert_eq!(parse_keys("<Nope>"), Err("Unknown key: <Nope>".to_string()));
+        assert_eq!(parse_keys("<C-"), Err("Unclosed key: <C-".to_string()));
+        assert_eq!(parse_keys("<F13>"), Err("Unknown key: <F13>".to_string()));
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use crate::models::{
+    completion::{DEFAULT_SOURCES, Source},
+    make::{DEFAULT_ERRORFORMAT, DEFAULT_MAKEPRG, ErrorFormat},
+    statusline::{DEFAULT_STATUSLINE, Segment},
+    theme::terminal_truecolor,
+};
+
+/// Editor-wide settings changed with `:set`.
+pub struct Options {
+    pub number: bool,
+    pub relativenumber: bool,
+    pub signcolumn: SignColumnMode,
+    pub cursorline: bool,
+    pub termguicolors: bool,
+    pub mouse: bool,
+    pub autosave: bool,
+    pub lsp: bool,
+    pub complete: Vec<Source>,
+    pub autocomplete: bool,
+    pub wrap: bool,
+    pub linebreak: bool,
+    pub breakindent: bool,
+    pub showbreak: String,
+    pub sidescroll: usize,
+    pub sidescrolloff: usize,
+    pub statusline: Vec<Segment>,
+    pub makeprg: String,
+    pub errorformat: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SignColumnMode {
+    Auto,
+    Yes,
+    No,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            number: false,
+            relativenumber: false,
+            signcolumn: SignColumnMode::Auto,
+            cursorline: false,
+            termguicolors: terminal_truecolor(),
+            mouse: true,
+            autosave: false,
+            lsp: true,
+            complete: Source::parse_list(DEFAULT_SOURCES).expect("default sources are valid"),
+            autocomplete: true,
+            wrap: true,
+            linebreak: false,
+            breakindent: false,
+            showbreak: String::new(),
+            sidescroll: 0,
+            sidescrolloff: 0,
+            statusline: Segment::parse_list(DEFAULT_STATUSLINE)
+                .expect("default status line is valid"),
+            makeprg: DEFAULT_MAKEPRG.to_string(),
+            errorformat: DEFAULT_ERRORFORMAT.to_string(),
+        }
+    }
+}
+
+impl Options {
+    /// Applies a single `:set` argument such as `nu`, `norelativenumber` or
+    /// `signcolumn=yes`.
+    pub fn set(&mut self, arg: &str) -> Result<(), String> {
+        if let Some((name, value)) = arg.split_once('=') {
+            return match name {
+                "signcolumn" | "scl" => {
+                    self.signcolumn = match value {
+                        "auto" => SignColumnMode::Auto,
+                        "yes" => SignColumnMode::Yes,
+                        "no" => SignColumnMode::No,
+                        _ => return Err(format!("Invalid argument: {}", arg)),
+                    };
+                    Ok(())
+                }
+                "showbreak" | "sbr" => {
+                    self.showbreak = value.to_string();
+                    Ok(())
+                }
+                "sidescroll" | "ss" => {
+                    self.sidescroll = parse_number(arg, value)?;
+                    Ok(())
+                }
+                "sidescrolloff" | "siso" => {
+                    self.sidescrolloff = parse_number(arg, value)?;
+                    Ok(())
+                }
+                "complete" | "cpt" => {
+                    self.complete = Source::parse_list(value)?;
+                    Ok(())
+                }
+                "statusline" | "stl" => {
+                    self.statusline = Segment::parse_list(value)?;
+                    Ok(())
+                }
+                "makeprg" | "mp" => {
+                    self.makeprg = value.to_string();
+                    Ok(())
+                }
+                "errorformat" | "efm" => {
+                    ErrorFormat::parse(value)?;
+                    self.errorformat = value.to_string();
+                    Ok(())
+                }
+                _ => Err(format!("Unknown option: {}", name)),
+            };
+        }
+
+        let (name, value) = match arg.strip_prefix("no") {
+            Some(name) => (name, false),
+            None => (arg, true),
+        };
+
+        match name {
+            "number" | "nu" => self.number = value,
+            "relativenumber" | "rnu" => self.relativenumber = value,
+            "cursorline" | "cul" => self.cursorline = value,
+            "termguicolors" | "tgc" => self.termguicolors = value,
+            "mouse" => self.mouse = value,
+            "autosave" => self.autosave = value,
+            "lsp" => self.lsp = value,
+            "autocomplete" | "acp" => self.autocomplete = value,
+            "wrap" => self.wrap = value,
+            "linebreak" | "lbr" => self.linebreak = value,
+            "breakindent" | "bri" => self.breakindent = value,
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits the arguments of `:set` at spaces, except those escaped with a
+/// backslash, so `makeprg=cargo\ test` is a single argument.
+pub fn split_args(args: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut arg = String::new();
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(' ') => arg.push(' '),
+                Some(other) => {
+                    arg.push('\\');
+                    arg.push(other);
+                }
+                None => arg.push('\\'),
+            },
+            c if c.is_whitespace() => {
+                if !arg.is_empty() {
+                    split.push(std::mem::take(&mut arg));
+                }
+            }
+            c => arg.push(c),
+        }
+    }
+    if !arg.is_empty() {
+        split.push(arg);
+    }
+    split
+}
+
+fn parse_number(arg: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Number required after =: {}", arg))
+}
+
+/// Returns the editor's configuration directory, `$XDG_CONFIG_HOME/text-editor`
+/// or `~/.config/text-editor`.
+pub fn config_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(config.join("text-editor"))
+}
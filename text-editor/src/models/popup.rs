@@ -0,0 +1,16 @@
+/// A `Popup` shows lines of text over the editor, such as hover
+/// documentation, until the next key is pressed.
+#[derive(Clone, Debug)]
+pub struct Popup {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+impl Popup {
+    pub fn new(title: impl Into<String>, lines: Vec<String>) -> Self {
+        Self {
+            title: title.into(),
+            lines,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use crate::models::message::Severity;
+
+/// A place in a file the quickfix list points at, with the line or message
+/// to show for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub pos: (usize, usize), // row and char column
+    pub text: String,
+    pub severity: Option<Severity>, // of compiler messages
+}
+
+impl Entry {
+    /// Formats the entry as `path:line:col: text`, counting from 1, with
+    /// the severity before the text if there is one.
+    pub fn describe(&self) -> String {
+        format!(
+            "{}:{}:{}: {}{}",
+            self.path.display(),
+            self.pos.0 + 1,
+            self.pos.1 + 1,
+            match self.severity {
+                Some(Severity::Error) => "error: ",
+                Some(Severity::Warn) => "warning: ",
+                Some(Severity::Info) => "note: ",
+                None => "",
+            },
+            self.text
+        )
+    }
+}
+
+/// A `Quickfix` list holds the results of the last `:grep` or `:make`,
+/// stepped through with `:cnext` and `:cprev` and shown in a panel with
+/// `:copen`.
+#[derive(Default)]
+pub struct Quickfix {
+    pub title: String,
+    pub open: bool,
+    pub focused: bool,
+    entries: Vec<Entry>,
+    current: usize,
+}
+
+impl Quickfix {
+    pub fn new(title: impl Into<String>, entries: Vec<Entry>) -> Self {
+        Self {
+            title: title.into(),
+            entries,
+            ..Self::default()
+        }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the index of the current entry.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn current_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.current)
+    }
+
+    /// Makes the entry `delta` places on the current one, stopping at
+    /// either end. Returns whether there was one to move to.
+    pub fn step(&mut self, delta: isize) -> bool {
+        match self.current.checked_add_signed(delta) {
+            Some(i) if i < self.entries.len() => {
+                self.current = i;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Makes entry `index` the current one, if there is one.
+    pub fn set_current(&mut self, index: usize) -> bool {
+        if index < self.entries.len() {
+            self.current = index;
+        }
+        index < self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(row: usize) -> Entry {
+        Entry {
+            path: PathBuf::from("src/main.rs"),
+            pos: (row, 4),
+            text: "fn main() {}".to_string(),
+            severity: None,
+        }
+    }
+
+    #[test]
+    fn steps_stop_at_either_end() {
+        let mut quickfix = Quickfix::new("grep", vec![entry(0), entry(1)]);
+        assert!(!quickfix.step(-1));
+        assert!(quickfix.step(1));
+        assert_eq!(quickfix.current_entry(), Some(&entry(1)));
+        assert!(!quickfix.step(1));
+        assert_eq!(quickfix.current(), 1);
+        assert_eq!(entry(1).describe(), "src/main.rs:2:5: fn main() {}");
+    }
+}
@@ -0,0 +1,132 @@
+use crate::models::document::Document;
+
+type Lines = (usize, usize); // first and last line, counting from 1
+
+/// Splits the line range off the front of Ex command `cmd`, returning the
+/// first and last lines it covers and the rest of the command. Lines count
+/// from 1 as in Vim, and line 0 stands before the first line: `:0r` reads
+/// in above it, and other commands take it as line 1. The range is
+/// `%` for the whole buffer, or one or two addresses separated by a comma:
+/// a line number, `.` for the cursor line, `$` for the last line or `'m`
+/// for the line of mark `m`, each followed by any `+N` or `-N` offsets.
+pub fn parse<'a>(
+    cmd: &'a str,
+    doc: &Document,
+    cursor_row: usize,
+) -> Result<(Option<Lines>, &'a str), String> {
+    let last = doc.lines().len();
+    if let Some(rest) = cmd.strip_prefix('%') {
+        return Ok((Some((1, last)), rest));
+    }
+
+    let Some((start, rest)) = address(cmd, doc, cursor_row)? else {
+        return Ok((None, cmd));
+    };
+    let (end, rest) = match rest.strip_prefix(',') {
+        Some(after) => match address(after, doc, cursor_row)? {
+            Some(end) => end,
+            None => return Err(format!("Invalid range: {}", cmd)),
+        },
+        None => (start, rest),
+    };
+    if start > last || end > last {
+        return Err("Invalid range".to_string());
+    }
+    // a backwards range is turned around, as Vim offers to
+    Ok((Some((start.min(end), start.max(end))), rest))
+}
+
+/// Reads one address with its offsets, returning its line and what follows.
+fn address<'a>(
+    text: &'a str,
+    doc: &Document,
+    cursor_row: usize,
+) -> Result<Option<(usize, &'a str)>, String> {
+    let mut chars = text.char_indices();
+    let (line, mut rest) = match chars.next() {
+        Some((_, '.')) => (cursor_row as isize + 1, &text[1..]),
+        Some((_, '$')) => (doc.lines().len() as isize, &text[1..]),
+        Some((_, '\'')) => {
+            let Some((i, name)) = chars.next() else {
+                return Err("Invalid range: '".to_string());
+            };
+            match doc.mark(name) {
+                Some((row, _)) => (row as isize + 1, &text[i + name.len_utf8()..]),
+                None => return Err("Mark not set".to_string()),
+            }
+        }
+        Some((_, c)) if c.is_ascii_digit() => {
+            let (n, after) = number(text);
+            (n as isize, after)
+        }
+        // an address made only of offsets counts from the cursor line
+        Some((_, '+' | '-')) => (cursor_row as isize + 1, text),
+        _ => return Ok(None),
+    };
+
+    let mut line = line;
+    while let Some(sign @ ('+' | '-')) = rest.chars().next() {
+        let (n, after) = number(&rest[1..]);
+        let n = if after.len() == rest.len() - 1 { 1 } else { n };
+        line += if sign == '+' {
+            n as isize
+        } else {
+            -(n as isize)
+        };
+        rest = after;
+    }
+    if line < 0 {
+        return Err("Invalid range".to_string());
+    }
+    Ok(Some((line as usize, rest)))
+}
+
+/// Reads the digits at the start of `text`, 0 if there are none.
+fn number(text: &str) -> (usize, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    (text[..end].parse().unwrap_or(0), &text[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc() -> Document {
+        let mut doc = Document::from_text("a\nb\nc\nd\ne");
+        doc.set_mark('m', (3, 0));
+        doc
+    }
+
+    #[test]
+    fn addresses_and_offsets() {
+        let doc = doc();
+        assert_eq!(parse("%!sort", &doc, 1).unwrap(), (Some((1, 5)), "!sort"));
+        assert_eq!(parse(".,.+2!jq", &doc, 1).unwrap(), (Some((2, 4)), "!jq"));
+        assert_eq!(parse("2,$d", &doc, 0).unwrap(), (Some((2, 5)), "d"));
+        assert_eq!(parse("'m,-", &doc, 2).unwrap(), (Some((2, 4)), ""));
+        assert_eq!(parse("4,2", &doc, 0).unwrap(), (Some((2, 4)), ""));
+        assert_eq!(parse("make", &doc, 0).unwrap(), (None, "make"));
+    }
+
+    #[test]
+    fn line_zero_is_before_the_first_line() {
+        let doc = doc();
+        assert_eq!(
+            parse("0r !date", &doc, 2).unwrap(),
+            (Some((0, 0)), "r !date")
+        );
+        assert_eq!(parse("0,2!sort", &doc, 2).unwrap(), (Some((0, 2)), "!sort"));
+        assert_eq!(parse(".-1", &doc, 0).unwrap(), (Some((0, 0)), ""));
+    }
+
+    #[test]
+    fn ranges_outside_the_buffer_are_errors() {
+        let doc = doc();
+        assert!(parse("7!sort", &doc, 0).is_err());
+        assert!(parse(".-3", &doc, 1).is_err());
+        assert!(parse("'x!sort", &doc, 1).is_err());
+        assert!(parse("1,!sort", &doc, 1).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+/// What a shell command wrote and how it exited.
+pub struct Output {
+    pub success: bool,
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs `cmd` with `sh -c`, feeding it `input` on stdin if there is some.
+/// Without input stdin is closed, so commands waiting on it end at once.
+pub fn run(cmd: &str, input: Option<&str>) -> io::Result<Output> {
+    pipe(Command::new("sh").args(["-c", cmd]), input)
+}
+
+/// Runs `command` as `run` does, for programs started without a shell.
+pub fn pipe(command: &mut Command, input: Option<&str>) -> io::Result<Output> {
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // written from another thread, so a command filling its stdout pipe
+    // before reading all of stdin cannot block both sides
+    let writer = match (child.stdin.take(), input) {
+        (Some(mut stdin), Some(input)) => {
+            let input = input.to_string();
+            Some(thread::spawn(move || stdin.write_all(input.as_bytes())))
+        }
+        _ => None,
+    };
+    let output = child.wait_with_output()?;
+    if let Some(writer) = writer {
+        // a command may exit without reading everything, which is fine
+        let _ = writer.join();
+    }
+
+    Ok(Output {
+        success: output.status.success(),
+        code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_is_piped_through() {
+        let output = run("sort", Some("b\na\n")).unwrap();
+        assert!(output.success);
+        assert_eq!(output.stdout, "a\nb\n");
+
+        let output = run("echo oops >&2; exit 3", None).unwrap();
+        assert_eq!((output.success, output.code), (false, Some(3)));
+        assert_eq!(output.stderr, "oops\n");
+    }
+}
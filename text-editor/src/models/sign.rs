@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+
+/// A `Sign` is a short marker drawn in the gutter next to a line, styled by
+/// the theme's `scope`.
+#[derive(Clone)]
+pub struct Sign {
+    pub text: String,
+    pub group: String,
+    pub priority: i32,
+    pub scope: String,
+}
+
+/// A `SignColumn` holds the signs placed on a document's lines, grouped by
+/// the feature that placed them.
+#[derive(Default)]
+pub struct SignColumn {
+    signs: BTreeMap<usize, Vec<Sign>>,
+}
+
+impl SignColumn {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn place(&mut self, row: usize, sign: Sign) {
+        let signs = self.signs.entry(row).or_default();
+        signs.retain(|s| s.group != sign.group);
+        signs.push(sign);
+        signs.sort_by_key(|s| -s.priority);
+    }
+
+    pub fn clear_group(&mut self, group: &str) {
+        for signs in self.signs.values_mut() {
+            signs.retain(|s| s.group != group);
+        }
+        self.signs.retain(|_, signs| !signs.is_empty());
+    }
+
+    /// Moves the signs on `from` and every later row by `delta` rows, keeping
+    /// them attached to their lines after lines are inserted or removed.
+    /// Signs on removed lines end up on the line before `from`.
+    pub fn shift(&mut self, from: usize, delta: isize) {
+        let moved = self.signs.split_off(&from);
+        for (row, signs) in moved {
+            let row = row.saturating_add_signed(delta).max(from.saturating_sub(1));
+            for sign in signs {
+                self.place(row, sign);
+            }
+        }
+    }
+
+    /// Returns the highest-priority sign on `row`.
+    pub fn get(&self, row: usize) -> Option<&Sign> {
+        self.signs.get(&row).and_then(|signs| signs.first())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signs.is_empty()
+    }
+}
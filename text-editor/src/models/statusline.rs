@@ -0,0 +1,114 @@
+use crate::models::editor::Editor;
+
+/// The segments shown when `statusline` is not set.
+pub const DEFAULT_STATUSLINE: &str =
+    "mode,path,dirty,readonly,=,pending,recording,selection,filetype,encoding,lineending,position";
+
+/// A `Segment` is one piece of the status line. `Align` is not drawn itself;
+/// the segments after it are pushed to the right edge.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Segment {
+    Mode,
+    Path,
+    Dirty,
+    ReadOnly,
+    FileType,
+    Encoding,
+    LineEnding,
+    Position,
+    Selection,
+    Recording,
+    Pending,
+    Align,
+}
+
+impl Segment {
+    /// Parses a comma-separated list of segment names such as
+    /// `mode,path,=,position`.
+    pub fn parse_list(spec: &str) -> Result<Vec<Self>, String> {
+        spec.split(',')
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Ok(match name {
+                    "mode" => Segment::Mode,
+                    "path" => Segment::Path,
+                    "dirty" => Segment::Dirty,
+                    "readonly" => Segment::ReadOnly,
+                    "filetype" => Segment::FileType,
+                    "encoding" => Segment::Encoding,
+                    "lineending" => Segment::LineEnding,
+                    "position" => Segment::Position,
+                    "selection" => Segment::Selection,
+                    "recording" => Segment::Recording,
+                    "pending" => Segment::Pending,
+                    "=" => Segment::Align,
+                    _ => return Err(format!("Unknown status line segment: {}", name)),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the text of the segment, or `None` if it has nothing to show.
+    pub fn text(&self, editor: &Editor) -> Option<String> {
+        let doc = editor.doc();
+        let (row, col) = editor.cursor();
+        match self {
+            Segment::Mode => Some(editor.mode().as_str().to_string()),
+            Segment::Path => Some(match doc.full_path() {
+                Some(path) => editor.relative_path(path),
+                None => doc.file_name().to_string(),
+            }),
+            Segment::Dirty => doc.dirty().then(|| "[+]".to_string()),
+            Segment::ReadOnly => doc.readonly().then(|| "[RO]".to_string()),
+            Segment::FileType => Some(doc.file_type().unwrap_or("text").to_string()),
+            Segment::Encoding => Some(doc.encoding().to_string()),
+            Segment::LineEnding => Some(doc.line_ending().name().to_string()),
+            Segment::Position => {
+                let lines = doc.lines().len();
+                let percent = match row {
+                    0 => "Top".to_string(),
+                    _ if row + 1 == lines => "Bot".to_string(),
+                    _ => format!("{}%", (row + 1) * 100 / lines),
+                };
+                Some(format!("{}:{} {}", row + 1, col + 1, percent))
+            }
+            Segment::Selection => editor.selection().map(|(start, end)| {
+                let chars = doc.text_range(start, end).chars().count();
+                // a selection ending on a line break ends at the start of
+                // the next line without selecting any of it
+                let last = if end.1 == 0 && end.0 > start.0 {
+                    end.0 - 1
+                } else {
+                    end.0
+                };
+                let rows = last - start.0 + 1;
+                if rows > 1 {
+                    format!("{} lines, {} chars", rows, chars)
+                } else {
+                    format!("{} chars", chars)
+                }
+            }),
+            Segment::Recording => editor.recording().map(|reg| format!("recording @{}", reg)),
+            Segment::Pending => Some(editor.pending().to_string()).filter(|p| !p.is_empty()),
+            Segment::Align => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Headless;
+
+    #[test]
+    fn selection_counts_the_rows_it_touches() {
+        let mut h = Headless::with_text("ab\ncd\nef", 40, 8);
+        h.keys("vdd").unwrap();
+        assert_eq!(Segment::Selection.text(h.editor()).unwrap(), "3 chars");
+        h.keys("s").unwrap();
+        assert_eq!(
+            Segment::Selection.text(h.editor()).unwrap(),
+            "2 lines, 6 chars"
+        );
+    }
+}
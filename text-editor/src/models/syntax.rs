@@ -0,0 +1,207 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::models::options::config_dir;
+
+const BUILTIN_GRAMMARS: [&str; 7] = [
+    include_str!("../../grammars/rust.toml"),
+    include_str!("../../grammars/markdown.toml"),
+    include_str!("../../grammars/toml.toml"),
+    include_str!("../../grammars/json.toml"),
+    include_str!("../../grammars/python.toml"),
+    include_str!("../../grammars/shell.toml"),
+    include_str!("../../grammars/diff.toml"),
+];
+
+/// A `Grammar` describes how to highlight one language: which files it
+/// applies to and an ordered list of rules mapping text to scopes.
+pub struct Grammar {
+    pub name: String,
+    extensions: Vec<String>,
+    file_names: Vec<String>,
+    shebangs: Vec<String>,
+    pub rules: Vec<Rule>,
+}
+
+/// A `Rule` either matches a single token within a line, or a region that
+/// runs from `begin` to `end` and may span several lines.
+pub struct Rule {
+    pub scope: String,
+    pub kind: RuleKind,
+}
+
+pub enum RuleKind {
+    Match(Regex),
+    Region {
+        begin: Regex,
+        end: Regex,
+        skip: Option<Regex>,
+    },
+}
+
+#[derive(Deserialize)]
+struct GrammarFile {
+    name: String,
+    #[serde(default)]
+    extensions: Vec<String>,
+    #[serde(default)]
+    file_names: Vec<String>,
+    #[serde(default)]
+    shebangs: Vec<String>,
+    #[serde(default)]
+    rules: Vec<RuleFile>,
+}
+
+#[derive(Deserialize)]
+struct RuleFile {
+    scope: String,
+    #[serde(rename = "match")]
+    pattern: Option<String>,
+    begin: Option<String>,
+    end: Option<String>,
+    skip: Option<String>,
+}
+
+impl Grammar {
+    /// Parses a grammar from its TOML source.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let file: GrammarFile = toml::from_str(source).map_err(|e| e.to_string())?;
+
+        let regex =
+            |pattern: &str| Regex::new(pattern).map_err(|e| format!("{}: {}", file.name, e));
+
+        let mut rules = Vec::new();
+        for rule in &file.rules {
+            let kind = match (&rule.pattern, &rule.begin, &rule.end) {
+                (Some(pattern), None, None) => RuleKind::Match(regex(pattern)?),
+                (None, Some(begin), Some(end)) => RuleKind::Region {
+                    begin: regex(begin)?,
+                    end: regex(end)?,
+                    skip: rule.skip.as_deref().map(regex).transpose()?,
+                },
+                _ => {
+                    return Err(format!(
+                        "{}: rule for `{}` needs either `match` or `begin` and `end`",
+                        file.name, rule.scope
+                    ));
+                }
+            };
+            rules.push(Rule {
+                scope: rule.scope.clone(),
+                kind,
+            });
+        }
+
+        Ok(Self {
+            name: file.name,
+            extensions: file.extensions,
+            file_names: file.file_names,
+            shebangs: file.shebangs,
+            rules,
+        })
+    }
+
+    fn matches_path(&self, path: &Path) -> bool {
+        let name = path.file_name().and_then(|n| n.to_str());
+        let ext = path.extension().and_then(|e| e.to_str());
+        name.is_some_and(|n| self.file_names.iter().any(|f| f == n))
+            || ext.is_some_and(|e| self.extensions.iter().any(|x| x == e))
+    }
+
+    fn matches_shebang(&self, first_line: &str) -> bool {
+        let Some(command) = first_line.strip_prefix("#!") else {
+            return false;
+        };
+
+        let mut words = command.split_whitespace();
+        let mut interpreter = words.next().unwrap_or_default();
+        if interpreter.ends_with("/env") {
+            interpreter = words.find(|w| !w.starts_with('-')).unwrap_or_default();
+        }
+        let interpreter = interpreter.rsplit('/').next().unwrap_or_default();
+        let unversioned = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        self.shebangs
+            .iter()
+            .any(|s| s == interpreter || s == unversioned)
+    }
+}
+
+/// The `SyntaxSet` holds every grammar known to the editor: the built-in
+/// ones plus any loaded from the user's grammar directory.
+pub struct SyntaxSet {
+    grammars: Vec<Rc<Grammar>>,
+}
+
+impl SyntaxSet {
+    /// Loads the built-in grammars and those found in `grammar_dir()`,
+    /// returning any grammar files that failed to load alongside the set.
+    pub fn load() -> (Self, Vec<String>) {
+        let mut grammars = Vec::new();
+        let mut errors = Vec::new();
+
+        if let Some(dir) = Self::grammar_dir() {
+            match Self::load_dir(&dir) {
+                Ok(loaded) => {
+                    for result in loaded {
+                        match result {
+                            Ok(grammar) => grammars.push(Rc::new(grammar)),
+                            Err(e) => errors.push(e),
+                        }
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => errors.push(format!("{}: {}", dir.display(), e)),
+            }
+        }
+
+        // user grammars come first so they can override the built-in ones
+        for source in BUILTIN_GRAMMARS {
+            let grammar = Grammar::parse(source).expect("built-in grammar is valid");
+            grammars.push(Rc::new(grammar));
+        }
+
+        (Self { grammars }, errors)
+    }
+
+    /// Returns the directory user grammars are loaded from.
+    pub fn grammar_dir() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("grammars"))
+    }
+
+    fn load_dir(dir: &Path) -> io::Result<Vec<Result<Grammar, String>>> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+            .collect();
+        paths.sort();
+
+        Ok(paths
+            .iter()
+            .map(|path| {
+                fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|source| Grammar::parse(&source))
+                    .map_err(|e| format!("{}: {}", path.display(), e))
+            })
+            .collect())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<Grammar>> {
+        self.grammars.iter().find(|g| g.name == name).cloned()
+    }
+
+    /// Picks a grammar from the file's path, falling back to the shebang on
+    /// its first line.
+    pub fn detect(&self, path: Option<&Path>, first_line: &str) -> Option<Rc<Grammar>> {
+        path.and_then(|p| self.grammars.iter().find(|g| g.matches_path(p)))
+            .or_else(|| self.grammars.iter().find(|g| g.matches_shebang(first_line)))
+            .cloned()
+    }
+}
@@ -0,0 +1,315 @@
+use std::ops::Range;
+
+use tree_sitter::{
+    InputEdit, Language, Node, Parser, Point, Query, QueryCursor, StreamingIterator, Tree,
+};
+
+use crate::models::{document::Change, gap_buffer::GapBuffer, highlighter::HighlightSpan};
+
+type Pos = (usize, usize); // (row, col)
+
+/// A `SyntaxTree` is a tree-sitter parse of a document, kept up to date from
+/// the changes the document applies. It provides highlighting and the
+/// structural navigation built on top of the tree.
+pub struct SyntaxTree {
+    parser: Parser,
+    tree: Tree,
+    text: String,
+    highlights: Query,
+    textobjects: Query,
+    lines: Vec<Vec<HighlightSpan>>,
+}
+
+impl SyntaxTree {
+    /// Parses `lines` with the tree-sitter grammar for the language `name`,
+    /// if there is one.
+    pub fn new(name: &str, lines: &[GapBuffer]) -> Option<Self> {
+        let (language, highlights, textobjects): (Language, &str, &str) = match name {
+            "rust" => (
+                tree_sitter_rust::LANGUAGE.into(),
+                tree_sitter_rust::HIGHLIGHTS_QUERY,
+                include_str!("../../queries/rust/textobjects.scm"),
+            ),
+            "python" => (
+                tree_sitter_python::LANGUAGE.into(),
+                tree_sitter_python::HIGHLIGHTS_QUERY,
+                include_str!("../../queries/python/textobjects.scm"),
+            ),
+            "shell" => (
+                tree_sitter_bash::LANGUAGE.into(),
+                tree_sitter_bash::HIGHLIGHT_QUERY,
+                include_str!("../../queries/shell/textobjects.scm"),
+            ),
+            "json" => (
+                tree_sitter_json::LANGUAGE.into(),
+                tree_sitter_json::HIGHLIGHTS_QUERY,
+                include_str!("../../queries/json/textobjects.scm"),
+            ),
+            _ => return None,
+        };
+
+        let mut parser = Parser::new();
+        parser.set_language(&language).ok()?;
+        let text = join_lines(lines);
+        let tree = parser.parse(&text, None)?;
+
+        let mut syntax_tree = Self {
+            parser,
+            tree,
+            text,
+            highlights: Query::new(&language, highlights).ok()?,
+            textobjects: Query::new(&language, textobjects).ok()?,
+            lines: vec![Vec::new(); lines.len()],
+        };
+        syntax_tree.highlight_rows(0..lines.len());
+        Some(syntax_tree)
+    }
+
+    /// Applies `change` to the tree, reparses incrementally and re-highlights
+    /// the rows the edit touched or whose structure changed.
+    pub fn edit(&mut self, change: &Change, lines: &[GapBuffer]) {
+        let start_byte = byte_offset(lines, change.start);
+        let start_position = point(lines, change.start);
+        let old_end_position = end_point(start_position, &change.removed);
+        let new_end_position = end_point(start_position, &change.inserted);
+
+        self.tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte: start_byte + change.removed.len(),
+            new_end_byte: start_byte + change.inserted.len(),
+            start_position,
+            old_end_position,
+            new_end_position,
+        });
+
+        self.text = join_lines(lines);
+        let Some(tree) = self.parser.parse(&self.text, Some(&self.tree)) else {
+            return;
+        };
+
+        let row = change.start.0;
+        let removed = change.removed.matches('\n').count() + 1;
+        let inserted = change.inserted.matches('\n').count() + 1;
+        self.lines
+            .splice(row..row + removed, vec![Vec::new(); inserted]);
+
+        let changed: Vec<_> = self.tree.changed_ranges(&tree).collect();
+        self.tree = tree;
+
+        self.highlight_rows(row..row + inserted);
+        for range in changed {
+            self.highlight_rows(range.start_point.row..range.end_point.row + 1);
+        }
+    }
+
+    pub fn spans(&self, row: usize) -> &[HighlightSpan] {
+        self.lines.get(row).map(|l| &l[..]).unwrap_or(&[])
+    }
+
+    fn highlight_rows(&mut self, rows: Range<usize>) {
+        let rows = rows.start.min(self.lines.len())..rows.end.min(self.lines.len());
+        if rows.is_empty() {
+            return;
+        }
+
+        let line_texts: Vec<&str> = self.text.split('\n').collect();
+        // per char: the scope painted there and the size of the node it came
+        // from, so inner nodes win over the nodes containing them
+        let mut painted: Vec<Vec<Option<(usize, usize)>>> = rows
+            .clone()
+            .map(|row| vec![None; line_texts[row].chars().count()])
+            .collect();
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_point_range(Point::new(rows.start, 0)..Point::new(rows.end, 0));
+        let names = self.highlights.capture_names();
+        let mut captures = cursor.captures(
+            &self.highlights,
+            self.tree.root_node(),
+            self.text.as_bytes(),
+        );
+
+        while let Some((m, i)) = captures.next() {
+            let capture = m.captures[*i];
+            let node = capture.node;
+            let size = node.byte_range().len();
+            let start = node.start_position();
+            let end = node.end_position();
+
+            for row in start.row.max(rows.start)..=end.row.min(rows.end - 1) {
+                let line = line_texts[row];
+                let from = if row == start.row {
+                    char_col(line, start.column)
+                } else {
+                    0
+                };
+                let to = if row == end.row {
+                    char_col(line, end.column)
+                } else {
+                    line.chars().count()
+                };
+                for cell in &mut painted[row - rows.start][from..to] {
+                    if cell.is_none_or(|(_, s)| s > size) {
+                        *cell = Some((capture.index as usize, size));
+                    }
+                }
+            }
+        }
+
+        for (i, cells) in painted.into_iter().enumerate() {
+            let mut spans: Vec<HighlightSpan> = Vec::new();
+            for (col, cell) in cells.into_iter().enumerate() {
+                let Some((index, _)) = cell else {
+                    continue;
+                };
+                let scope = names[index];
+                match spans.last_mut() {
+                    Some(last) if last.range.end == col && last.scope == scope => {
+                        last.range.end += 1;
+                    }
+                    _ => spans.push(HighlightSpan {
+                        range: col..col + 1,
+                        scope: scope.to_string(),
+                    }),
+                }
+            }
+            self.lines[rows.start + i] = spans;
+        }
+    }
+
+    /// Returns the range of the smallest `{object}.inner` or `{object}.outer`
+    /// capture around `pos`, with `object` one of `function`, `class` or
+    /// `parameter`.
+    pub fn text_object(&self, object: &str, inner: bool, pos: Pos) -> Option<(Pos, Pos)> {
+        let byte = self.byte_at(pos);
+        let wanted = [
+            format!("{}.{}", object, if inner { "inner" } else { "outer" }),
+            format!("{}.inner", object),
+        ];
+        let names = self.textobjects.capture_names();
+
+        for name in &wanted {
+            let mut best: Option<Node> = None;
+            let mut cursor = QueryCursor::new();
+            cursor.set_byte_range(byte..byte + 1);
+            let mut captures = cursor.captures(
+                &self.textobjects,
+                self.tree.root_node(),
+                self.text.as_bytes(),
+            );
+            while let Some((m, i)) = captures.next() {
+                let capture = m.captures[*i];
+                let node = capture.node;
+                if names[capture.index as usize] == name
+                    && node.byte_range().contains(&byte)
+                    && best.is_none_or(|b| node.byte_range().len() < b.byte_range().len())
+                {
+                    best = Some(node);
+                }
+            }
+            if let Some(node) = best {
+                return Some(self.node_range(node));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the range of the smallest named node strictly containing the
+    /// range `start..end`.
+    pub fn expand(&self, start: Pos, end: Pos) -> Option<(Pos, Pos)> {
+        let (start, end) = (self.byte_at(start), self.byte_at(end));
+        let mut node = self
+            .tree
+            .root_node()
+            .named_descendant_for_byte_range(start, end)?;
+
+        while node.start_byte() == start && node.end_byte() == end {
+            node = node.parent()?;
+        }
+
+        Some(self.node_range(node))
+    }
+
+    /// Returns the start of the next (or previous) function after (or
+    /// before) `pos`.
+    pub fn function_start(&self, pos: Pos, forward: bool) -> Option<Pos> {
+        let byte = self.byte_at(pos);
+        let names = self.textobjects.capture_names();
+        let mut cursor = QueryCursor::new();
+        let mut captures = cursor.captures(
+            &self.textobjects,
+            self.tree.root_node(),
+            self.text.as_bytes(),
+        );
+
+        let mut starts = Vec::new();
+        while let Some((m, i)) = captures.next() {
+            let capture = m.captures[*i];
+            if names[capture.index as usize] == "function.outer" {
+                starts.push(capture.node.start_byte());
+            }
+        }
+
+        let target = if forward {
+            starts.into_iter().filter(|&s| s > byte).min()
+        } else {
+            starts.into_iter().filter(|&s| s < byte).max()
+        }?;
+
+        Some(self.pos_at(target))
+    }
+
+    fn node_range(&self, node: Node) -> (Pos, Pos) {
+        (self.pos_at(node.start_byte()), self.pos_at(node.end_byte()))
+    }
+
+    fn byte_at(&self, pos: Pos) -> usize {
+        let mut byte = 0;
+        for (row, line) in self.text.split('\n').enumerate() {
+            if row == pos.0 {
+                return byte + line.chars().take(pos.1).map(char::len_utf8).sum::<usize>();
+            }
+            byte += line.len() + 1;
+        }
+        self.text.len()
+    }
+
+    fn pos_at(&self, byte: usize) -> Pos {
+        let before = &self.text[..byte.min(self.text.len())];
+        let row = before.matches('\n').count();
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (row, before[line_start..].chars().count())
+    }
+}
+
+fn join_lines(lines: &[GapBuffer]) -> String {
+    let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    lines.join("\n")
+}
+
+fn byte_offset(lines: &[GapBuffer], pos: Pos) -> usize {
+    let before: usize = lines[..pos.0]
+        .iter()
+        .map(|l| l.chars().map(char::len_utf8).sum::<usize>() + 1)
+        .sum();
+    before + point(lines, pos).column
+}
+
+fn point(lines: &[GapBuffer], pos: Pos) -> Point {
+    let column = lines[pos.0].chars().take(pos.1).map(char::len_utf8).sum();
+    Point::new(pos.0, column)
+}
+
+fn end_point(start: Point, text: &str) -> Point {
+    match text.rsplit_once('\n') {
+        Some((before, last)) => {
+            Point::new(start.row + before.matches('\n').count() + 1, last.len())
+        }
+        None => Point::new(start.row, start.column + text.len()),
+    }
+}
+
+fn char_col(line: &str, byte: usize) -> usize {
+    line[..byte.min(line.len())].chars().count()
+}
@@ -0,0 +1,98 @@
+use tui::layout::{Constraint, Direction, Layout, Rect};
+
+use crate::models::window::Window;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    Horizontal, // windows stacked top to bottom
+    Vertical,   // windows side by side
+}
+
+/// A `Tab` holds its own window layout.
+pub struct Tab {
+    windows: Vec<Window>,
+    direction: SplitDirection,
+    active: usize,
+}
+
+impl Tab {
+    pub fn new(window: Window) -> Self {
+        Self {
+            windows: vec![window],
+            direction: SplitDirection::Horizontal,
+            active: 0,
+        }
+    }
+
+    pub fn split(&mut self, window: Window, direction: SplitDirection) {
+        if self.windows.len() == 1 {
+            self.direction = direction;
+        }
+        self.windows.insert(self.active + 1, window);
+        self.active += 1;
+    }
+
+    /// Closes the active window, returning `false` if it was the last one.
+    pub fn close_window(&mut self) -> bool {
+        if self.windows.len() == 1 {
+            return false;
+        }
+        self.windows.remove(self.active);
+        if self.active >= self.windows.len() {
+            self.active = self.windows.len() - 1;
+        }
+        true
+    }
+
+    pub fn next_window(&mut self) {
+        self.active = (self.active + 1) % self.windows.len();
+    }
+
+    pub fn set_active(&mut self, index: usize) {
+        self.active = index.min(self.windows.len() - 1);
+    }
+
+    pub fn window_rects(&self, area: Rect) -> Vec<Rect> {
+        let n = self.windows.len() as u32;
+        let direction = match self.direction {
+            SplitDirection::Horizontal => Direction::Vertical,
+            SplitDirection::Vertical => Direction::Horizontal,
+        };
+        Layout::default()
+            .direction(direction)
+            .constraints(vec![Constraint::Ratio(1, n); self.windows.len()])
+            .split(area)
+    }
+
+    pub fn active_rect(&self, area: Rect) -> Rect {
+        self.window_rects(area)[self.active]
+    }
+
+    pub fn windows(&self) -> &Vec<Window> {
+        &self.windows
+    }
+
+    /// Returns the index of the window whose rect contains the screen cell
+    /// `(x, y)`.
+    pub fn window_at(&self, area: Rect, x: u16, y: u16) -> Option<usize> {
+        self.window_rects(area)
+            .iter()
+            .position(|r| (r.x..r.x + r.width).contains(&x) && (r.y..r.y + r.height).contains(&y))
+    }
+
+    pub fn windows_mut(&mut self) -> &mut [Window] {
+        &mut self.windows
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.windows[self.active]
+    }
+
+    pub fn window_mut(&mut self) -> &mut Window {
+        &mut self.windows[self.active]
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+}
@@ -0,0 +1,222 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use serde::Deserialize;
+use tui::style::{Color, Modifier, Style};
+
+use crate::models::options::config_dir;
+
+const BUILTIN_THEMES: [(&str, &str); 4] = [
+    ("default", include_str!("../../themes/default.toml")),
+    ("gruvbox", include_str!("../../themes/gruvbox.toml")),
+    (
+        "solarized-light",
+        include_str!("../../themes/solarized-light.toml"),
+    ),
+    ("mono", include_str!("../../themes/mono.toml")),
+];
+
+/// A `Theme` maps named scopes to styles. UI scopes such as `statusline`
+/// or `selection` style the editor's chrome, syntax scopes such as
+/// `keyword` style highlighted text.
+pub struct Theme {
+    pub name: String,
+    ui: HashMap<String, Style>,
+    syntax: HashMap<String, Style>,
+}
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    #[serde(default)]
+    palette: HashMap<String, String>,
+    #[serde(default)]
+    ui: HashMap<String, StyleFile>,
+    #[serde(default)]
+    syntax: HashMap<String, StyleFile>,
+}
+
+#[derive(Deserialize)]
+struct StyleFile {
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+impl Theme {
+    /// Loads the theme `name` from the user's theme directory, falling back
+    /// to the built-in themes. Without `truecolor`, RGB colors are mapped to
+    /// the nearest color of the 256-color palette.
+    pub fn load(name: &str, truecolor: bool) -> Result<Self, String> {
+        let user_file = Self::theme_dir().map(|dir| dir.join(format!("{}.toml", name)));
+        let source = match user_file.filter(|p| p.exists()) {
+            Some(path) => {
+                fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            None => BUILTIN_THEMES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, s)| s.to_string())
+                .ok_or_else(|| format!("Cannot find color scheme '{}'", name))?,
+        };
+
+        Self::parse(name, &source, truecolor)
+    }
+
+    /// Parses a theme from its TOML source.
+    pub fn parse(name: &str, source: &str, truecolor: bool) -> Result<Self, String> {
+        let file: ThemeFile = toml::from_str(source).map_err(|e| format!("{}: {}", name, e))?;
+
+        let resolve =
+            |styles: &HashMap<String, StyleFile>| -> Result<HashMap<String, Style>, String> {
+                styles
+                    .iter()
+                    .map(|(scope, spec)| {
+                        let style = spec
+                            .to_style(&file.palette, truecolor)
+                            .map_err(|e| format!("{}: {}: {}", name, scope, e))?;
+                        Ok((scope.clone(), style))
+                    })
+                    .collect()
+            };
+
+        Ok(Self {
+            name: name.to_string(),
+            ui: resolve(&file.ui)?,
+            syntax: resolve(&file.syntax)?,
+        })
+    }
+
+    /// Returns the directory user themes are loaded from.
+    pub fn theme_dir() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("themes"))
+    }
+
+    pub fn ui(&self, scope: &str) -> Style {
+        Self::lookup(&self.ui, scope)
+    }
+
+    pub fn syntax(&self, scope: &str) -> Style {
+        Self::lookup(&self.syntax, scope)
+    }
+
+    /// Looks up `scope`, falling back from dotted scopes such as
+    /// `function.method` to their parent.
+    fn lookup(styles: &HashMap<String, Style>, scope: &str) -> Style {
+        match styles.get(scope) {
+            Some(style) => *style,
+            None => match scope.rsplit_once('.') {
+                Some((parent, _)) => Self::lookup(styles, parent),
+                None => Style::default(),
+            },
+        }
+    }
+}
+
+impl StyleFile {
+    fn to_style(
+        &self,
+        palette: &HashMap<String, String>,
+        truecolor: bool,
+    ) -> Result<Style, String> {
+        let color = |name: &str| {
+            let name = palette.get(name).map(String::as_str).unwrap_or(name);
+            parse_color(name, truecolor)
+        };
+
+        let mut style = Style::default();
+        if let Some(fg) = &self.fg {
+            style = style.fg(color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(color(bg)?);
+        }
+        for modifier in &self.modifiers {
+            style = style.add_modifier(match modifier.as_str() {
+                "bold" => Modifier::BOLD,
+                "dim" => Modifier::DIM,
+                "italic" => Modifier::ITALIC,
+                "underlined" => Modifier::UNDERLINED,
+                "reversed" => Modifier::REVERSED,
+                "crossed_out" => Modifier::CROSSED_OUT,
+                _ => return Err(format!("unknown modifier `{}`", modifier)),
+            });
+        }
+
+        Ok(style)
+    }
+}
+
+/// Parses a color name, `#rrggbb` or a 256-color palette index.
+fn parse_color(name: &str, truecolor: bool) -> Result<Color, String> {
+    let color = match name.to_ascii_lowercase().as_str() {
+        "reset" | "none" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.starts_with('#') && hex.len() == 7 => {
+            let channel = |i: usize| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .map_err(|_| format!("invalid color `{}`", name))
+            };
+            let (r, g, b) = (channel(1)?, channel(3)?, channel(5)?);
+            if truecolor {
+                Color::Rgb(r, g, b)
+            } else {
+                Color::Indexed(nearest_256(r, g, b))
+            }
+        }
+        index => Color::Indexed(
+            index
+                .parse()
+                .map_err(|_| format!("invalid color `{}`", name))?,
+        ),
+    };
+
+    Ok(color)
+}
+
+/// Maps an RGB color to the closest entry of the xterm 256-color palette's
+/// color cube or grayscale ramp.
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = ((avg.saturating_sub(8)) / 10).min(23) as u8;
+    let gray = 8 + gray_index * 10;
+
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(cr, r) + d(cg, g) + d(cb, b)
+    };
+
+    if distance((gray, gray, gray)) < distance(cube) {
+        232 + gray_index
+    } else {
+        16 + (36 * ri + 6 * gi + bi) as u8
+    }
+}
+
+/// Returns whether the terminal advertises 24-bit color support.
+pub fn terminal_truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|v| v == "truecolor" || v == "24bit")
+}
@@ -0,0 +1,31 @@
+/// A `Window` is a view onto one of the editor's buffers.
+pub struct Window {
+    buffer: usize,
+    pub cursor: (usize, usize), // (row, col)
+    pub pref_col: usize,
+    pub anchor: (usize, usize), // other end of a visual selection
+    pub top: (usize, usize),    // first row shown, and its screen rows scrolled off
+    pub hscroll: usize,         // first column shown without wrap
+}
+
+impl Window {
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            buffer,
+            cursor: (0, 0),
+            pref_col: 0,
+            anchor: (0, 0),
+            top: (0, 0),
+            hscroll: 0,
+        }
+    }
+
+    pub fn buffer(&self) -> usize {
+        self.buffer
+    }
+
+    /// Renumbers the buffer shown, for when a buffer before it was closed.
+    pub fn set_buffer(&mut self, buffer: usize) {
+        self.buffer = buffer;
+    }
+}
@@ -0,0 +1,97 @@
+use crate::models::options::Options;
+
+/// A `ScreenRow` is the part of a line drawn on one row of the screen: the
+/// chars `start..end`, drawn after `indent` columns of break indent and
+/// `showbreak` marker on continuation rows.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ScreenRow {
+    pub start: usize,
+    pub end: usize,
+    pub indent: usize,
+}
+
+/// Splits a line into the rows it is drawn on in a text area `width`
+/// columns wide. Without `wrap` the whole line is one row and is scrolled
+/// horizontally instead. Every line, even an empty one, has at least one
+/// row, and a line whose last row is full gets an empty row after it so
+/// the column just past its end has a cell of its own.
+pub fn screen_rows(line: &[char], width: usize, options: &Options) -> Vec<ScreenRow> {
+    let width = width.max(1);
+    if !options.wrap || line.len() < width {
+        return vec![ScreenRow {
+            start: 0,
+            end: line.len(),
+            indent: 0,
+        }];
+    }
+
+    let indent = continuation_indent(line, options).min(width - 1);
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < line.len() {
+        let indent = if rows.is_empty() { 0 } else { indent };
+        let mut end = (start + width - indent).min(line.len());
+        if options.linebreak
+            && end < line.len()
+            && !line[end].is_whitespace()
+            && let Some(blank) = line[start..end].iter().rposition(|c| c.is_whitespace())
+        {
+            // break after the last blank so words stay whole
+            end = start + blank + 1;
+        }
+        rows.push(ScreenRow { start, end, indent });
+        start = end;
+    }
+
+    let last = rows[rows.len() - 1];
+    if last.indent + last.end - last.start == width {
+        rows.push(ScreenRow {
+            start: line.len(),
+            end: line.len(),
+            indent,
+        });
+    }
+
+    rows
+}
+
+/// Returns the width of what is drawn before the text of continuation rows.
+fn continuation_indent(line: &[char], options: &Options) -> usize {
+    let showbreak = options.showbreak.chars().count();
+    if options.breakindent {
+        line.iter().take_while(|c| c.is_whitespace()).count() + showbreak
+    } else {
+        showbreak
+    }
+}
+
+/// Returns the index of the row of `rows` that column `col` is drawn on.
+/// The column just past the end of the line belongs to the last row.
+pub fn row_of(rows: &[ScreenRow], col: usize) -> usize {
+    rows.iter()
+        .position(|r| col < r.end)
+        .unwrap_or(rows.len() - 1)
+}
+
+/// Returns the first column to show of an unwrapped line so that column
+/// `col` stays `sidescrolloff` columns away from the edges of a text area
+/// `width` wide, scrolling by at least `sidescroll` columns at a time, or
+/// recentering when `sidescroll` is zero.
+pub fn side_scroll(hscroll: usize, col: usize, width: usize, options: &Options) -> usize {
+    let width = width.max(1);
+    let off = options.sidescrolloff.min((width - 1) / 2);
+    let wanted = if col < hscroll + off {
+        col.saturating_sub(off)
+    } else if col + off >= hscroll + width {
+        col + off + 1 - width
+    } else {
+        return hscroll;
+    };
+
+    let scrolled = match options.sidescroll {
+        0 => col.saturating_sub(width / 2),
+        step if wanted > hscroll => wanted.max(hscroll + step),
+        step => wanted.min(hscroll.saturating_sub(step)),
+    };
+    scrolled.clamp((col + 1).saturating_sub(width), col)
+}
@@ -0,0 +1,718 @@
+use crate::models::{
+    completion::Completion,
+    document::Document,
+    editor::{Editor, Mode},
+    explorer::Explorer,
+    finder::Finder,
+    popup::Popup,
+    quickfix::Quickfix,
+    statusline::Segment,
+};
+
+use std::path::PathBuf;
+
+use tui::{
+    Frame,
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+
+/// How many completion candidates are shown at a time.
+const COMPLETION_ROWS: usize = 10;
+/// How wide the file finder has to be to show a preview beside the files.
+const FINDER_PREVIEW_WIDTH: u16 = 40;
+/// How wide the file explorer sidebar is, at most half the screen.
+const EXPLORER_WIDTH: u16 = 30;
+/// How many quickfix entries the panel opened by `:copen` shows at a time.
+const QUICKFIX_ROWS: u16 = 10;
+
+/// Draws the whole editor onto `f`, returning the editor area that key,
+/// mouse and paste events are handled in.
+pub fn draw<B: Backend>(f: &mut Frame<B>, editor: &Editor) -> Rect {
+    let chunks = screen_layout(f.size(), editor);
+
+    if chunks[0].height > 0 {
+        draw_tab_line(f, &chunks[0], editor);
+    }
+    draw_editor(f, &chunks[1], editor);
+    if let Some(explorer) = editor.explorer() {
+        draw_explorer(f, &chunks[4], editor, explorer);
+    }
+    if editor.quickfix().open {
+        draw_quickfix(f, &chunks[5], editor, editor.quickfix());
+    }
+    draw_status_line(f, &chunks[2], editor);
+    draw_message_line(f, &chunks[3], editor);
+    if editor.show_messages() {
+        draw_messages(f, &chunks[1], editor);
+    }
+    if let Some(popup) = editor.popup() {
+        draw_popup(f, &chunks[1], editor, popup);
+    }
+    if let Some(completion) = editor.completion() {
+        draw_completion(f, &chunks[1], editor, completion);
+    }
+    if let Some(finder) = editor.finder() {
+        draw_finder(f, &chunks[1], editor, finder);
+    }
+
+    chunks[1]
+}
+
+/// Splits the screen into the tab line, the editor area, the status line,
+/// the message line, the file explorer sidebar left of the editor area and
+/// the quickfix panel below it. The last two are empty while closed.
+pub fn screen_layout(area: Rect, editor: &Editor) -> Vec<Rect> {
+    let tab_line = if editor.tabs().len() > 1 { 1 } else { 0 };
+    let mut chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(tab_line), // tab line
+            Constraint::Min(1),           // editor area
+            Constraint::Length(1),        // status line
+            Constraint::Length(1),        // message line
+        ])
+        .split(area);
+
+    let sidebar = match editor.explorer() {
+        Some(_) => EXPLORER_WIDTH.min(area.width / 2),
+        None => 0,
+    };
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(sidebar), Constraint::Min(1)])
+        .split(chunks[1]);
+
+    let quickfix = editor.quickfix();
+    let panel = if quickfix.open {
+        (quickfix.len().max(1) as u16).min(QUICKFIX_ROWS) + 1
+    } else {
+        0
+    };
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(panel)])
+        .split(columns[1]);
+    chunks[1] = rows[0];
+    chunks.push(columns[0]);
+    chunks.push(rows[1]);
+    chunks
+}
+
+fn draw_tab_line<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor) {
+    let spans: Vec<Span> = editor
+        .tabs()
+        .iter()
+        .enumerate()
+        .map(|(i, tab)| {
+            let doc = &editor.buffers()[tab.window().buffer()];
+            let label = format!(
+                " {}: {}{} ",
+                i + 1,
+                doc.file_name(),
+                if doc.dirty() { " [+]" } else { "" }
+            );
+            let style = if i == editor.active_tab() {
+                editor.theme().ui("tabline.active")
+            } else {
+                Style::default()
+            };
+            Span::styled(label, style)
+        })
+        .collect();
+
+    let paragraph = Paragraph::new(Spans::from(spans)).style(editor.theme().ui("tabline"));
+
+    f.render_widget(paragraph, *area);
+}
+
+fn draw_editor<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor) {
+    let tab = editor.tab();
+    let rects = tab.window_rects(*area);
+    for (window_index, (window, rect)) in tab.windows().iter().zip(rects).enumerate() {
+        let doc = &editor.buffers()[window.buffer()];
+        let gutter = editor.gutter(window);
+        let width = (editor.text_rect(window, &rect).width as usize).max(1);
+        let active = window_index == tab.active();
+        let theme = editor.theme();
+
+        let layout = editor.layout(window, &rect);
+        let mut rows: Vec<Spans> = Vec::new();
+        let mut styles = Vec::new();
+        for line in layout.lines() {
+            let i = line.row;
+            let chars: Vec<char> = doc.lines()[i].chars().collect();
+            if line.wrap_index == 0 {
+                styles = line_styles(editor, doc, i, chars.len(), active);
+            }
+            let cursor_line = i == window.cursor.0;
+            let cursorline = if cursor_line && editor.options().cursorline {
+                Some(theme.ui("cursorline"))
+            } else {
+                None
+            };
+
+            let mut spans = Vec::new();
+            if line.wrap_index == 0 {
+                if gutter.sign_width() > 0 {
+                    spans.push(match doc.signs().get(i) {
+                        Some(sign) => Span::styled(
+                            format!("{:<w$}", sign.text, w = gutter.sign_width()),
+                            theme.ui("gutter").patch(theme.ui(&sign.scope)),
+                        ),
+                        None => Span::styled(" ".repeat(gutter.sign_width()), theme.ui("gutter")),
+                    });
+                }
+                let number_style = if cursor_line {
+                    theme.ui("gutter").patch(theme.ui("gutter.current"))
+                } else {
+                    theme.ui("gutter")
+                };
+                spans.push(Span::styled(
+                    gutter.number(i, window.cursor.0),
+                    number_style,
+                ));
+            } else {
+                spans.push(Span::styled(" ".repeat(gutter.width()), theme.ui("gutter")));
+            }
+            if line.indent > 0 {
+                // break indent, then the showbreak marker
+                let showbreak: String = editor
+                    .options()
+                    .showbreak
+                    .chars()
+                    .take(line.indent)
+                    .collect();
+                let blank = line.indent - showbreak.chars().count();
+                spans.push(Span::raw(" ".repeat(blank)));
+                spans.push(Span::styled(showbreak, theme.ui("nontext")));
+            }
+            let visible = layout.visible(line);
+            let chunk = &chars[visible.clone()];
+            let chunk_styles: Vec<Style> = styles[visible]
+                .iter()
+                .map(|s| match cursorline {
+                    Some(cl) => cl.patch(*s),
+                    None => *s,
+                })
+                .collect();
+            spans.extend(styled_chunk(chunk, &chunk_styles));
+            let mut used = line.indent + chunk.len();
+            // the worst diagnostic of a line is shown after its end, or
+            // else its blame
+            let diagnostic = doc
+                .diagnostics()
+                .iter()
+                .filter(|d| d.start.0 == i)
+                .max_by_key(|d| d.severity);
+            let annotation = match diagnostic {
+                Some(d) => Some((d.message.lines().next().unwrap_or_default(), d.scope())),
+                None => doc
+                    .blame()
+                    .and_then(|b| b.get(i))
+                    .filter(|b| !b.is_empty())
+                    .map(|b| (b.as_str(), "nontext")),
+            };
+            if let Some((message, scope)) = annotation
+                && layout.visible(line).end == chars.len()
+                && used + 2 < width
+            {
+                let text: String = format!("  {}", message)
+                    .chars()
+                    .take(width - used)
+                    .collect();
+                used += text.chars().count();
+                spans.push(Span::styled(text, theme.ui(scope)));
+            }
+            if let Some(cl) = cursorline {
+                spans.push(Span::styled(" ".repeat(width.saturating_sub(used)), cl));
+            }
+            rows.push(Spans::from(spans));
+        }
+
+        f.render_widget(Paragraph::new(rows).style(theme.ui("text")), rect);
+    }
+
+    let rect = tab.active_rect(*area);
+    let (y, x) = editor.doc_to_screen(&rect);
+    f.set_cursor(rect.x + x, rect.y + y);
+}
+
+/// Returns the style of each char of `row`, from its highlights and the
+/// visual selection.
+fn line_styles(
+    editor: &Editor,
+    doc: &Document,
+    row: usize,
+    len: usize,
+    active: bool,
+) -> Vec<Style> {
+    let theme = editor.theme();
+    let mut styles = vec![Style::default(); len];
+    for hl in doc.highlights(row) {
+        let style = theme.syntax(&hl.scope);
+        for s in &mut styles[hl.range.start.min(len)..hl.range.end.min(len)] {
+            *s = style;
+        }
+    }
+
+    if let Some((start, end)) = editor.selection().filter(|_| active)
+        && (start.0..=end.0).contains(&row)
+    {
+        let from = if row == start.0 { start.1 } else { 0 };
+        let to = if row == end.0 { end.1 } else { len };
+        for s in &mut styles[from.min(len)..to.min(len)] {
+            *s = s.patch(theme.ui("selection"));
+        }
+    }
+
+    for d in doc.diagnostics() {
+        if (d.start.0..=d.end.0).contains(&row) {
+            let from = if row == d.start.0 { d.start.1 } else { 0 };
+            let to = if row == d.end.0 { d.end.1 } else { len };
+            for s in &mut styles[from.min(len)..to.min(len).max(from.min(len))] {
+                *s = s.add_modifier(Modifier::UNDERLINED);
+            }
+        }
+    }
+
+    styles
+}
+
+/// Groups the chars of a wrapped line segment into runs of equal style.
+fn styled_chunk<'a>(chunk: &[char], styles: &[Style]) -> Vec<Span<'a>> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut run = String::new();
+    let mut run_style = styles.first().copied().unwrap_or_default();
+
+    for (c, style) in chunk.iter().zip(styles) {
+        if *style != run_style {
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+            run_style = *style;
+        }
+        run.push(*c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, run_style));
+    }
+
+    spans
+}
+
+fn draw_status_line<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor) {
+    let theme = editor.theme();
+    let mode = editor.mode().as_str().to_lowercase();
+    let base = theme
+        .ui("statusline")
+        .patch(theme.ui(&format!("statusline.{}", mode)));
+
+    let mut left: Vec<Span> = Vec::new();
+    let mut right: Vec<Span> = Vec::new();
+    let mut aligned = false;
+    for segment in &editor.options().statusline {
+        if *segment == Segment::Align {
+            aligned = true;
+            continue;
+        }
+        let Some(text) = segment.text(editor) else {
+            continue;
+        };
+        let style = match segment {
+            Segment::Mode => base.patch(theme.ui(&format!("statusline.mode.{}", mode))),
+            _ => base,
+        };
+        let side = if aligned { &mut right } else { &mut left };
+        side.push(Span::styled(format!(" {} ", text), style));
+    }
+
+    let used: usize = left.iter().chain(&right).map(|s| s.width()).sum();
+    let fill = (area.width as usize).saturating_sub(used);
+    left.push(Span::styled(" ".repeat(fill), base));
+    left.extend(right);
+
+    f.render_widget(Paragraph::new(Spans::from(left)).style(base), *area);
+}
+
+/// Draws the command being typed, or else the latest message.
+fn draw_message_line<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor) {
+    let theme = editor.theme();
+    let (text, style) = if *(editor.mode()) == Mode::Command {
+        (format!(":{}", editor.command_buffer()), theme.ui("text"))
+    } else if let Some(msg) = editor.message() {
+        (msg.text.clone(), theme.ui(msg.severity.scope()))
+    } else {
+        (String::new(), theme.ui("text"))
+    };
+
+    f.render_widget(Paragraph::new(text).style(style), *area);
+}
+
+/// Draws the `:messages` history over the bottom of the editor area.
+fn draw_messages<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor) {
+    let theme = editor.theme();
+    let messages = editor.messages();
+    let height = (messages.len() as u16 + 2).min(area.height);
+    let rect = Rect {
+        y: area.y + area.height - height,
+        height,
+        ..*area
+    };
+
+    let skip = messages
+        .len()
+        .saturating_sub(height.saturating_sub(2) as usize);
+    let lines: Vec<Spans> = messages[skip..]
+        .iter()
+        .map(|msg| {
+            Spans::from(Span::styled(
+                msg.text.clone(),
+                theme.ui(msg.severity.scope()),
+            ))
+        })
+        .collect();
+
+    let block = Block::default().borders(Borders::TOP).title(" Messages ");
+    f.render_widget(Clear, rect);
+    f.render_widget(
+        Paragraph::new(lines).style(theme.ui("text")).block(block),
+        rect,
+    );
+}
+
+/// Draws `popup` next to the cursor, below it if it fits there and above it
+/// otherwise.
+fn draw_popup<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor, popup: &Popup) {
+    let rect = editor.tab().active_rect(*area);
+    let (y, x) = editor.doc_to_screen(&rect);
+    let (cx, cy) = (rect.x + x, rect.y + y);
+
+    let longest = popup
+        .lines
+        .iter()
+        .map(|l| l.chars().count())
+        .chain([popup.title.chars().count() + 2])
+        .max()
+        .unwrap_or_default();
+    let width = (longest as u16 + 2).min(area.width);
+    let below = area.bottom().saturating_sub(cy + 1);
+    let above = cy.saturating_sub(area.y);
+    let wanted = popup.lines.len() as u16 + 2;
+    let (y, height) = if wanted <= below || below >= above {
+        (cy + 1, wanted.min(below))
+    } else {
+        let height = wanted.min(above);
+        (cy - height, height)
+    };
+    if height < 3 {
+        return;
+    }
+    let rect = Rect {
+        x: cx.min(area.right() - width),
+        y,
+        width,
+        height,
+    };
+
+    let theme = editor.theme();
+    let lines: Vec<Spans> = popup
+        .lines
+        .iter()
+        .map(|l| Spans::from(l.as_str()))
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" {} ", popup.title));
+    f.render_widget(Clear, rect);
+    f.render_widget(
+        Paragraph::new(lines).style(theme.ui("popup")).block(block),
+        rect,
+    );
+}
+
+/// Draws the matching completion candidates under the start of the
+/// completed text, or over it if they do not fit below, with the preview of
+/// the selected one beside them.
+fn draw_completion<B: Backend>(
+    f: &mut Frame<B>,
+    area: &Rect,
+    editor: &Editor,
+    completion: &Completion,
+) {
+    let rect = editor.tab().active_rect(*area);
+    let (y, x) = editor.doc_to_screen(&rect);
+    let back = editor.cursor().1.saturating_sub(completion.start.1) as u16;
+    let (cx, cy) = ((rect.x + x).saturating_sub(back).max(rect.x), rect.y + y);
+
+    let below = area.bottom().saturating_sub(cy + 1);
+    let above = cy.saturating_sub(area.y);
+    let wanted = completion.len().min(COMPLETION_ROWS) as u16;
+    let (y, height) = if wanted <= below || below >= above {
+        (cy + 1, wanted.min(below))
+    } else {
+        (cy - wanted.min(above), wanted.min(above))
+    };
+    if height == 0 {
+        return;
+    }
+
+    let text_width = completion
+        .matches()
+        .map(|c| c.text.chars().count())
+        .max()
+        .unwrap_or_default();
+    let width = (text_width as u16 + 9).min(area.width);
+    let list = Rect {
+        x: cx.min(area.right() - width),
+        y,
+        width,
+        height,
+    };
+
+    let theme = editor.theme();
+    let first = completion
+        .selected()
+        .map_or(0, |i| (i + 1).saturating_sub(height as usize));
+    let rows: Vec<Spans> = completion
+        .matches()
+        .enumerate()
+        .skip(first)
+        .take(height as usize)
+        .map(|(i, candidate)| {
+            let style = if completion.selected() == Some(i) {
+                theme.ui("popup").patch(theme.ui("popup.selected"))
+            } else {
+                theme.ui("popup")
+            };
+            let text = format!(
+                " {:<w$} {:>6} ",
+                candidate.text,
+                candidate.source.name(),
+                w = text_width
+            );
+            Spans::from(Span::styled(text, style))
+        })
+        .collect();
+    f.render_widget(Clear, list);
+    f.render_widget(Paragraph::new(rows).style(theme.ui("popup")), list);
+
+    if completion.preview.is_empty() {
+        return;
+    }
+    let longest = completion
+        .preview
+        .iter()
+        .map(|l| l.chars().count())
+        .max()
+        .unwrap_or_default() as u16;
+    let right = area.right().saturating_sub(list.right());
+    let left = list.x.saturating_sub(area.x);
+    let width = (longest + 2).min(right.max(left));
+    if width < 10 {
+        return;
+    }
+    let x = if right >= left {
+        list.right()
+    } else {
+        list.x - width
+    };
+    let height = (completion.preview.len() as u16 + 2).min(area.bottom() - y);
+    if height < 3 {
+        return;
+    }
+    let preview = Rect {
+        x,
+        y,
+        width,
+        height,
+    };
+    let lines: Vec<Spans> = completion
+        .preview
+        .iter()
+        .map(|l| Spans::from(l.as_str()))
+        .collect();
+    let block = Block::default().borders(Borders::ALL).title(" Preview ");
+    f.render_widget(Clear, preview);
+    f.render_widget(
+        Paragraph::new(lines).style(theme.ui("popup")).block(block),
+        preview,
+    );
+}
+
+/// Draws the file finder over the middle of the editor area: the query and
+/// the matching files on the left, the highlighted file on the right.
+fn draw_finder<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor, finder: &Finder) {
+    let width = (area.width * 9 / 10).max(area.width.min(20));
+    let height = (area.height * 9 / 10).max(area.height.min(5));
+    let rect = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    // the preview only gets a pane when there is room for it
+    let (list, preview) = if width >= FINDER_PREVIEW_WIDTH {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rect);
+        (chunks[0], Some(chunks[1]))
+    } else {
+        (rect, None)
+    };
+
+    let theme = editor.theme();
+    let rows = list.height.saturating_sub(3) as usize;
+    let first = finder
+        .selected()
+        .map_or(0, |i| (i + 1).saturating_sub(rows));
+    let mut lines = vec![Spans::from(format!("> {}", finder.query()))];
+    lines.extend(
+        finder
+            .matches()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(i, file)| {
+                let style = if finder.selected() == Some(i) {
+                    theme.ui("popup").patch(theme.ui("popup.selected"))
+                } else {
+                    theme.ui("popup")
+                };
+                let width = list.width.saturating_sub(2) as usize;
+                Spans::from(Span::styled(format!("{:<width$}", file), style))
+            }),
+    );
+    let title = format!(" Files {}/{} ", finder.len(), finder.total());
+    let block = Block::default().borders(Borders::ALL).title(title);
+    f.render_widget(Clear, list);
+    f.render_widget(
+        Paragraph::new(lines).style(theme.ui("popup")).block(block),
+        list,
+    );
+    let typed = finder.query().chars().count() as u16 + 2;
+    f.set_cursor((list.x + 1 + typed).min(list.right() - 1), list.y + 1);
+
+    if let Some(preview) = preview {
+        let lines: Vec<Spans> = finder
+            .preview
+            .iter()
+            .map(|l| Spans::from(l.as_str()))
+            .collect();
+        let block = Block::default().borders(Borders::ALL).title(" Preview ");
+        f.render_widget(Clear, preview);
+        f.render_widget(
+            Paragraph::new(lines).style(theme.ui("popup")).block(block),
+            preview,
+        );
+    }
+}
+
+/// Draws the file explorer tree, marking the files open in a buffer and
+/// those with unsaved changes, with the explorer's prompt at the bottom.
+fn draw_explorer<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor, explorer: &Explorer) {
+    if area.width < 3 || area.height == 0 {
+        return;
+    }
+    let theme = editor.theme();
+    let open: Vec<(PathBuf, bool)> = editor
+        .buffers()
+        .iter()
+        .filter_map(|doc| Some((std::path::absolute(doc.full_path()?).ok()?, doc.dirty())))
+        .collect();
+
+    let prompt = explorer.prompt.as_ref().map(|p| p.text(&explorer.root));
+    let rows = area.height as usize - usize::from(prompt.is_some());
+    let first = (explorer.selected() + 1).saturating_sub(rows);
+    let width = area.width as usize - 1;
+    let mut lines: Vec<Spans> = explorer
+        .entries()
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .map(|(i, entry)| {
+            let marker = match (entry.is_dir, explorer.is_expanded(&entry.path)) {
+                (true, true) => "▾ ",
+                (true, false) => "▸ ",
+                (false, _) => "  ",
+            };
+            let dirty = open.iter().find(|(p, _)| *p == entry.path).map(|(_, d)| *d);
+            let mut style = match (entry.is_dir, dirty) {
+                (true, _) => theme.ui("explorer.dir"),
+                (false, Some(_)) => theme.ui("explorer.open"),
+                (false, None) => theme.ui("explorer"),
+            };
+            if i == explorer.selected() && explorer.focused {
+                style = style.patch(theme.ui("popup.selected"));
+            }
+            let text = format!(
+                "{}{}{}{}",
+                "  ".repeat(entry.depth),
+                marker,
+                entry.name(),
+                if dirty == Some(true) { " [+]" } else { "" }
+            );
+            let text: String = text.chars().take(width).collect();
+            Spans::from(Span::styled(format!("{:<width$}", text), style))
+        })
+        .collect();
+    if let Some(prompt) = &prompt {
+        lines.resize(rows, Spans::default());
+        lines.push(Spans::from(prompt.as_str()));
+    }
+
+    let block = Block::default()
+        .borders(Borders::RIGHT)
+        .style(theme.ui("explorer"));
+    f.render_widget(Clear, *area);
+    f.render_widget(Paragraph::new(lines).block(block), *area);
+
+    if explorer.focused {
+        let (x, y) = match &prompt {
+            Some(prompt) => (prompt.chars().count() as u16, area.height - 1),
+            None => (0, (explorer.selected() - first) as u16),
+        };
+        f.set_cursor(area.x + x.min(area.width - 2), area.y + y);
+    }
+}
+
+/// Draws the quickfix panel, scrolled to keep the current entry in view.
+fn draw_quickfix<B: Backend>(f: &mut Frame<B>, area: &Rect, editor: &Editor, quickfix: &Quickfix) {
+    if area.height < 2 {
+        return;
+    }
+    let theme = editor.theme();
+    let rows = area.height as usize - 1;
+    let first = (quickfix.current() + 1).saturating_sub(rows);
+    let lines: Vec<Spans> = quickfix
+        .entries()
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .map(|(i, entry)| {
+            let style = if i == quickfix.current() {
+                theme.ui("popup.selected")
+            } else {
+                theme.ui("text")
+            };
+            let text = format!("{:<w$}", entry.describe(), w = area.width as usize);
+            Spans::from(Span::styled(text, style))
+        })
+        .collect();
+
+    let title = format!(" Quickfix: {} ", quickfix.title);
+    let block = Block::default().borders(Borders::TOP).title(title);
+    f.render_widget(Clear, *area);
+    f.render_widget(
+        Paragraph::new(lines).style(theme.ui("text")).block(block),
+        *area,
+    );
+    if quickfix.focused {
+        let y = (quickfix.current() - first) as u16;
+        f.set_cursor(area.x, area.y + 1 + y);
+    }
+}
@@ -0,0 +1,51 @@
+//! Fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use std::{env, fs, path::PathBuf};
+
+use text_editor::{headless::Headless, models::document::Document};
+
+/// A project made of files in its own temp directory, which is removed
+/// again when it is dropped, even after a failed assertion.
+pub struct TempProject {
+    pub dir: PathBuf,
+}
+
+impl TempProject {
+    /// Makes the directory `text-editor-<feature>-<pid>-<name>` holding
+    /// `files`, each a path relative to it and its text.
+    pub fn new(feature: &str, name: &str, files: &[(&str, &str)]) -> Self {
+        let dir = env::temp_dir().join(format!(
+            "text-editor-{}-{}-{}",
+            feature,
+            std::process::id(),
+            name
+        ));
+        for (path, text) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        Self { dir }
+    }
+
+    /// Returns an editor rooted in the project, showing `file` or an empty
+    /// buffer.
+    pub fn editor(&self, file: Option<&str>, width: u16, height: u16) -> Headless {
+        let mut h = match file {
+            Some(file) => {
+                let doc = Document::open(self.dir.join(file).to_str().unwrap()).unwrap();
+                Headless::new(doc, width, height)
+            }
+            None => Headless::with_text("", width, height),
+        };
+        h.editor_mut().set_root(&self.dir);
+        h
+    }
+}
+
+impl Drop for TempProject {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs;
+
+use text_editor::headless::Headless;
+
+#[test]
+fn ctrl_n_completes_words_from_the_buffers() {
+    let mut h = Headless::with_text("format_string from_str\n\n", 60, 10);
+    h.keys("sifs<C-n>").unwrap();
+    let completion = h.editor().completion().unwrap();
+    let texts: Vec<&str> = completion.matches().map(|c| c.text.as_str()).collect();
+    assert_eq!(texts, ["format_string", "from_str"]);
+    assert_eq!(completion.selected(), Some(0));
+    let screen = h.screen();
+    assert!(screen[2].contains("format_string buffer"), "{:?}", screen);
+    assert!(
+        screen.iter().any(|row| row.contains("[No Name]:1")),
+        "{:?}",
+        screen
+    );
+
+    // typing narrows the matches, Ctrl-Y takes the selected one
+    h.keys("t<C-n><C-y>").unwrap();
+    assert_eq!(h.text(), "format_string from_str\nfrom_str");
+    assert_eq!(h.cursor(), (1, 8));
+
+    h.keys("<Esc>u").unwrap();
+    assert_eq!(h.text(), "format_string from_str\nfst");
+}
+
+#[test]
+fn ctrl_e_and_moving_away_close_the_popup() {
+    let mut h = Headless::with_text("alpha alpine\n\n", 60, 10);
+    h.keys("sial<C-p>").unwrap();
+    assert_eq!(h.editor().completion().unwrap().selected(), Some(1));
+    h.keys("<C-e>").unwrap();
+    assert!(h.editor().completion().is_none());
+    assert_eq!(h.text(), "alpha alpine\nal");
+
+    h.keys("<C-n> ").unwrap();
+    assert!(h.editor().completion().is_none());
+
+    let mut h = Headless::with_text("", 60, 10);
+    h.keys("i<C-n>").unwrap();
+    assert_eq!(h.editor().message().unwrap().text, "No completions");
+}
+
+#[test]
+fn paths_complete_after_a_slash() {
+    let dir = std::env::temp_dir().join(format!("text-editor-completion-{}", std::process::id()));
+    fs::create_dir_all(dir.join("beta")).unwrap();
+    fs::write(dir.join("alpha.txt"), "first line\nsecond line\n").unwrap();
+
+    let mut h = Headless::with_text("", 60, 12);
+    h.keys(&format!("i{}/", dir.display())).unwrap();
+    let completion = h.editor().completion().unwrap();
+    let texts: Vec<&str> = completion.matches().map(|c| c.text.as_str()).collect();
+    assert_eq!(texts, ["alpha.txt", "beta/"]);
+
+    h.keys("al<C-n>").unwrap();
+    assert!(h.screen().iter().any(|row| row.contains("first line")));
+    h.keys("<CR>").unwrap();
+    assert_eq!(h.text(), format!("{}/alpha.txt", dir.display()));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn relative_paths_complete_from_the_project_root() {
+    let dir = std::env::temp_dir().join(format!(
+        "text-editor-completion-{}-root",
+        std::process::id()
+    ));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/main.rs"), "").unwrap();
+
+    let mut h = Headless::with_text("", 60, 12);
+    h.editor_mut().set_root(&dir);
+    h.keys("isrc/").unwrap();
+    let completion = h.editor().completion().unwrap();
+    let texts: Vec<&str> = completion.matches().map(|c| c.text.as_str()).collect();
+    assert_eq!(texts, ["main.rs"]);
+    fs::remove_dir_all(dir).unwrap();
+}
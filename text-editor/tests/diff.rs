@@ -0,0 +1,48 @@
+use std::{env, fs};
+
+use text_editor::{headless::Headless, models::document::Document};
+
+#[test]
+fn diff_shows_what_saving_would_change() {
+    let path = env::temp_dir().join(format!("text-editor-diff-{}.txt", std::process::id()));
+    fs::write(&path, "one\ntwo\nthree\n").unwrap();
+    let mut h = Headless::new(Document::open(path.to_str().unwrap()).unwrap(), 60, 16);
+    h.command("diff").unwrap();
+    assert_eq!(h.editor().message().unwrap().text, "No changes");
+
+    h.keys("jix<Esc>").unwrap();
+    h.command("diff").unwrap();
+    let name = path.display().to_string();
+    assert_eq!(
+        h.text(),
+        format!(
+            "--- {0} (on disk)\n+++ {0} (buffer)\n@@ -1,3 +1,3 @@\n one\n-two\n+xtwo\n three",
+            name
+        )
+    );
+    assert_eq!(h.editor().tab().windows().len(), 2);
+    assert!(h.command("s").is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn diff_again_reuses_its_buffer_and_window() {
+    let path = env::temp_dir().join(format!("text-editor-diff-{}-again.txt", std::process::id()));
+    fs::write(&path, "one\ntwo\n").unwrap();
+    let mut h = Headless::new(Document::open(path.to_str().unwrap()).unwrap(), 60, 16);
+    h.keys("ix<Esc>").unwrap();
+    h.command("diff").unwrap();
+    h.keys("<C-w>wjiy<Esc>").unwrap();
+    h.command("diff").unwrap();
+    assert!(h.text().ends_with("-two\n+xone\n+tywo"), "{}", h.text());
+    assert_eq!(h.cursor(), (0, 0));
+    assert_eq!(h.editor().tab().windows().len(), 2);
+    assert_eq!(h.editor().buffers().len(), 2);
+
+    // once its window is closed, the diff opens in a new one
+    h.command("q").unwrap();
+    h.command("diff").unwrap();
+    assert_eq!(h.editor().tab().windows().len(), 2);
+    assert_eq!(h.editor().buffers().len(), 2);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::TempProject;
+use text_editor::headless::Headless;
+
+/// Makes a project with `src/main.rs` and `notes.txt`, and an editor rooted
+/// in it with the explorer open.
+fn project(name: &str) -> (Headless, TempProject) {
+    let project = TempProject::new(
+        "explorer",
+        name,
+        &[("src/main.rs", "fn main() {}\n"), ("notes.txt", "")],
+    );
+    let mut h = project.editor(None, 60, 8);
+    h.command("explorer").unwrap();
+    (h, project)
+}
+
+#[test]
+fn lists_directories_before_files() {
+    let (h, _project) = project("list");
+    let screen = h.screen();
+    assert!(screen[0].starts_with("▸ src "), "{:?}", screen);
+    assert!(screen[1].starts_with("  notes.txt "), "{:?}", screen);
+}
+
+#[test]
+fn enter_opens_a_file_which_is_marked_while_dirty() {
+    let (mut h, _project) = project("open");
+    h.keys("<CR>s<CR>").unwrap();
+    assert_eq!(h.text(), "fn main() {}");
+    assert!(!h.editor().explorer().unwrap().focused);
+    h.keys("ix<Esc>").unwrap();
+    assert!(
+        h.screen()[1].starts_with("    main.rs [+]"),
+        "{:?}",
+        h.screen()
+    );
+}
+
+#[test]
+fn n_creates_a_file_next_to_the_selection() {
+    let (mut h, project) = project("create");
+    h.keys("<CR>snlib.rs<CR>").unwrap();
+    assert!(project.dir.join("src/lib.rs").exists());
+    assert!(h.screen()[1].starts_with("    lib.rs"), "{:?}", h.screen());
+}
+
+#[test]
+fn moving_a_directory_takes_its_open_buffers_along() {
+    let (mut h, project) = project("move");
+    h.keys("<CR>s<CR><C-e>wr").unwrap();
+    assert!(
+        h.screen()[5].starts_with("Move to: src"),
+        "{:?}",
+        h.screen()
+    );
+    h.keys("<BS><BS><BS>app<CR>").unwrap();
+    assert!(project.dir.join("app/main.rs").exists());
+    assert!(!project.dir.join("src").exists());
+    assert_eq!(
+        h.editor().doc().full_path(),
+        Some(project.dir.join("app/main.rs").as_path())
+    );
+}
+
+#[test]
+fn deleting_asks_first() {
+    let (mut h, project) = project("delete");
+    h.keys("sxn").unwrap();
+    assert!(project.dir.join("notes.txt").exists());
+    h.keys("xy").unwrap();
+    assert!(!project.dir.join("notes.txt").exists());
+}
+
+#[test]
+fn q_closes_the_explorer() {
+    let (mut h, _project) = project("close");
+    h.keys("q").unwrap();
+    assert!(h.editor().explorer().is_none());
+    assert!(!h.screen()[0].contains("src"), "{:?}", h.screen());
+}
+
+#[test]
+fn deleting_warns_about_modified_buffers_and_closes_them() {
+    let (mut h, project) = project("delete-modified");
+    h.keys("s<CR>ix<Esc><C-e>x").unwrap();
+    assert!(
+        h.screen()[5].starts_with("Delete notes.txt (1 modified)"),
+        "{:?}",
+        h.screen()
+    );
+    h.keys("y").unwrap();
+    assert!(!project.dir.join("notes.txt").exists());
+    assert_eq!(h.editor().buffers().len(), 1);
+    assert_eq!(h.editor().doc().full_path(), None);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::TempProject;
+use text_editor::headless::Headless;
+
+/// Makes a project with two sources and an ignored build output, and an
+/// editor rooted in it.
+fn project(name: &str) -> (Headless, TempProject) {
+    let project = TempProject::new(
+        "finder",
+        name,
+        &[
+            (".gitignore", "target/\n"),
+            ("src/editor.rs", "pub struct Editor;\n"),
+            ("src/document.rs", "pub struct Document;\n"),
+            ("target/editor.rs", ""),
+        ],
+    );
+    (project.editor(None, 80, 12), project)
+}
+
+#[test]
+fn ctrl_p_lists_the_files_that_are_not_ignored() {
+    let (mut h, _project) = project("list");
+    h.keys("<C-p>").unwrap();
+    assert_eq!(h.editor().finder().unwrap().total(), 2);
+}
+
+#[test]
+fn typing_narrows_the_list_and_previews_the_match() {
+    let (mut h, _project) = project("query");
+    h.keys("<C-p>edr").unwrap();
+    let finder = h.editor().finder().unwrap();
+    assert_eq!(finder.matches().collect::<Vec<_>>(), ["src/editor.rs"]);
+    let screen = h.screen();
+    assert!(
+        screen.iter().any(|row| row.contains("Files 1/2")),
+        "{:?}",
+        screen
+    );
+    assert!(screen.iter().any(|row| row.contains("pub struct Editor;")));
+}
+
+#[test]
+fn enter_opens_the_file_in_the_window() {
+    let (mut h, project) = project("open");
+    h.keys("<C-p>edr<CR>").unwrap();
+    assert!(h.editor().finder().is_none());
+    assert_eq!(h.text(), "pub struct Editor;");
+    assert_eq!(
+        h.editor().doc().full_path(),
+        Some(project.dir.join("src/editor.rs").as_path())
+    );
+    // named after the root, wherever the editor runs
+    let status = &h.screen()[10];
+    assert!(status.contains(" src/editor.rs "), "{:?}", status);
+}
+
+#[test]
+fn ctrl_v_opens_the_file_in_a_vertical_split() {
+    let (mut h, _project) = project("split");
+    h.command("find doc").unwrap();
+    h.keys("<C-v>").unwrap();
+    assert_eq!(h.editor().tab().windows().len(), 2);
+    assert_eq!(h.text(), "pub struct Document;");
+}
+
+#[test]
+fn escape_closes_the_finder_without_opening_anything() {
+    let (mut h, _project) = project("escape");
+    h.keys("<C-p>xyz<Esc>").unwrap();
+    assert!(h.editor().finder().is_none());
+    assert_eq!(h.text(), "");
+}
@@ -0,0 +1,130 @@
+use std::{env, fs};
+
+use text_editor::{
+    headless::Headless,
+    models::{
+        document::Document,
+        language::{Command, LanguageConfig},
+    },
+};
+
+fn formatter(script: &str, auto_format: bool) -> LanguageConfig {
+    LanguageConfig {
+        formatter: Some(Command {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+        }),
+        auto_format,
+        ..LanguageConfig::default()
+    }
+}
+
+#[test]
+fn format_edits_only_the_changed_lines() {
+    let mut h = Headless::with_text("x\nkeep me\nx", 40, 8);
+    assert_eq!(
+        h.command("format"),
+        Err("No formatter for this buffer".to_string())
+    );
+
+    let path = env::temp_dir().join(format!("text-editor-format-{}.rs", std::process::id()));
+    fs::write(&path, "x\nkeep me\nx\n").unwrap();
+    let mut h = Headless::new(Document::open(path.to_str().unwrap()).unwrap(), 40, 8);
+    h.editor_mut().languages_mut().set(
+        "rust",
+        formatter(
+            "sed s/x/y/ | awk 'NR == 1 && $0 != \"top\" { print \"top\" } { print }'",
+            false,
+        ),
+    );
+    h.keys("jdd").unwrap();
+    h.command("format").unwrap();
+    assert_eq!(h.text(), "top\ny\nkeep me\ny");
+    assert_eq!(h.cursor(), (2, 2));
+    assert_eq!(
+        h.editor().message().unwrap().text,
+        format!("\"{}\" formatted", h.editor().doc().file_name())
+    );
+
+    // one undo takes the whole change back
+    h.keys("u").unwrap();
+    assert_eq!(h.text(), "x\nkeep me\nx");
+    h.keys("<C-r>").unwrap();
+    h.command("format").unwrap();
+    assert!(
+        h.editor()
+            .message()
+            .unwrap()
+            .text
+            .ends_with("already formatted")
+    );
+
+    // a failing formatter on save still writes the file
+    h.editor_mut()
+        .languages_mut()
+        .set("rust", formatter("echo 'syntax error' >&2; exit 1", true));
+    h.keys("ix<Esc>").unwrap();
+    h.command("s").unwrap_err();
+    assert_eq!(h.editor().message().unwrap().text, "sh: syntax error");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "top\ny\nxkeep me\ny");
+
+    h.editor_mut()
+        .languages_mut()
+        .set("rust", formatter("tr k K", true));
+    h.command("s").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "top\ny\nxKeep me\ny");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn sq_stays_to_show_a_formatter_failure() {
+    let path = env::temp_dir().join(format!("text-editor-format-{}-sq.rs", std::process::id()));
+    fs::write(&path, "x\n").unwrap();
+    let mut h = Headless::new(Document::open(path.to_str().unwrap()).unwrap(), 40, 8);
+    h.editor_mut()
+        .languages_mut()
+        .set("rust", formatter("echo 'syntax error' >&2; exit 1", true));
+    h.keys("iy<Esc>").unwrap();
+    assert_eq!(h.command("sq"), Err("sh: syntax error".to_string()));
+    assert!(!h.editor().should_quit());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "yx");
+
+    h.editor_mut()
+        .languages_mut()
+        .set("rust", formatter("cat", true));
+    h.command("sq").unwrap();
+    assert!(h.editor().should_quit());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn saving_under_a_new_name_formats_for_its_file_type() {
+    let path = env::temp_dir().join(format!("text-editor-format-{}-as.rs", std::process::id()));
+    let mut h = Headless::with_text("x", 40, 8);
+    h.editor_mut()
+        .languages_mut()
+        .set("rust", formatter("tr x y", true));
+    h.command(&format!("s {}", path.display())).unwrap();
+    assert_eq!(h.editor().doc().file_type(), Some("rust"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "y");
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn a_failed_save_under_a_new_name_keeps_the_old_one() {
+    let path = env::temp_dir().join(format!("text-editor-format-{}-keep.rs", std::process::id()));
+    fs::write(&path, "x\n").unwrap();
+    let mut h = Headless::new(Document::open(path.to_str().unwrap()).unwrap(), 40, 8);
+    h.editor_mut()
+        .languages_mut()
+        .set("rust", formatter("tr x y", true));
+    let swap = Document::swap_path_for(&path);
+    assert!(h.command("s /nonexistent-dir/x.rs").is_err());
+    let doc = h.editor().doc();
+    assert_eq!(doc.full_path(), Some(path.as_path()));
+    assert_eq!(doc.file_name(), path.file_name().unwrap().to_str().unwrap());
+    assert_eq!(doc.swap_path(), Some(swap.as_path()));
+    assert!(swap.exists());
+    drop(h);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,104 @@
+use std::{env, fs, path::Path};
+
+use git2::{Repository, Signature, Time};
+use text_editor::{headless::Headless, models::document::Document};
+
+fn staged(repo: &Repository, path: &str) -> String {
+    let mut index = repo.index().unwrap();
+    index.read(true).unwrap();
+    let entry = index.get_path(Path::new(path), 0).unwrap();
+    let blob = repo.find_blob(entry.id).unwrap();
+    String::from_utf8(blob.content().to_vec()).unwrap()
+}
+
+#[test]
+fn git_signs_follow_edits_and_hunks_stage_and_reset() {
+    let dir = env::temp_dir().join(format!("text-editor-git-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let repo = Repository::init(&dir).unwrap();
+    fs::write(dir.join("notes.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("notes.txt")).unwrap();
+    index.write().unwrap();
+
+    let path = dir.join("notes.txt");
+    let mut h = Headless::new(Document::open(path.to_str().unwrap()).unwrap(), 40, 10);
+    assert!(h.screen()[0].starts_with("one"), "{:?}", h.screen());
+
+    // a changed line and an added one
+    h.keys("jix<Esc>jj").unwrap();
+    h.command("r !echo five").unwrap();
+    let screen = h.screen();
+    assert!(screen[0].starts_with("  one"), "{:?}", screen);
+    assert!(screen[1].starts_with("~ xtwo"), "{:?}", screen);
+    assert!(screen[4].starts_with("+ five"), "{:?}", screen);
+
+    h.keys("[c").unwrap();
+    assert_eq!(h.cursor(), (1, 0));
+    h.keys("]c").unwrap();
+    assert_eq!(h.cursor(), (4, 0));
+    h.keys("]c").unwrap();
+    assert_eq!(h.editor().message().unwrap().text, "No more hunks");
+
+    h.keys("[cgh").unwrap();
+    assert_eq!(
+        h.editor().popup().unwrap().lines,
+        ["@@ -2,1 +2,1 @@", "-two", "+xtwo"]
+    );
+
+    // staging takes the hunk into the index, and its sign away
+    h.command("stagehunk").unwrap();
+    assert_eq!(staged(&repo, "notes.txt"), "one\nxtwo\nthree\nfour\n");
+    assert!(h.screen()[1].starts_with("  xtwo"), "{:?}", h.screen());
+
+    h.keys("]c").unwrap();
+    h.command("resethunk").unwrap();
+    assert_eq!(h.text(), "one\nxtwo\nthree\nfour");
+    assert!(h.screen()[0].starts_with("one"), "{:?}", h.screen());
+    h.keys("u").unwrap();
+    assert_eq!(h.text(), "one\nxtwo\nthree\nfour\nfive");
+    assert!(h.command("resethunk").is_err());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn blame_annotates_lines_with_their_commit() {
+    let dir = env::temp_dir().join(format!("text-editor-blame-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let repo = Repository::init(&dir).unwrap();
+    fs::write(dir.join("notes.txt"), "one\ntwo\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("notes.txt")).unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let author = Signature::new("Ann", "ann@example.com", &Time::new(1_700_000_000, 0)).unwrap();
+    let commit = repo
+        .commit(Some("HEAD"), &author, &author, "notes", &tree, &[])
+        .unwrap();
+    let id = commit.to_string();
+
+    let path = dir.join("notes.txt");
+    let mut h = Headless::new(Document::open(path.to_str().unwrap()).unwrap(), 60, 10);
+    h.keys("jix<Esc>").unwrap();
+    h.command("r !echo three").unwrap();
+    h.command("blame").unwrap();
+    let screen = h.screen();
+    let blame = format!("  one  {} Ann 2023-11-14", &id[..8]);
+    assert!(screen[0].starts_with(&blame), "{:?}", screen);
+    assert!(
+        screen[1].contains("xtwo  Not committed yet"),
+        "{:?}",
+        screen
+    );
+    assert!(
+        screen[2].contains("three  Not committed yet"),
+        "{:?}",
+        screen
+    );
+
+    // lines added afterwards have no blame until it is read again
+    h.command("r !echo four").unwrap();
+    assert_eq!(h.screen()[3].trim_end(), "~ four");
+    h.command("blame").unwrap();
+    assert!(!h.screen()[0].contains("Ann"), "{:?}", h.screen());
+    fs::remove_dir_all(dir).unwrap();
+}
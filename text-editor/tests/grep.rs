@@ -0,0 +1,75 @@
+mod common;
+
+use common::TempProject;
+use text_editor::headless::Headless;
+
+/// Makes a project with a match in each of two sources, and an editor
+/// rooted in it showing `src/b.rs`.
+fn project(name: &str) -> (Headless, TempProject) {
+    let project = TempProject::new(
+        "grep",
+        name,
+        &[
+            ("src/a.rs", "// needle\nfn a() {}\n"),
+            ("src/b.rs", "fn b() {\n    let needle = 1;\n}\n"),
+            ("notes.txt", "no match here\n"),
+        ],
+    );
+    (project.editor(Some("src/b.rs"), 60, 12), project)
+}
+
+#[test]
+fn grep_jumps_to_the_first_match() {
+    let (mut h, _project) = project("first");
+    h.command("grep needle").unwrap();
+    assert_eq!(h.editor().doc().file_name(), "a.rs");
+    assert_eq!(h.cursor(), (0, 3));
+    assert_eq!(h.editor().message().unwrap().text, "(1 of 2): // needle");
+}
+
+#[test]
+fn cnext_shows_a_file_already_open_in_its_buffer() {
+    let (mut h, _project) = project("next");
+    h.command("grep needle").unwrap();
+    h.command("cnext").unwrap();
+    assert_eq!(h.editor().doc().file_name(), "b.rs");
+    assert_eq!(h.cursor(), (1, 8));
+    assert_eq!(h.editor().buffers().len(), 2);
+    assert_eq!(h.command("cn"), Err("No more items".to_string()));
+    h.command("cprev").unwrap();
+    assert_eq!(h.editor().doc().file_name(), "a.rs");
+}
+
+#[test]
+fn copen_lists_the_matches_relative_to_the_root() {
+    let (mut h, _project) = project("open");
+    h.command("grep needle").unwrap();
+    h.command("copen").unwrap();
+    let screen = h.screen();
+    assert!(screen[7].contains("Quickfix: grep needle"), "{:?}", screen);
+    assert_eq!(screen[8], "src/a.rs:1:4: // needle");
+    assert_eq!(screen[9], "src/b.rs:2:9: let needle = 1;");
+
+    h.keys("s<CR>").unwrap();
+    assert_eq!(h.editor().doc().file_name(), "b.rs");
+    assert!(!h.editor().quickfix().focused);
+    h.command("cclose").unwrap();
+    assert!(!h.screen().iter().any(|row| row.contains("Quickfix")));
+}
+
+#[test]
+fn grep_searches_the_paths_given() {
+    let (mut h, _project) = project("paths");
+    h.command("grep needle src/b.rs").unwrap();
+    assert_eq!(h.editor().quickfix().len(), 1);
+    assert_eq!(h.editor().doc().file_name(), "b.rs");
+}
+
+#[test]
+fn no_match_is_an_error() {
+    let (mut h, _project) = project("none");
+    assert_eq!(
+        h.command("grep 'not there'"),
+        Err("No match: not there".to_string())
+    );
+}
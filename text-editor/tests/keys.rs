@@ -0,0 +1,208 @@
+use std::{env, fs};
+
+use text_editor::{
+    headless::Headless,
+    models::{document::Document, editor::Mode},
+};
+
+#[test]
+fn insert_and_escape() {
+    let mut h = Headless::with_text("", 40, 8);
+    h.keys("ihello<CR>world").unwrap();
+    assert_eq!(h.mode(), Mode::Insert);
+    h.keys("<Esc>").unwrap();
+    assert_eq!(h.mode(), Mode::Normal);
+    assert_eq!(h.text(), "hello\nworld");
+    assert_eq!(h.cursor(), (1, 5));
+}
+
+#[test]
+fn backspace_joins_lines() {
+    let mut h = Headless::with_text("ab\ncd", 40, 8);
+    h.keys("si<BS><BS><Esc>").unwrap();
+    assert_eq!(h.text(), "acd");
+    assert_eq!(h.cursor(), (0, 1));
+}
+
+#[test]
+fn wasd_moves_the_cursor() {
+    let mut h = Headless::with_text("one\ntwo\nthree", 40, 8);
+    h.keys("ddss").unwrap();
+    assert_eq!(h.cursor(), (2, 2));
+    h.keys("aw").unwrap();
+    assert_eq!(h.cursor(), (1, 1));
+}
+
+#[test]
+fn j_and_k_move_by_line_and_gj_by_screen_row() {
+    // 10 columns of text wrap the first line onto two screen rows
+    let mut h = Headless::with_text("abcdefghijklmno\nxyz", 10, 8);
+    h.keys("j").unwrap();
+    assert_eq!(h.cursor(), (1, 0));
+    h.keys("k").unwrap();
+    assert_eq!(h.cursor(), (0, 0));
+    h.keys("gj").unwrap();
+    assert_eq!(h.cursor(), (0, 10));
+    assert_eq!(h.screen_cursor(), (0, 1));
+}
+
+#[test]
+fn visual_x_deletes_the_selection() {
+    let mut h = Headless::with_text("hello world", 40, 8);
+    h.keys("vddddd").unwrap();
+    assert_eq!(h.mode(), Mode::Visual);
+    h.keys("x").unwrap();
+    assert_eq!(h.mode(), Mode::Normal);
+    assert_eq!(h.text(), "world");
+}
+
+#[test]
+fn undo_and_redo_an_insert_session() {
+    let mut h = Headless::with_text("", 40, 8);
+    h.keys("ifoo<CR>bar<Esc>").unwrap();
+    h.keys("u").unwrap();
+    assert_eq!(h.text(), "");
+    h.keys("<C-r>").unwrap();
+    assert_eq!(h.text(), "foo\nbar");
+}
+
+#[test]
+fn undoing_back_to_the_saved_text_clears_dirty() {
+    let mut h = Headless::with_text("abc", 40, 8);
+    h.keys("ix<Esc>").unwrap();
+    assert!(h.editor().doc().dirty());
+    h.keys("u").unwrap();
+    assert!(!h.editor().doc().dirty());
+    h.keys("<C-r>").unwrap();
+    assert!(h.editor().doc().dirty());
+
+    h.keys("u").unwrap();
+    assert!(!h.editor().doc().dirty());
+}
+
+#[test]
+fn edits_after_undoing_past_a_save_leave_the_buffer_dirty() {
+    let path = env::temp_dir().join(format!("text-editor-keys-{}.txt", std::process::id()));
+    fs::write(&path, "abc").unwrap();
+    let doc = Document::open(path.to_str().unwrap()).unwrap();
+    let mut h = Headless::new(doc, 40, 8);
+    h.keys("ix<Esc>").unwrap();
+    h.command("s").unwrap();
+    h.keys("u").unwrap();
+    assert!(h.editor().doc().dirty());
+    h.keys("<C-r>").unwrap();
+    assert!(!h.editor().doc().dirty());
+
+    // the saved text was in the dropped redo step, so nothing gets back to it
+    h.keys("uiy<Esc>u").unwrap();
+    assert_eq!(h.text(), "abc");
+    assert!(h.editor().doc().dirty());
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn macros_record_and_replay() {
+    let mut h = Headless::with_text("", 40, 8);
+    h.keys("qaiab<Esc>q@a@@").unwrap();
+    assert_eq!(h.text(), "ababab");
+    assert_eq!(h.editor().recording(), None);
+}
+
+#[test]
+fn paste_is_one_undo_step() {
+    let mut h = Headless::with_text("", 40, 8);
+    h.paste("one\r\ntwo");
+    assert_eq!(h.text(), "one\ntwo");
+    h.keys("u").unwrap();
+    assert_eq!(h.text(), "");
+}
+
+#[test]
+fn renders_text_and_status_line() {
+    let mut h = Headless::with_text("hello\nworld", 30, 5);
+    h.keys("ix<Esc>").unwrap();
+    let screen = h.screen();
+    assert_eq!(screen[0], "xhello");
+    assert_eq!(screen[1], "world");
+    assert!(screen[3].starts_with(" NORMAL  [No Name]  [+]"));
+}
+
+#[test]
+fn command_line_and_messages() {
+    let mut h = Headless::with_text("", 40, 5);
+    h.keys("ix<Esc>:q").unwrap();
+    assert_eq!(h.mode(), Mode::Command);
+    assert_eq!(h.screen()[4], ":q");
+    h.keys("<CR>").unwrap();
+    assert_eq!(h.mode(), Mode::Normal);
+    assert!(!h.editor().should_quit());
+    assert!(h.screen()[4].starts_with("No write since last change"));
+}
+
+#[test]
+fn vertical_split_shows_the_buffer_twice() {
+    let mut h = Headless::with_text("hello", 30, 5);
+    h.keys("<C-w>v").unwrap();
+    assert_eq!(h.editor().tab().windows().len(), 2);
+    assert_eq!(h.screen()[0], "hello          hello");
+}
+
+#[test]
+fn resize_rewraps_the_text() {
+    let mut h = Headless::with_text("abcdefghij", 20, 5);
+    assert_eq!(h.screen()[0], "abcdefghij");
+    h.resize(6, 5);
+    assert_eq!(h.screen()[..2], ["abcdef", "ghij"]);
+}
+
+#[test]
+fn ex_commands_report_errors() {
+    let mut h = Headless::with_text("one\ntwo", 40, 8);
+    assert_eq!(h.command("normal sdix<Esc>"), Ok(()));
+    assert_eq!(h.text(), "one\ntxwo");
+    assert_eq!(h.mode(), Mode::Normal);
+    assert_eq!(
+        h.command("bogus"),
+        Err("Not an editor command: bogus".to_string())
+    );
+    assert_eq!(
+        h.command("normal <Nope>"),
+        Err("Unknown key: <Nope>".to_string())
+    );
+}
+
+#[test]
+fn tabs_open_cycle_and_close() {
+    let path = env::temp_dir().join(format!("text-editor-keys-{}-tab.txt", std::process::id()));
+    fs::write(&path, "tab two").unwrap();
+    let mut h = Headless::with_text("tab one", 60, 8);
+    assert_eq!(h.screen()[0], "tab one");
+
+    h.command(&format!("tabnew {}", path.display())).unwrap();
+    let name = path.file_name().unwrap().to_str().unwrap();
+    assert_eq!(h.screen()[0], format!(" 1: [No Name]  2: {}", name));
+    assert_eq!(h.screen()[1], "tab two");
+    assert_eq!(h.editor().active_tab(), 1);
+
+    // the label marks a modified buffer
+    h.keys("ix<Esc>").unwrap();
+    assert_eq!(h.screen()[0], format!(" 1: [No Name]  2: {} [+]", name));
+
+    h.keys("gt").unwrap();
+    assert_eq!(h.editor().active_tab(), 0);
+    assert_eq!(h.screen()[1], "tab one");
+    h.keys("gT").unwrap();
+    assert_eq!(h.editor().active_tab(), 1);
+    h.command("tabnext").unwrap();
+    assert_eq!(h.editor().active_tab(), 0);
+
+    h.command("tabclose").unwrap();
+    assert_eq!(h.editor().tabs().len(), 1);
+    assert_eq!(h.screen()[0], "xtab two");
+    assert_eq!(
+        h.command("tabclose"),
+        Err("Cannot close last tab page".to_string())
+    );
+    drop(h);
+    let _ = fs::remove_file(&path);
+}
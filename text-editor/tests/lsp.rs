@@ -0,0 +1,341 @@
+use std::{
+    fs,
+    io::{self, BufReader},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::{Value, json};
+use text_editor::{
+    headless::Headless,
+    models::{
+        document::Document,
+        language::{Command, LanguageConfig},
+        lsp::{LanguageServer, read_message, write_message},
+    },
+};
+
+const SOURCE: &str = "fn main() {\n    let x = 1;\n}\n";
+
+/// Answers requests the way a language server would, with canned results,
+/// and passes every message it receives on to the test.
+fn mock_server() -> (LanguageServer, Receiver<Value>) {
+    let (client_read, mut server_write) = io::pipe().unwrap();
+    let (server_read, client_write) = io::pipe().unwrap();
+    let (received, messages) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut reader = BufReader::new(server_read);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            let method = message["method"].as_str().unwrap_or_default().to_string();
+            let uri = message["params"]["textDocument"]["uri"].clone();
+            if received.send(message.clone()).is_err() || method == "exit" {
+                break;
+            }
+            let result = match method.as_str() {
+                "initialize" => json!({ "capabilities": {
+                    "textDocumentSync": 2,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "renameProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                } }),
+                "textDocument/hover" => {
+                    json!({ "contents": { "kind": "markdown", "value": "fn main()" } })
+                }
+                "textDocument/definition" => json!({
+                    "uri": uri,
+                    "range": { "start": { "line": 1, "character": 8 }, "end": { "line": 1, "character": 9 } },
+                }),
+                "textDocument/rename" => json!({ "changes": { uri.as_str().unwrap(): [{
+                    "range": { "start": { "line": 1, "character": 8 }, "end": { "line": 1, "character": 9 } },
+                    "newText": message["params"]["newName"],
+                }] } }),
+                "textDocument/completion" => json!([
+                    { "label": "main_loop" },
+                    { "label": "main_thread" },
+                    { "label": "other" },
+                ]),
+                _ => Value::Null,
+            };
+            if method == "textDocument/didOpen" {
+                let diagnostics = json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": {
+                    "uri": uri,
+                    "diagnostics": [{
+                        "range": { "start": { "line": 1, "character": 8 }, "end": { "line": 1, "character": 9 } },
+                        "severity": 2,
+                        "message": "unused variable: `x`",
+                    }],
+                } });
+                write_message(&mut server_write, &diagnostics).unwrap();
+            }
+            if let Some(id) = message.get("id") {
+                let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                write_message(&mut server_write, &response).unwrap();
+            }
+        }
+    });
+
+    let server = LanguageServer::connect("mock", client_read, client_write, &std::env::temp_dir());
+    (server, messages)
+}
+
+/// Opens `SOURCE` as a Rust file served by the mock server, once the server
+/// got it.
+fn open(name: &str) -> (Headless, Receiver<Value>, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("text-editor-lsp-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.rs");
+    fs::write(&path, SOURCE).unwrap();
+
+    let doc = Document::open(path.to_str().unwrap()).unwrap();
+    let mut h = Headless::new(doc, 60, 10);
+    let (server, messages) = mock_server();
+    h.editor_mut().lsp_mut().insert("rust", server);
+    wait_for(&mut h, |h| !h.editor().doc().diagnostics().is_empty());
+    (h, messages, dir)
+}
+
+fn wait_for(h: &mut Headless, done: impl Fn(&Headless) -> bool) {
+    let start = Instant::now();
+    while !done(h) {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        h.poll_lsp();
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn received(messages: &Receiver<Value>, method: &str) -> Vec<Value> {
+    messages
+        .try_iter()
+        .filter(|m| m["method"] == method)
+        .map(|m| m["params"].clone())
+        .collect()
+}
+
+#[test]
+fn opens_then_sends_incremental_changes() {
+    let (mut h, messages, dir) = open("changes");
+    let opened = received(&messages, "textDocument/didOpen");
+    assert_eq!(opened.len(), 1);
+    assert_eq!(opened[0]["textDocument"]["text"], SOURCE.trim_end());
+    assert_eq!(opened[0]["textDocument"]["languageId"], "rust");
+
+    h.keys("sddddi😀<BS>é").unwrap();
+    // one change is sent for each key
+    let changes: Vec<Value> =
+        std::iter::from_fn(|| messages.recv_timeout(Duration::from_secs(5)).ok())
+            .filter(|m| m["method"] == "textDocument/didChange")
+            .map(|m| m["params"].clone())
+            .take(3)
+            .collect();
+    let edits: Vec<(Value, Value)> = changes
+        .iter()
+        .flat_map(|c| c["contentChanges"].as_array().unwrap().clone())
+        .map(|c| (c["range"].clone(), c["text"].clone()))
+        .collect();
+    let range = |from: u64, to: u64| json!({ "start": { "line": 1, "character": from }, "end": { "line": 1, "character": to } });
+    assert_eq!(
+        edits,
+        [
+            (range(4, 4), json!("😀")),
+            (range(4, 6), json!("")),
+            (range(4, 4), json!("é")),
+        ]
+    );
+    let versions: Vec<_> = changes
+        .iter()
+        .map(|c| &c["textDocument"]["version"])
+        .collect();
+    assert!(versions.windows(2).all(|v| v[0].as_i64() < v[1].as_i64()));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn diagnostics_show_in_the_gutter_and_inline() {
+    let (h, _messages, dir) = open("diagnostics");
+    let screen = h.screen();
+    assert!(screen[1].starts_with("W"), "{:?}", screen);
+    assert!(
+        screen[1].ends_with("let x = 1;  unused variable: `x`"),
+        "{:?}",
+        screen
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn hover_shows_a_popup_until_the_next_key() {
+    let (mut h, _messages, dir) = open("hover");
+    h.keys("K").unwrap();
+    wait_for(&mut h, |h| h.editor().popup().is_some());
+    assert!(h.screen().iter().any(|row| row.contains("fn main()")));
+
+    h.keys("<Esc>").unwrap();
+    assert!(h.editor().popup().is_none());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn goto_definition_moves_the_cursor() {
+    let (mut h, _messages, dir) = open("definition");
+    h.keys("gd").unwrap();
+    wait_for(&mut h, |h| h.cursor() == (1, 8));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rename_is_one_undo_step() {
+    let (mut h, _messages, dir) = open("rename");
+    h.command("rename count").unwrap();
+    wait_for(&mut h, |h| h.text().contains("let count = 1;"));
+
+    h.keys("u").unwrap();
+    assert_eq!(h.text(), SOURCE.trim_end());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn completion_lists_the_server_items() {
+    let (mut h, _messages, dir) = open("completion");
+    h.keys("ima<C-x><C-o>").unwrap();
+    wait_for(&mut h, |h| {
+        h.editor().completion().is_some_and(|c| !c.waiting)
+    });
+    let completion = h.editor().completion().unwrap();
+    let texts: Vec<&str> = completion.matches().map(|c| c.text.as_str()).collect();
+    assert_eq!(texts, ["main_loop", "main_thread"]);
+    assert_eq!(completion.selected(), Some(0));
+
+    h.keys("<C-n><C-y>").unwrap();
+    assert!(h.text().starts_with("main_threadfn main()"));
+    assert!(h.editor().completion().is_none());
+
+    // the completion undoes on its own
+    h.keys("<Esc>u").unwrap();
+    assert!(h.text().starts_with("mafn main()"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn trigger_characters_open_completion() {
+    let (mut h, _messages, dir) = open("trigger");
+    h.keys("i.").unwrap();
+    wait_for(&mut h, |h| {
+        h.editor().completion().is_some_and(|c| !c.waiting)
+    });
+    let completion = h.editor().completion().unwrap();
+    assert_eq!(completion.len(), 3);
+    assert_eq!(completion.selected(), None);
+
+    // with nothing selected, Enter breaks the line
+    h.keys("<CR>").unwrap();
+    assert!(h.text().starts_with(".\nfn main()"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn saving_under_a_new_name_closes_the_old_file() {
+    let (mut h, messages, dir) = open("save-as");
+    received(&messages, "textDocument/didOpen");
+    let moved = dir.join("moved.rs");
+    h.command(&format!("s {}", moved.display())).unwrap();
+
+    let notes: Vec<Value> =
+        std::iter::from_fn(|| messages.recv_timeout(Duration::from_secs(5)).ok())
+            .filter(|m| {
+                m["method"] == "textDocument/didClose" || m["method"] == "textDocument/didOpen"
+            })
+            .take(2)
+            .collect();
+    let uri = |m: &Value| {
+        m["params"]["textDocument"]["uri"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(notes[0]["method"], "textDocument/didClose");
+    assert!(uri(&notes[0]).ends_with("/main.rs"));
+    assert_eq!(notes[1]["method"], "textDocument/didOpen");
+    assert!(uri(&notes[1]).ends_with("/moved.rs"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn moving_a_file_in_the_explorer_reopens_it_under_the_new_path() {
+    let (mut h, messages, dir) = open("explorer-move");
+    received(&messages, "textDocument/didOpen");
+    h.editor_mut().set_root(&dir);
+    h.command("explorer").unwrap();
+    h.keys("r<BS><BS><BS><BS><BS><BS><BS>moved.rs<CR>").unwrap();
+    assert!(dir.join("moved.rs").exists());
+
+    // the new path is opened on the next sync
+    h.poll_lsp();
+    let notes: Vec<Value> =
+        std::iter::from_fn(|| messages.recv_timeout(Duration::from_secs(5)).ok())
+            .filter(|m| {
+                m["method"] == "textDocument/didClose" || m["method"] == "textDocument/didOpen"
+            })
+            .take(2)
+            .collect();
+    let uri = |m: &Value| {
+        m["params"]["textDocument"]["uri"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(notes[0]["method"], "textDocument/didClose");
+    assert!(uri(&notes[0]).ends_with("/main.rs"));
+    assert_eq!(notes[1]["method"], "textDocument/didOpen");
+    assert!(uri(&notes[1]).ends_with("/moved.rs"));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn servers_start_in_the_editor_root() {
+    let dir = std::env::temp_dir().join(format!("text-editor-lsp-{}-root", std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let path = dir.join("src/main.rs");
+    fs::write(&path, SOURCE).unwrap();
+
+    let doc = Document::open(path.to_str().unwrap()).unwrap();
+    let mut h = Headless::new(doc, 60, 10);
+    h.editor_mut().set_root(&dir);
+    // a server that only keeps where it runs and what it is sent
+    let server = Command {
+        command: "sh".to_string(),
+        args: vec!["-c".to_string(), "pwd > cwd; exec cat > sent".to_string()],
+    };
+    h.editor_mut().languages_mut().set(
+        "rust",
+        LanguageConfig {
+            language_server: Some(server),
+            ..LanguageConfig::default()
+        },
+    );
+    h.keys("<Esc>").unwrap();
+
+    let start = Instant::now();
+    let sent = loop {
+        let sent = fs::read_to_string(dir.join("sent")).unwrap_or_default();
+        if sent.contains("rootUri") {
+            break sent;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        thread::sleep(Duration::from_millis(5));
+    };
+    assert!(
+        sent.contains(&format!("\"rootUri\":\"file://{}\"", dir.display())),
+        "{}",
+        sent
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("cwd")).unwrap().trim_end(),
+        dir.to_str().unwrap()
+    );
+    drop(h);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use std::{
+    fs, thread,
+    time::{Duration, Instant},
+};
+
+use common::TempProject;
+use text_editor::headless::Headless;
+
+/// Makes a project with a build script reporting an error and a warning,
+/// and an editor rooted in it that builds with the script.
+fn project(name: &str) -> (Headless, TempProject) {
+    let project = TempProject::new(
+        "make",
+        name,
+        &[
+            ("src/main.rs", "fn main() {\n    boom();\n}\n"),
+            (
+                "build.sh",
+                "echo 'src/main.rs:2:5: error: cannot find function `boom`' >&2\n\
+                 echo 'src/main.rs:3:1: warning: unbalanced' >&2\n\
+                 exit 1\n",
+            ),
+        ],
+    );
+    let mut h = project.editor(None, 60, 10);
+    h.command(r"set makeprg=sh\ build.sh").unwrap();
+    (h, project)
+}
+
+#[test]
+fn make_jumps_to_the_first_error() {
+    let (mut h, _project) = project("first");
+    h.command("make").unwrap();
+    assert_eq!(h.editor().doc().file_name(), "main.rs");
+    assert_eq!(h.cursor(), (1, 4));
+    assert_eq!(
+        h.editor().message().unwrap().text,
+        "(1 of 2): cannot find function `boom`"
+    );
+    h.command("cn").unwrap();
+    assert_eq!(h.cursor(), (2, 0));
+}
+
+#[test]
+fn make_marks_the_lines_with_their_severity() {
+    let (mut h, _project) = project("signs");
+    h.command("make").unwrap();
+    let screen = h.screen();
+    assert!(screen[0].starts_with("  fn main"), "{:?}", screen);
+    assert!(screen[1].starts_with("E     boom();"), "{:?}", screen);
+    assert!(screen[2].starts_with("W }"), "{:?}", screen);
+}
+
+#[test]
+fn errorformat_reads_custom_messages() {
+    let (mut h, project) = project("efm");
+    fs::write(project.dir.join("build.sh"), "echo 'E src/main.rs 1'\n").unwrap();
+    h.command(r"set efm=E\ %f\ %l makeprg=sh\ build.sh\ src")
+        .unwrap();
+    assert_eq!(h.editor().options().makeprg, "sh build.sh src");
+    h.command("make").unwrap();
+    assert_eq!(h.editor().quickfix().entries()[0].pos, (0, 0));
+    // without a type, the line is not marked
+    assert!(h.screen()[1].starts_with("  "), "{:?}", h.screen());
+    assert!(h.command("set efm=%q").is_err());
+}
+
+#[test]
+fn a_clean_build_empties_the_quickfix_list() {
+    let (mut h, project) = project("clean");
+    fs::write(project.dir.join("build.sh"), "exit 0\n").unwrap();
+    h.command("make").unwrap();
+    assert_eq!(h.editor().message().unwrap().text, "Build succeeded");
+    assert!(h.editor().quickfix().is_empty());
+}
+
+#[test]
+fn make_runs_in_the_background() {
+    let (mut h, _project) = project("background");
+    h.keys(":make<CR>").unwrap();
+    assert_eq!(h.editor().message().unwrap().text, "Running sh build.sh");
+    h.keys(":make<CR>").unwrap();
+    assert_eq!(
+        h.editor().message().unwrap().text,
+        "A build is already running"
+    );
+
+    let start = Instant::now();
+    while !h.poll_make() {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(h.editor().doc().file_name(), "main.rs");
+    assert_eq!(h.cursor(), (1, 4));
+}
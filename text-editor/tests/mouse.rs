@@ -0,0 +1,87 @@
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use text_editor::{headless::Headless, models::editor::Mode};
+
+const LONG: &str = "short\nthis line is long enough to wrap around\nend";
+
+fn click(h: &mut Headless, x: u16, y: u16) {
+    h.mouse(event(MouseEventKind::Down(MouseButton::Left), x, y));
+}
+
+fn event(kind: MouseEventKind, x: u16, y: u16) -> MouseEvent {
+    MouseEvent {
+        kind,
+        column: x,
+        row: y,
+        modifiers: KeyModifiers::NONE,
+    }
+}
+
+#[test]
+fn a_click_on_a_wrapped_line_places_the_cursor_in_its_row() {
+    let mut h = Headless::with_text(LONG, 20, 8);
+    click(&mut h, 3, 2);
+    assert_eq!(h.cursor(), (1, 23));
+    // past the end of a line goes to its end
+    click(&mut h, 10, 3);
+    assert_eq!(h.cursor(), (2, 3));
+    assert_eq!(h.mode(), Mode::Normal);
+}
+
+#[test]
+fn dragging_selects() {
+    let mut h = Headless::with_text(LONG, 20, 8);
+    click(&mut h, 1, 0);
+    h.mouse(event(MouseEventKind::Drag(MouseButton::Left), 4, 1));
+    assert_eq!(h.mode(), Mode::Visual);
+    assert_eq!(h.editor().selection(), Some(((0, 1), (1, 5))));
+
+    // a click ends the selection
+    click(&mut h, 0, 3);
+    assert_eq!(h.mode(), Mode::Normal);
+}
+
+#[test]
+fn a_double_click_selects_a_word_or_a_run_of_punctuation() {
+    let mut h = Headless::with_text("foo::bar(x)", 20, 8);
+    click(&mut h, 6, 0);
+    click(&mut h, 6, 0);
+    assert_eq!(h.editor().selection(), Some(((0, 5), (0, 8))));
+
+    click(&mut h, 3, 0);
+    click(&mut h, 3, 0);
+    assert_eq!(h.editor().selection(), Some(((0, 3), (0, 5))));
+}
+
+#[test]
+fn a_triple_click_selects_the_line() {
+    let mut h = Headless::with_text("foo::bar(x)\nnext", 20, 8);
+    for _ in 0..3 {
+        click(&mut h, 6, 0);
+    }
+    assert_eq!(h.editor().selection(), Some(((0, 0), (1, 0))));
+}
+
+#[test]
+fn a_click_in_the_inactive_split_makes_it_active() {
+    let mut h = Headless::with_text(LONG, 20, 8);
+    h.command("sp").unwrap();
+    assert_eq!(h.editor().tab().active(), 1);
+    h.keys("jj").unwrap();
+    click(&mut h, 2, 0);
+    assert_eq!(h.editor().tab().active(), 0);
+    assert_eq!(h.cursor(), (0, 2));
+    // the other window keeps its cursor
+    assert_eq!(h.editor().tab().windows()[1].cursor, (2, 0));
+}
+
+#[test]
+fn the_wheel_scrolls_the_window_under_the_pointer() {
+    let text: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+    let mut h = Headless::with_text(&text.join("\n"), 20, 8);
+    h.mouse(event(MouseEventKind::ScrollDown, 0, 0));
+    assert_eq!(h.screen()[0], "4");
+    // the cursor is kept on screen
+    assert_eq!(h.cursor(), (3, 0));
+    h.mouse(event(MouseEventKind::ScrollUp, 0, 0));
+    assert_eq!(h.screen()[0], "1");
+}
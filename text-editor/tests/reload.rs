@@ -0,0 +1,92 @@
+use std::{
+    env,
+    fs::{self, File},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use text_editor::{
+    headless::Headless,
+    models::document::{Document, LineEnding},
+};
+
+#[test]
+fn reloading_picks_up_a_changed_line_ending() {
+    let path = env::temp_dir().join(format!("text-editor-reload-{}.txt", std::process::id()));
+    fs::write(&path, "one\ntwo\n").unwrap();
+    let doc = Document::open(path.to_str().unwrap()).unwrap();
+    let mut h = Headless::new(doc, 40, 8);
+    assert_eq!(h.editor().doc().line_ending(), LineEnding::Unix);
+
+    fs::write(&path, "one\r\ntwo\r\nthree\r\n").unwrap();
+    h.command("e!").unwrap();
+    assert_eq!(h.text(), "one\ntwo\nthree");
+    assert_eq!(h.editor().doc().line_ending(), LineEnding::Dos);
+    assert!(!h.editor().doc().dirty());
+    let _ = fs::remove_file(&path);
+}
+
+/// Writes `text` to `path` as if another program did, a while after the
+/// editor read it.
+fn write_outside(path: &Path, text: &str) {
+    fs::write(path, text).unwrap();
+    let later = SystemTime::now() + Duration::from_secs(2);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+}
+
+#[test]
+fn focus_gained_reloads_a_file_changed_on_disk() {
+    let path = env::temp_dir().join(format!(
+        "text-editor-reload-{}-focus.txt",
+        std::process::id()
+    ));
+    fs::write(&path, "one\n").unwrap();
+    let doc = Document::open(path.to_str().unwrap()).unwrap();
+    let mut h = Headless::new(doc, 60, 8);
+
+    write_outside(&path, "one\ntwo\n");
+    h.focus(true);
+    assert_eq!(h.text(), "one\ntwo");
+
+    // a modified buffer is kept, with a warning
+    h.keys("ix<Esc>").unwrap();
+    write_outside(&path, "three\n");
+    h.focus(true);
+    assert_eq!(h.text(), "xone\ntwo");
+    assert!(
+        h.editor()
+            .message()
+            .unwrap()
+            .text
+            .ends_with("changed on disk since editing started (:e! to reload)")
+    );
+    drop(h);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn focus_lost_writes_modified_buffers_with_autosave() {
+    let path = env::temp_dir().join(format!(
+        "text-editor-reload-{}-autosave.txt",
+        std::process::id()
+    ));
+    fs::write(&path, "one").unwrap();
+    let doc = Document::open(path.to_str().unwrap()).unwrap();
+    let mut h = Headless::new(doc, 60, 8);
+
+    h.keys("ix<Esc>").unwrap();
+    h.focus(false);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "one");
+
+    h.command("set autosave").unwrap();
+    h.focus(false);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "xone");
+    assert!(!h.editor().doc().dirty());
+    drop(h);
+    let _ = fs::remove_file(&path);
+}
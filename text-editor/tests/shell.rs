@@ -0,0 +1,96 @@
+use text_editor::{headless::Headless, models::editor::Mode};
+
+#[test]
+fn range_filters_are_one_undoable_edit() {
+    let mut h = Headless::with_text("c\nb\na\nz", 40, 8);
+    h.command("1,3!sort").unwrap();
+    assert_eq!(h.text(), "a\nb\nc\nz");
+    assert_eq!(h.editor().message().unwrap().text, "3 lines filtered");
+    h.keys("u").unwrap();
+    assert_eq!(h.text(), "c\nb\na\nz");
+
+    h.command("%!tr a-z A-Z").unwrap();
+    assert_eq!(h.text(), "C\nB\nA\nZ");
+
+    // a failing filter keeps the lines
+    assert!(h.command("%!echo nope >&2; exit 1").is_err());
+    assert_eq!(h.text(), "C\nB\nA\nZ");
+    assert!(h.command("9!sort").is_err());
+}
+
+#[test]
+fn filter_operator_takes_the_lines_of_a_motion() {
+    let mut h = Headless::with_text("3\n1\n2\n0", 40, 8);
+    h.keys("!j").unwrap();
+    assert_eq!(h.mode(), Mode::Command);
+    h.keys("sort<CR>").unwrap();
+    assert_eq!(h.text(), "1\n3\n2\n0");
+
+    h.keys("!!echo x<CR>").unwrap();
+    assert_eq!(h.text(), "x\n3\n2\n0");
+
+    // the selected lines of visual mode
+    h.keys("jvj!sort -r<CR>").unwrap();
+    assert_eq!(h.text(), "x\n3\n2\n0");
+    h.keys("vj!sort<CR>").unwrap();
+    assert_eq!(h.text(), "x\n2\n3\n0");
+    assert_eq!(h.cursor(), (1, 0));
+}
+
+#[test]
+fn shell_output_is_shown_or_read_into_the_buffer() {
+    let mut h = Headless::with_text("one\ntwo", 40, 8);
+    h.command("r !printf 'a\\nb\\n'").unwrap();
+    assert_eq!(h.text(), "one\na\nb\ntwo");
+    assert_eq!(h.cursor(), (1, 0));
+    h.keys("u").unwrap();
+    assert_eq!(h.text(), "one\ntwo");
+
+    h.command("!echo out").unwrap();
+    assert_eq!(h.editor().message().unwrap().text, "out");
+    assert_eq!(h.text(), "one\ntwo");
+    assert!(h.command("!exit 2").is_err());
+    assert_eq!(h.editor().message().unwrap().text, "shell returned 2");
+}
+
+#[test]
+fn line_zero_reads_in_above_the_first_line() {
+    let mut h = Headless::with_text("one\ntwo", 40, 8);
+    h.keys("j").unwrap();
+    h.command("0r !echo top").unwrap();
+    assert_eq!(h.text(), "top\none\ntwo");
+    assert_eq!(h.cursor(), (0, 0));
+    h.command("$r !echo bottom").unwrap();
+    assert_eq!(h.text(), "top\none\ntwo\nbottom");
+    // other commands take line 0 as line 1
+    h.command("0,1!tr a-z A-Z").unwrap();
+    assert_eq!(h.text(), "TOP\none\ntwo\nbottom");
+}
+
+#[test]
+fn filter_operator_takes_g_and_counts() {
+    let mut h = Headless::with_text("d\nc\nb\na\nz", 40, 8);
+    h.keys("j!G").unwrap();
+    assert_eq!(h.screen()[7].trim_end(), ":.,.+3!");
+    h.keys("sort<CR>").unwrap();
+    assert_eq!(h.text(), "d\na\nb\nc\nz");
+
+    h.keys("!2jsort -r<CR>").unwrap();
+    assert_eq!(h.text(), "d\nc\nb\na\nz");
+    h.keys("!3!tr a-z A-Z<CR>").unwrap();
+    assert_eq!(h.text(), "d\nC\nB\nA\nz");
+}
+
+#[test]
+fn a_filter_that_prints_nothing_deletes_the_lines() {
+    let mut h = Headless::with_text("a\nb\nc", 40, 8);
+    h.command("2!true").unwrap();
+    assert_eq!(h.text(), "a\nc");
+    h.command("$!true").unwrap();
+    assert_eq!(h.text(), "a");
+    assert_eq!(h.cursor(), (0, 0));
+    h.command("%!true").unwrap();
+    assert_eq!(h.text(), "");
+    h.keys("uu").unwrap();
+    assert_eq!(h.text(), "a\nc");
+}
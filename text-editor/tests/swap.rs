@@ -0,0 +1,101 @@
+use std::{env, fs, path::PathBuf};
+
+use text_editor::{
+    headless::Headless,
+    models::{document::Document, message::Severity},
+};
+
+/// Makes a file holding "on disk" with a swap file next to it that was
+/// written by process `pid` and saved `text`.
+fn file_with_swap(name: &str, pid: u32, text: &str) -> (PathBuf, PathBuf) {
+    let path = env::temp_dir().join(format!(
+        "text-editor-swap-{}-{}.txt",
+        std::process::id(),
+        name
+    ));
+    fs::write(&path, "on disk").unwrap();
+    let swap = Document::swap_path_for(&path);
+    fs::write(
+        &swap,
+        format!(
+            "# SWAP\n# pid={}\n# path={}\n# cursor=0,0\n# fileformat=dos\n# text\n{}\n",
+            pid,
+            path.display(),
+            text
+        ),
+    )
+    .unwrap();
+    (path, swap)
+}
+
+fn remove(path: &PathBuf, swap: &PathBuf) {
+    let _ = fs::remove_file(swap);
+    let _ = fs::remove_file(path.with_extension("tmp"));
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn opening_a_file_with_a_swap_file_recovers_it_and_says_so() {
+    // no process has id 0, so the editor that wrote it is gone
+    let (path, swap) = file_with_swap("recover", 0, "#[derive(Debug)]\n# heading\nunsaved");
+
+    let mut h = Headless::with_text("", 60, 8);
+    h.command(&format!("sp {}", path.display())).unwrap();
+    let message = h.editor().message().unwrap();
+    assert_eq!(message.severity, Severity::Warn);
+    assert_eq!(
+        message.text,
+        format!("\"{}\" recovered from swap file", path.display())
+    );
+    assert_eq!(h.text(), "#[derive(Debug)]\n# heading\nunsaved");
+    assert_eq!(h.editor().doc().line_ending().name(), "dos");
+    assert!(h.editor().doc().dirty());
+    remove(&path, &swap);
+}
+
+#[test]
+fn a_swap_file_of_a_running_editor_is_not_recovered() {
+    let (path, swap) = file_with_swap("running", std::os::unix::process::parent_id(), "theirs");
+
+    let mut h = Headless::with_text("", 60, 8);
+    assert_eq!(
+        h.command(&format!("sp {}", path.display())),
+        Err(format!("\"{}\": Swap file exists", path.display()))
+    );
+    assert_eq!(h.text(), "");
+    assert!(swap.exists());
+    remove(&path, &swap);
+}
+
+#[test]
+fn opening_an_open_file_under_another_spelling_shows_its_buffer() {
+    let path = env::temp_dir().join(format!("text-editor-swap-{}-open.txt", std::process::id()));
+    fs::write(&path, "one").unwrap();
+    let doc = Document::open(path.to_str().unwrap()).unwrap();
+    let mut h = Headless::new(doc, 60, 8);
+    h.keys("ix<Esc>").unwrap();
+
+    let dir = path.parent().unwrap();
+    let other = dir
+        .join("..")
+        .join(dir.file_name().unwrap())
+        .join(".")
+        .join(path.file_name().unwrap());
+    h.command(&format!("sp {}", other.display())).unwrap();
+    assert_eq!(h.editor().buffers().len(), 1);
+    assert_eq!(h.text(), "xone");
+    assert!(Document::swap_path_for(&path).exists());
+    drop(h);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn a_file_that_cannot_be_opened_leaves_no_swap_file() {
+    let path = env::temp_dir().join(format!(
+        "text-editor-swap-{}-missing.txt",
+        std::process::id()
+    ));
+    let mut h = Headless::with_text("", 60, 8);
+    assert!(h.command(&format!("sp {}", path.display())).is_err());
+    assert!(!Document::swap_path_for(&path).exists());
+}